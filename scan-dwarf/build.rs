@@ -0,0 +1,49 @@
+// Identify the source the scanner is built from, so that cached results
+// from any other source are ignored without anyone having to remember to
+// say so.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+/// Every file under `dir`, in no particular order
+fn files(dir: &Path, ret: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files(&path, ret)?;
+        } else {
+            ret.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+
+    let mut paths = vec![PathBuf::from("Cargo.toml")];
+    files(Path::new("src"), &mut paths)?;
+    paths.sort();
+
+    let mut hasher = Sha256::new();
+    for path in &paths {
+        let data = fs::read(path)?;
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(&data);
+    }
+
+    let hash = hasher.finalize();
+    let hex = hash[..8]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    println!("cargo:rustc-env=SCAN_DWARF_SOURCE={hex}");
+
+    Ok(())
+}
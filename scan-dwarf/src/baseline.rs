@@ -0,0 +1,282 @@
+//! Known findings, which are not to be reported again
+//!
+//! A baseline records each diagnostic by the object, function and code it
+//! concerns rather than by address, so that it survives rebuilding, and how
+//! many there are of each.  Findings in the baseline are suppressed, as many
+//! of each as it records, and those in the baseline which are no longer
+//! found are reported as fixed.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{Diagnostic, DiagnosticKind, ScanReport};
+
+/// The version of the baseline format written by this scanner
+const BASELINE_VERSION: u32 = 2;
+
+/// A finding
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Entry {
+    pub object: String,
+    pub function: Option<String>,
+    pub code: String,
+}
+
+fn one() -> usize {
+    1
+}
+
+/// A finding, and how many times it was found
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Counted {
+    #[serde(flatten)]
+    entry: Entry,
+    /// Absent before version 2, which recorded each finding once
+    #[serde(default = "one")]
+    count: usize,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct File {
+    version: u32,
+    entries: Vec<Counted>,
+}
+
+/// A set of known findings
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    entries: BTreeMap<Entry, usize>,
+    /// The objects we have been asked about, and how many of each entry we
+    /// matched
+    objects: HashSet<String>,
+    matched: HashMap<Entry, usize>,
+}
+
+/// The entry for `diag`, in the object at `path`
+///
+/// Failures to examine an object are never known, lest they hide everything
+/// else.
+fn entry(path: &str, diag: &Diagnostic) -> Option<Entry> {
+    if let DiagnosticKind::Failed(_) = diag.kind {
+        return None;
+    }
+
+    Some(Entry {
+        object: path.to_string(),
+        function: diag.function.clone(),
+        code: diag.code().to_string(),
+    })
+}
+
+impl Baseline {
+    /// Read the baseline at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let file: File =
+            serde_json::from_slice(&data).with_context(|| format!("parsing {}", path.display()))?;
+
+        if file.version > BASELINE_VERSION {
+            return Err(anyhow!(
+                "{} has baseline version {}, but we only understand {BASELINE_VERSION}",
+                path.display(),
+                file.version
+            ));
+        }
+
+        let mut entries = BTreeMap::new();
+        for x in file.entries {
+            *entries.entry(x.entry).or_default() += x.count;
+        }
+
+        Ok(Baseline {
+            entries,
+            ..Baseline::default()
+        })
+    }
+
+    /// Write this baseline to `path`
+    pub fn store(&self, path: &Path) -> Result<()> {
+        let file = File {
+            version: BASELINE_VERSION,
+            entries: self
+                .entries
+                .iter()
+                .map(|(entry, count)| Counted {
+                    entry: entry.clone(),
+                    count: *count,
+                })
+                .collect(),
+        };
+
+        let mut data = serde_json::to_vec_pretty(&file)?;
+        data.push(b'\n');
+        fs::write(path, data).with_context(|| format!("writing {}", path.display()))
+    }
+
+    /// Record each finding in `report`, for the object at `path`
+    pub fn add(&mut self, path: &str, report: &ScanReport) {
+        for x in report.diagnostics.iter().filter_map(|x| entry(path, x)) {
+            *self.entries.entry(x).or_default() += 1;
+        }
+    }
+
+    /// Remove the findings in `report`, for the object at `path`, which are
+    /// already known, up to as many of each as are known
+    pub fn suppress(&mut self, path: &str, report: &mut ScanReport) {
+        self.objects.insert(path.to_string());
+
+        report.diagnostics.retain(|diag| {
+            let Some(x) = entry(path, diag) else {
+                return true;
+            };
+            let known = self.entries.get(&x).copied().unwrap_or(0);
+            let matched = self.matched.entry(x).or_default();
+
+            if *matched < known {
+                *matched += 1;
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// The known findings, in the objects we have been asked about, which
+    /// were not found, and how many fewer times each was found than known
+    pub fn fixed(&self) -> impl Iterator<Item = (&Entry, usize)> {
+        self.entries
+            .iter()
+            .filter(|(x, _)| self.objects.contains(&x.object))
+            .map(|(x, count)| (x, count - self.matched.get(x).copied().unwrap_or(0)))
+            .filter(|(_, n)| *n != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::Source;
+
+    fn diag(function: &str, kind: DiagnosticKind) -> Diagnostic {
+        Diagnostic {
+            die: Some(1),
+            function: Some(function.to_string()),
+            source: Source::default(),
+            severity: kind.default_severity(),
+            kind,
+        }
+    }
+
+    fn report(diagnostics: Vec<Diagnostic>) -> ScanReport {
+        ScanReport {
+            diagnostics,
+            ..Default::default()
+        }
+    }
+
+    fn clobbered(function: &str, pc: u64) -> Diagnostic {
+        diag(function, DiagnosticKind::SavedClobbered { pc })
+    }
+
+    fn entry(object: &str, function: &str, code: &str) -> Entry {
+        Entry {
+            object: object.to_string(),
+            function: Some(function.to_string()),
+            code: code.to_string(),
+        }
+    }
+
+    #[test]
+    fn suppress() {
+        let mut baseline = Baseline::default();
+        baseline.add(
+            "a",
+            &report(vec![
+                clobbered("f", 0x10),
+                clobbered("f", 0x20),
+                diag("g", DiagnosticKind::NoSavedArgs { nparams: 1 }),
+                diag("h", DiagnosticKind::Failed("oops".to_string())),
+            ]),
+        );
+
+        // Found at other addresses, once more than known, and failing
+        let mut found = report(vec![
+            clobbered("f", 0x14),
+            clobbered("f", 0x24),
+            clobbered("f", 0x34),
+            diag("h", DiagnosticKind::Failed("oops".to_string())),
+        ]);
+        baseline.suppress("a", &mut found);
+
+        assert_eq!(found.diagnostics.len(), 2);
+        assert!(matches!(
+            found.diagnostics[0].kind,
+            DiagnosticKind::SavedClobbered { pc: 0x34 }
+        ));
+        assert!(matches!(
+            found.diagnostics[1].kind,
+            DiagnosticKind::Failed(_)
+        ));
+
+        // `g` was not found, and nothing was asked of `b`
+        baseline.add("b", &report(vec![clobbered("f", 0x10)]));
+        assert_eq!(
+            baseline.fixed().collect::<Vec<_>>(),
+            vec![(&entry("a", "g", "no-saved-args"), 1)]
+        );
+    }
+
+    #[test]
+    fn fewer() {
+        let mut baseline = Baseline::default();
+        baseline.add(
+            "a",
+            &report(vec![clobbered("f", 0x10), clobbered("f", 0x20)]),
+        );
+
+        let mut found = report(vec![clobbered("f", 0x10)]);
+        baseline.suppress("a", &mut found);
+        assert!(found.diagnostics.is_empty());
+        assert_eq!(
+            baseline.fixed().collect::<Vec<_>>(),
+            vec![(&entry("a", "f", "saved-clobbered"), 1)]
+        );
+    }
+
+    #[test]
+    fn files() {
+        let path = env::temp_dir().join(format!("scan-dwarf-baseline-{}", std::process::id()));
+
+        let mut baseline = Baseline::default();
+        baseline.add(
+            "a",
+            &report(vec![clobbered("f", 0x10), clobbered("f", 0x20)]),
+        );
+        baseline.store(&path).unwrap();
+        let mut loaded = Baseline::load(&path).unwrap();
+        assert_eq!(loaded.entries, baseline.entries);
+
+        // Version 1 recorded each entry once, each line counting as one
+        fs::write(
+            &path,
+            r#"{"version":1,"entries":[
+                {"object":"a","function":"f","code":"saved-clobbered"},
+                {"object":"a","function":"f","code":"saved-clobbered"}]}"#,
+        )
+        .unwrap();
+        loaded = Baseline::load(&path).unwrap();
+        assert_eq!(loaded.entries, baseline.entries);
+
+        fs::write(&path, r#"{"version":3,"entries":[]}"#).unwrap();
+        let err = Baseline::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(err.is_err());
+    }
+}
@@ -0,0 +1,200 @@
+// An on-disk cache of what we found in each object, so that rescanning an
+// unchanged tree need not parse any DWARF.
+//
+// Entries are keyed by a hash of the sections our results are derived from,
+// along with the object's build-id if it has one.  Entries written by a
+// scanner built from different source are ignored.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use object::{Object, ObjectSection};
+use sha2::{Digest, Sha256};
+
+use crate::ScanReport;
+
+/// The version of the scanner that wrote an entry, any change invalidates it
+///
+/// The trailing hash is that of the source the scanner was built from, as
+/// the build script found it, so that any change to what we find or how we
+/// record it invalidates every entry.
+const SCANNER_VERSION: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    " ",
+    env!("CARGO_PKG_VERSION"),
+    " ",
+    env!("SCAN_DWARF_SOURCE")
+);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    version: String,
+    report: ScanReport,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The key under which results for `object` are cached
+///
+/// This is a hash of the debug information and text from which our results
+/// are derived, along with their relocations and the symbols those refer to,
+/// as in a relocatable object they are not filled in.  The build-id alone
+/// will not do, as our own rewriters keep it while moving every entry in
+/// the debug information.
+pub fn key(object: &object::File) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for section in object.sections() {
+        let name = section.name()?;
+        let target = name
+            .strip_prefix(".rela")
+            .or_else(|| name.strip_prefix(".rel"))
+            .unwrap_or(name);
+        if target.starts_with(".debug_") || target.starts_with(".text") || name == ".symtab" {
+            let data = section.uncompressed_data()?;
+            hasher.update(name.as_bytes());
+            hasher.update((data.len() as u64).to_le_bytes());
+            hasher.update(&data);
+        }
+    }
+
+    let hash = hex(&hasher.finalize());
+    match object.build_id()? {
+        Some(id) => Ok(format!("buildid-{}-{hash}", hex(id))),
+        None => Ok(format!("sha256-{hash}")),
+    }
+}
+
+fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{key}.json"))
+}
+
+/// The cached report for `key`, if there is one we can trust
+pub fn load(dir: &Path, key: &str) -> Option<ScanReport> {
+    let data = fs::read(entry_path(dir, key)).ok()?;
+    let entry: Entry = serde_json::from_slice(&data).ok()?;
+
+    if entry.version == SCANNER_VERSION {
+        Some(entry.report)
+    } else {
+        None
+    }
+}
+
+/// Cache `report` under `key`
+///
+/// The entry is written to a temporary file and renamed into place, so that
+/// concurrent scans never see a partial entry.
+pub fn store(dir: &Path, key: &str, report: &ScanReport) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+
+    let path = entry_path(dir, key);
+    let tmp = dir.join(format!(".{key}.{}", std::process::id()));
+    let entry = Entry {
+        version: SCANNER_VERSION.to_string(),
+        report: report.clone(),
+    };
+
+    let mut file = fs::File::create(&tmp).with_context(|| format!("creating {}", tmp.display()))?;
+    file.write_all(&serde_json::to_vec(&entry)?)?;
+    fs::rename(&tmp, &path).with_context(|| format!("renaming into {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use object::elf::ET_EXEC;
+
+    use super::*;
+    use crate::elf;
+    use crate::index::tests::function;
+    use crate::Status;
+
+    /// A GNU build-id note of `id`
+    fn build_id(id: &[u8]) -> Vec<u8> {
+        let mut ret = Vec::new();
+        ret.extend_from_slice(&4u32.to_le_bytes());
+        ret.extend_from_slice(&(id.len() as u32).to_le_bytes());
+        ret.extend_from_slice(&object::elf::NT_GNU_BUILD_ID.to_le_bytes());
+        ret.extend_from_slice(b"GNU\0");
+        ret.extend_from_slice(id);
+        ret
+    }
+
+    fn key_of(sections: &[(&str, &[u8])]) -> String {
+        let data = elf::tests::object_with(ET_EXEC, &[0xc3; 0x10], sections);
+        key(&object::File::parse(&*data).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn keys() {
+        let note = build_id(&[0xab, 0xcd, 0xef, 0x01]);
+        let info = [1, 2, 3, 4];
+        let moved = [1, 2, 4, 3];
+
+        let with_id = key_of(&[(".note.gnu.build-id", &note), (".debug_info", &info)]);
+        assert!(with_id.starts_with("buildid-abcdef01-"), "{with_id}");
+        assert_eq!(
+            with_id,
+            key_of(&[(".note.gnu.build-id", &note), (".debug_info", &info)])
+        );
+
+        // As when the locations are rewritten, keeping the build-id
+        assert_ne!(
+            with_id,
+            key_of(&[(".note.gnu.build-id", &note), (".debug_info", &moved)])
+        );
+
+        // As when the saved arguments are added, which we never read
+        assert_eq!(
+            with_id,
+            key_of(&[
+                (".note.gnu.build-id", &note),
+                (".debug_info", &info),
+                (".SUNW_saveargs", &[0; 8]),
+            ])
+        );
+
+        let without = key_of(&[(".debug_info", &info)]);
+        assert!(without.starts_with("sha256-"), "{without}");
+        assert_ne!(without, key_of(&[(".debug_info", &moved)]));
+        assert_ne!(without, key_of(&[(".debug_line", &info)]));
+    }
+
+    #[test]
+    fn entries() {
+        let dir = env::temp_dir().join(format!("scan-dwarf-cache-{}", std::process::id()));
+        let report = ScanReport {
+            functions: vec![function("f", 0x1000, 0x1040, Status::Saved { offset: -40 })],
+            ..Default::default()
+        };
+
+        assert!(load(&dir, "k").is_none());
+        store(&dir, "k", &report).unwrap();
+        let loaded = load(&dir, "k").unwrap();
+        assert_eq!(loaded.functions.len(), 1);
+        assert_eq!(loaded.functions[0].name, "f");
+        assert!(load(&dir, "other").is_none());
+
+        // What another scanner wrote is ignored
+        let mut entry: serde_json::Value =
+            serde_json::from_slice(&fs::read(entry_path(&dir, "k")).unwrap()).unwrap();
+        entry["version"] = "scan-dwarf 0.0.0 elsewhere".into();
+        fs::write(entry_path(&dir, "k"), entry.to_string()).unwrap();
+        let stale = load(&dir, "k");
+
+        // As is what is not an entry at all
+        fs::write(entry_path(&dir, "k"), "{").unwrap();
+        let damaged = load(&dir, "k");
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(stale.is_none());
+        assert!(damaged.is_none());
+    }
+}
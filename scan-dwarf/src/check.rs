@@ -0,0 +1,442 @@
+//! Rules about what each part of a tree must achieve, and the checking of
+//! results against them
+//!
+//! Rules are read from a TOML file, as a list of `[[rule]]` tables.  Each
+//! may restrict itself to objects, source files, or functions matching a
+//! glob, and may set
+//!
+//! - `min-coverage`, the percentage of each function in which its saved
+//!   arguments must be found,
+//! - `array`, whether functions with integer parameters are `required` to
+//!   save them, `forbidden` from doing so, or it is `optional`,
+//! - `diagnostics`, a table giving the level (`allow`, `warn`, or `deny`) of
+//!   each diagnostic code, as `--allow`, `--warn`, and `--deny` would,
+//!   including `missing-array`, `forbidden-array` and `low-coverage`, by
+//!   which functions failing the rules above are reported.
+//!
+//! Where several rules match, later rules override earlier ones.  A source
+//! file is matched by its path, relative paths being joined to the directory
+//! in which its unit was compiled, as `--source` matches it.
+//!
+//! ```toml
+//! [[rule]]
+//! path = "**/kernel/**"
+//! array = "required"
+//! min-coverage = 90.0
+//!
+//! [[rule]]
+//! path = "**/lib/libexempt.so*"
+//! array = "optional"
+//! diagnostics = { no-saved-args = "allow" }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::policy::{Level, Policy};
+use crate::schema::{self, FunctionStatus, Record};
+use crate::Severity;
+
+/// Whether functions with integer parameters should save them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Presence {
+    Required,
+    #[default]
+    Optional,
+    Forbidden,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Rule {
+    path: Option<String>,
+    source: Option<String>,
+    function: Option<String>,
+    min_coverage: Option<f64>,
+    array: Option<Presence>,
+    #[serde(default)]
+    diagnostics: BTreeMap<String, Level>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    #[serde(default, rename = "rule")]
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Matcher {
+    path: Option<GlobMatcher>,
+    source: Option<GlobMatcher>,
+    function: Option<GlobMatcher>,
+    rule: Rule,
+}
+
+/// What is expected of a function
+#[derive(Debug, Clone, Default)]
+pub struct Expectations {
+    pub min_coverage: Option<f64>,
+    pub array: Presence,
+    pub policy: Policy,
+}
+
+/// The rules of a policy file
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: Vec<Matcher>,
+}
+
+fn glob(pattern: &Option<String>, literal_separator: bool) -> Result<Option<GlobMatcher>> {
+    pattern
+        .as_deref()
+        .map(|x| {
+            Ok(GlobBuilder::new(x)
+                .literal_separator(literal_separator)
+                .build()
+                .with_context(|| format!("invalid glob: {x}"))?
+                .compile_matcher())
+        })
+        .transpose()
+}
+
+/// True if `glob` is absent, or `value` is known and matches it
+fn matches(glob: &Option<GlobMatcher>, value: Option<impl AsRef<Path>>) -> bool {
+    match glob {
+        None => true,
+        Some(glob) => value.is_some_and(|x| glob.is_match(x)),
+    }
+}
+
+impl Rules {
+    /// Read the rules in the TOML file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        Rules::parse(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// Parse rules from TOML `text`
+    pub fn parse(text: &str) -> Result<Self> {
+        let file: File = toml::from_str(text)?;
+        let mut rules = Vec::with_capacity(file.rules.len());
+
+        for (n, rule) in file.rules.into_iter().enumerate() {
+            let mut policy = Policy::default();
+            for (code, level) in &rule.diagnostics {
+                policy
+                    .set(code, *level)
+                    .with_context(|| format!("rule {}", n + 1))?;
+            }
+
+            rules.push(Matcher {
+                path: glob(&rule.path, true)?,
+                source: glob(&rule.source, true)?,
+                function: glob(&rule.function, false)?,
+                rule,
+            });
+        }
+
+        Ok(Rules { rules })
+    }
+
+    /// What is expected of `function`, declared in the file at `source`, in
+    /// the object at `path`
+    ///
+    /// Anything not known to match a rule is taken not to, so that which is
+    /// expected of an object as a whole is that of rules on objects alone.
+    pub fn expectations(
+        &self,
+        path: &str,
+        source: Option<&Path>,
+        function: Option<&str>,
+    ) -> Expectations {
+        let mut ret = Expectations::default();
+
+        for m in &self.rules {
+            if !matches(&m.path, Some(path))
+                || !matches(&m.source, source)
+                || !matches(&m.function, function)
+            {
+                continue;
+            }
+
+            if let Some(x) = m.rule.min_coverage {
+                ret.min_coverage = Some(x);
+            }
+            if let Some(x) = m.rule.array {
+                ret.array = x;
+            }
+            for (code, level) in &m.rule.diagnostics {
+                // Validated when the rules were parsed
+                ret.policy.set(code, *level).unwrap();
+            }
+        }
+
+        ret
+    }
+}
+
+fn violation(
+    func: &schema::Function,
+    expect: &Expectations,
+    code: &str,
+    message: String,
+) -> Option<schema::Diagnostic> {
+    let severity = match expect.policy.level_of(code, Severity::Error) {
+        Level::Allow => return None,
+        Level::Warn => Severity::Warning,
+        Level::Deny => Severity::Error,
+    };
+
+    Some(schema::Diagnostic {
+        path: func.path.clone(),
+        die: Some(func.die),
+        function: Some(func.name.clone()),
+        unit: func.unit.clone(),
+        comp_dir: func.comp_dir.clone(),
+        file: func.file.clone(),
+        line: func.line,
+        code: code.to_string(),
+        severity,
+        message,
+    })
+}
+
+/// Check `records` against `rules`
+///
+/// The result is each diagnostic in `records` which is not allowed, with the
+/// severity the rules give it, and an error for each function which does not
+/// meet expectations, unless the rules say otherwise of its code, in the
+/// order of `records`.
+pub fn check(rules: &Rules, records: &[Record]) -> Vec<schema::Diagnostic> {
+    let mut ret = Vec::new();
+
+    for record in records {
+        match record {
+            Record::Function(func) => {
+                let source = func.source().path();
+                let expect = rules.expectations(&func.path, source.as_deref(), Some(&func.name));
+
+                match (func.status, expect.array) {
+                    (FunctionStatus::Missing, Presence::Required) => ret.extend(violation(
+                        func,
+                        &expect,
+                        "missing-array",
+                        format!(
+                            "{}(): {} parameters but no saved args, which are required",
+                            func.name, func.nparams
+                        ),
+                    )),
+                    (FunctionStatus::Saved, Presence::Forbidden) => ret.extend(violation(
+                        func,
+                        &expect,
+                        "forbidden-array",
+                        format!("{}() saves its arguments, which is forbidden", func.name),
+                    )),
+                    _ => (),
+                }
+
+                if let Some(min) = expect.min_coverage {
+                    if func.status == FunctionStatus::Saved && func.coverage < min {
+                        ret.extend(violation(
+                            func,
+                            &expect,
+                            "low-coverage",
+                            format!(
+                                "{}() saved arguments valid in {:2.2}%, less than the required {min}%",
+                                func.name, func.coverage
+                            ),
+                        ));
+                    }
+                }
+            }
+            Record::Diagnostic(diag) => {
+                let source = diag.source().path();
+                let expect =
+                    rules.expectations(&diag.path, source.as_deref(), diag.function.as_deref());
+
+                let severity = match expect.policy.level_of(&diag.code, diag.severity) {
+                    Level::Allow => continue,
+                    Level::Warn => Severity::Warning,
+                    Level::Deny => Severity::Error,
+                };
+
+                ret.push(schema::Diagnostic {
+                    severity,
+                    ..diag.clone()
+                });
+            }
+            Record::Skipped(_) | Record::Summary(_) => (),
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::function;
+    use crate::{ScanReport, Status};
+
+    const RULES: &str = r#"
+        [[rule]]
+        path = "**/kernel/**"
+        array = "required"
+        min-coverage = 90.0
+        diagnostics = { no-saved-args = "deny" }
+
+        [[rule]]
+        path = "**/kernel/exempt/**"
+        array = "optional"
+        diagnostics = { no-saved-args = "allow" }
+
+        [[rule]]
+        source = "/src/**/legacy.c"
+        array = "forbidden"
+
+        [[rule]]
+        function = "fast_*"
+        diagnostics = { low-coverage = "warn" }
+    "#;
+
+    /// The record of a function `name`, from `/src/lib/<file>`, in the
+    /// object at `path`, saving its arguments if `saved`, and valid in 37.5%
+    /// of it
+    fn func(path: &str, file: &str, name: &str, saved: bool) -> Record {
+        let status = match saved {
+            true => Status::Saved { offset: -40 },
+            false => Status::Missing,
+        };
+        let mut func = function(name, 0x1000, 0x1040, status);
+        func.source.comp_dir = Some("/src/lib".to_string());
+        func.source.file = Some(file.to_string());
+        let report = ScanReport {
+            functions: vec![func],
+            ..Default::default()
+        };
+
+        schema::records(path, &report).remove(0)
+    }
+
+    fn codes(found: &[schema::Diagnostic]) -> Vec<(&str, Severity)> {
+        found
+            .iter()
+            .map(|x| (x.code.as_str(), x.severity))
+            .collect()
+    }
+
+    #[test]
+    fn precedence() {
+        let rules = Rules::parse(RULES).unwrap();
+
+        let expect = rules.expectations("/kernel/fs", None, Some("f"));
+        assert_eq!(expect.array, Presence::Required);
+        assert_eq!(expect.min_coverage, Some(90.0));
+        assert_eq!(
+            expect.policy.level_of("no-saved-args", Severity::Warning),
+            Level::Deny
+        );
+
+        // The later rule overrides only what it says
+        let expect = rules.expectations("/kernel/exempt/fs", None, Some("f"));
+        assert_eq!(expect.array, Presence::Optional);
+        assert_eq!(expect.min_coverage, Some(90.0));
+        assert_eq!(
+            expect.policy.level_of("no-saved-args", Severity::Warning),
+            Level::Allow
+        );
+
+        // What is not known does not match
+        let expect = rules.expectations("/usr/lib/libc.so", None, None);
+        assert_eq!(expect.array, Presence::Optional);
+        assert_eq!(expect.min_coverage, None);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Rules::parse("[[rule]]\narray = \"sometimes\"").is_err());
+        assert!(Rules::parse("[[rule]]\nname = \"f\"").is_err());
+        assert!(Rules::parse("[[rule]]\ndiagnostics = { no-such-thing = \"deny\" }").is_err());
+        assert!(Rules::parse("[[rule]]\npath = \"[\"").is_err());
+    }
+
+    #[test]
+    fn functions() {
+        let rules = Rules::parse(RULES).unwrap();
+        let records = [
+            func("/kernel/fs", "fs.c", "missing", false),
+            func("/kernel/fs", "fs.c", "low", true),
+            func("/kernel/fs", "fs.c", "fast_path", true),
+            func("/kernel/exempt/fs", "fs.c", "exempt", false),
+            // Matched by its path joined to its compilation directory
+            func("/usr/lib/libc.so", "legacy.c", "legacy", true),
+            func("/usr/lib/libc.so", "/elsewhere/legacy.c", "moved", true),
+        ];
+
+        let found = check(&rules, &records);
+        assert_eq!(
+            codes(&found),
+            vec![
+                ("missing-array", Severity::Error),
+                ("low-coverage", Severity::Error),
+                ("low-coverage", Severity::Warning),
+                ("forbidden-array", Severity::Error),
+            ]
+        );
+        assert_eq!(
+            found
+                .iter()
+                .map(|x| x.function.as_deref())
+                .collect::<Vec<_>>(),
+            vec![
+                Some("missing"),
+                Some("low"),
+                Some("fast_path"),
+                Some("legacy")
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostics() {
+        let rules = Rules::parse(RULES).unwrap();
+        let diag = |path: &str| {
+            Record::Diagnostic(schema::Diagnostic {
+                path: path.to_string(),
+                die: Some(1),
+                function: Some("f".to_string()),
+                unit: None,
+                comp_dir: None,
+                file: None,
+                line: None,
+                code: "no-saved-args".to_string(),
+                severity: Severity::Warning,
+                message: "f(): 1 parameters but no saved args".to_string(),
+            })
+        };
+
+        let found = check(
+            &rules,
+            &[
+                diag("/kernel/fs"),
+                diag("/kernel/exempt/fs"),
+                diag("/usr/lib/libc.so"),
+            ],
+        );
+        assert_eq!(
+            codes(&found),
+            vec![
+                ("no-saved-args", Severity::Error),
+                ("no-saved-args", Severity::Warning),
+            ]
+        );
+        assert_eq!(found[1].path, "/usr/lib/libc.so");
+    }
+}
@@ -0,0 +1,1509 @@
+//! Just enough of the machine code of a function to follow its arguments
+//!
+//! Each instruction is decoded into the few [`Op`]s that matter to where
+//! arguments go: moves between registers and memory, changes to the stack
+//! pointer, and transfers of control, with registers numbered as DWARF
+//! numbers them.  A [`State`] then follows, from the entry of a function,
+//! what is in each register and in each part of the stack written, in terms
+//! of what each argument register held at entry and where the stack pointer
+//! was.  Anything else an instruction does makes what it writes unknown.
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{anyhow, Result};
+use capstone::arch::arm64::{Arm64OperandType, Arm64Shift};
+use capstone::arch::x86::{X86Insn, X86OperandType};
+use capstone::prelude::*;
+use capstone::InsnGroupType;
+use gimli::Register;
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind};
+
+use crate::CodeRange;
+
+/// The architectures whose code we understand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    pub(crate) fn of(object: &object::File) -> Result<Arch> {
+        match object.architecture() {
+            object::Architecture::X86_64 => Ok(Arch::X86_64),
+            object::Architecture::Aarch64 => Ok(Arch::Aarch64),
+            x => Err(anyhow!("cannot decode code for {x:?}")),
+        }
+    }
+
+    /// The registers in which integer arguments are passed, in order
+    pub(crate) fn argument_registers(self) -> &'static [Register] {
+        match self {
+            // %rdi, %rsi, %rdx, %rcx, %r8, %r9
+            Arch::X86_64 => &[
+                Register(5),
+                Register(4),
+                Register(1),
+                Register(2),
+                Register(8),
+                Register(9),
+            ],
+            // x0-x7
+            Arch::Aarch64 => &[
+                Register(0),
+                Register(1),
+                Register(2),
+                Register(3),
+                Register(4),
+                Register(5),
+                Register(6),
+                Register(7),
+            ],
+        }
+    }
+
+    pub(crate) fn stack_pointer(self) -> Register {
+        match self {
+            Arch::X86_64 => Register(7),
+            Arch::Aarch64 => Register(31),
+        }
+    }
+
+    /// True if a call may change `reg`
+    fn clobbered_by_call(self, reg: Register) -> bool {
+        match self {
+            // Everything but %rbx, %rbp, %rsp and %r12-%r15
+            Arch::X86_64 => !matches!(reg.0, 3 | 6 | 7 | 12..=15),
+            // Everything but x19-x29, sp, and d8-d15
+            Arch::Aarch64 => !matches!(reg.0, 19..=29 | 31 | 72..=79),
+        }
+    }
+}
+
+/// A place in memory, at `disp` from the sum of two registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Mem {
+    /// None if there is no base register, or it is the program counter
+    pub base: Option<Register>,
+    pub index: Option<Register>,
+    pub disp: i64,
+}
+
+/// Something an instruction reads or writes, of a size in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operand {
+    Reg(Register, u8),
+    Imm(i64),
+    Mem(Mem, u8),
+}
+
+/// How a value is made wider
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Extend {
+    Sign,
+    Zero,
+}
+
+/// Something an instruction does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    /// `dst` becomes `src`, made wider as `extend` says if it is smaller,
+    /// and made wider as a write to part of a register is, if `dst` is
+    /// such a part
+    Move {
+        dst: Operand,
+        src: Operand,
+        extend: Option<Extend>,
+    },
+    /// `dst` becomes the address of `mem`
+    Address {
+        dst: Register,
+        mem: Mem,
+    },
+    /// `value` is added to `dst`
+    Add {
+        dst: Register,
+        value: i64,
+    },
+    /// `size` bytes of `mem` are written with something we do not follow
+    Clobber {
+        mem: Mem,
+        size: u8,
+    },
+    /// Memory from the address in `dst` onward is written `size` bytes at a
+    /// time, as many times as `count` says if it is repeated
+    Fill {
+        dst: Register,
+        count: Option<Register>,
+        size: u8,
+    },
+    Call,
+    Return,
+    /// Control goes to `target`, if it is known, or if the jump is
+    /// conditional perhaps to the next instruction
+    Jump {
+        target: Option<u64>,
+        conditional: bool,
+    },
+}
+
+/// An instruction, and what it does
+#[derive(Debug, Clone)]
+pub(crate) struct Insn {
+    pub address: u64,
+    pub text: String,
+    pub ops: Vec<Op>,
+    /// Every register the instruction writes, whether by its ops or not
+    pub writes: Vec<Register>,
+}
+
+impl Insn {
+    /// True if control may continue to the next instruction
+    pub(crate) fn falls_through(&self) -> bool {
+        !self.ops.iter().any(|x| {
+            matches!(
+                x,
+                Op::Return
+                    | Op::Jump {
+                        conditional: false,
+                        ..
+                    }
+            )
+        })
+    }
+
+    /// Where else control may go, if it is known
+    pub(crate) fn target(&self) -> Option<u64> {
+        self.ops.iter().find_map(|x| match x {
+            Op::Jump { target, .. } => *target,
+            _ => None,
+        })
+    }
+}
+
+/// The code of the function `name` at `range`, if it is in a section of code
+///
+/// In a relocatable object every section begins at 0, so where several
+/// sections of code hold `range` the one meant is that of the symbol for the
+/// function, to which its address was relocated, and if there is no such
+/// symbol, we do not guess.
+pub(crate) fn function_code<'a>(
+    object: &object::File<'a>,
+    name: &str,
+    range: &CodeRange,
+) -> Option<&'a [u8]> {
+    let mut sections = object.sections().filter(|x| {
+        x.kind() == SectionKind::Text
+            && x.address() <= range.start
+            && range.end <= x.address() + x.size()
+    });
+    let first = sections.next()?;
+
+    let section = if sections.next().is_none() {
+        first
+    } else {
+        let index = object
+            .symbols()
+            .find(|x| x.is_definition() && x.address() == range.start && x.name() == Ok(name))?
+            .section_index()?;
+        object.section_by_index(index).ok()?
+    };
+
+    let data = section.data().ok()?;
+    let start = (range.start - section.address()) as usize;
+    data.get(start..start + (range.end - range.start) as usize)
+}
+
+/// A capstone able to disassemble code for `arch`
+pub(crate) fn disassembler(arch: Arch) -> Result<Capstone> {
+    match arch {
+        Arch::X86_64 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .syntax(arch::x86::ArchSyntax::Att)
+            .build(),
+        Arch::Aarch64 => Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .build(),
+    }
+    .map_err(|x| anyhow!("{x}"))
+}
+
+/// Decodes the instructions of functions
+pub(crate) struct Decoder {
+    arch: Arch,
+    cs: Capstone,
+}
+
+impl Decoder {
+    pub(crate) fn new(arch: Arch) -> Result<Decoder> {
+        let mut cs = disassembler(arch)?;
+        cs.set_detail(true).map_err(|x| anyhow!("{x}"))?;
+        Ok(Decoder { arch, cs })
+    }
+
+    /// Decode `code`, which is at `address`, stopping at anything which is
+    /// not an instruction
+    pub(crate) fn decode(&self, code: &[u8], address: u64) -> Result<Vec<Insn>> {
+        let insns = self
+            .cs
+            .disasm_all(code, address)
+            .map_err(|x| anyhow!("{x}"))?;
+
+        let mut ret = Vec::new();
+        for insn in insns.iter() {
+            let detail = self.cs.insn_detail(insn).map_err(|x| anyhow!("{x}"))?;
+            let mnemonic = insn.mnemonic().unwrap_or("");
+            let (ops, mut writes) = match self.arch {
+                Arch::X86_64 => self.x86(insn, &detail),
+                Arch::Aarch64 => self.aarch64(mnemonic, &detail),
+            };
+
+            writes.extend(
+                detail
+                    .regs_write()
+                    .iter()
+                    .filter_map(|x| self.register(*x))
+                    .filter_map(|x| match x {
+                        Operand::Reg(r, _) => Some(r),
+                        _ => None,
+                    }),
+            );
+            // The return address pushed by a call is popped by the return
+            if ops.contains(&Op::Call) {
+                let sp = self.arch.stack_pointer();
+                writes.retain(|x| *x != sp);
+                writes.extend(
+                    (0..128)
+                        .map(Register)
+                        .filter(|x| self.arch.clobbered_by_call(*x)),
+                );
+            }
+            writes.sort_by_key(|x| x.0);
+            writes.dedup();
+
+            ret.push(Insn {
+                address: insn.address(),
+                text: format!("{mnemonic} {}", insn.op_str().unwrap_or(""))
+                    .trim_end()
+                    .to_string(),
+                ops,
+                writes,
+            });
+        }
+
+        Ok(ret)
+    }
+
+    /// The register `id`, or for a register which always reads as zero,
+    /// that zero
+    fn register(&self, id: RegId) -> Option<Operand> {
+        let name = self.cs.reg_name(id)?;
+        match self.arch {
+            Arch::X86_64 => x86_register(&name),
+            Arch::Aarch64 => aarch64_register(&name),
+        }
+    }
+
+    fn x86(&self, insn: &capstone::Insn, detail: &InsnDetail) -> (Vec<Op>, Vec<Register>) {
+        let ArchDetail::X86Detail(x86) = detail.arch_detail() else {
+            return (Vec::new(), Vec::new());
+        };
+
+        let mut read = Vec::new();
+        let mut written = Vec::new();
+        for op in x86.operands() {
+            let operand = match op.op_type {
+                X86OperandType::Reg(x) => match self.register(x) {
+                    Some(x) => x,
+                    None => continue,
+                },
+                X86OperandType::Imm(x) => Operand::Imm(x),
+                X86OperandType::Mem(m) => {
+                    let reg = |x: RegId| match self.register(x) {
+                        Some(Operand::Reg(r, _)) if x.0 != 0 => Some(r),
+                        _ => None,
+                    };
+                    // Anything relative to a segment is no part of the stack
+                    let base = if m.segment().0 == 0 {
+                        reg(m.base())
+                    } else {
+                        None
+                    };
+                    let mem = Mem {
+                        base,
+                        index: reg(m.index()),
+                        disp: m.disp(),
+                    };
+                    Operand::Mem(mem, op.size)
+                }
+                _ => continue,
+            };
+
+            match op.access {
+                Some(x) if x.is_writable() => written.push(operand),
+                _ => read.push(operand),
+            }
+        }
+
+        let mut writes = written
+            .iter()
+            .filter_map(|x| match x {
+                Operand::Reg(r, _) => Some(*r),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let sp = Register(7);
+        let top = |size| {
+            Operand::Mem(
+                Mem {
+                    base: Some(sp),
+                    index: None,
+                    disp: 0,
+                },
+                size,
+            )
+        };
+        let groups = detail
+            .groups()
+            .iter()
+            .map(|x| x.0 as u32)
+            .collect::<Vec<_>>();
+        let id = insn.id().0;
+        let is = |x: X86Insn| id == x as u32;
+
+        let ops = if groups.contains(&InsnGroupType::CS_GRP_CALL) {
+            vec![Op::Call]
+        } else if groups.contains(&InsnGroupType::CS_GRP_RET) {
+            vec![Op::Return]
+        } else if groups.contains(&InsnGroupType::CS_GRP_JUMP) {
+            let target = match read.first() {
+                Some(Operand::Imm(x)) => Some(*x as u64),
+                _ => None,
+            };
+            vec![Op::Jump {
+                target,
+                conditional: !is(X86Insn::X86_INS_JMP),
+            }]
+        } else if is(X86Insn::X86_INS_PUSH) {
+            match read.first() {
+                Some(src) => vec![
+                    Op::Add { dst: sp, value: -8 },
+                    Op::Move {
+                        dst: top(8),
+                        src: *src,
+                        extend: Some(Extend::Sign),
+                    },
+                ],
+                None => Vec::new(),
+            }
+        } else if is(X86Insn::X86_INS_POP) {
+            match written.first() {
+                Some(dst) => vec![
+                    Op::Move {
+                        dst: *dst,
+                        src: top(8),
+                        extend: None,
+                    },
+                    Op::Add { dst: sp, value: 8 },
+                ],
+                None => Vec::new(),
+            }
+        } else if is(X86Insn::X86_INS_LEAVE) {
+            let bp = Register(6);
+            writes.extend([sp, bp]);
+            vec![
+                Op::Move {
+                    dst: Operand::Reg(sp, 8),
+                    src: Operand::Reg(bp, 8),
+                    extend: None,
+                },
+                Op::Move {
+                    dst: Operand::Reg(bp, 8),
+                    src: top(8),
+                    extend: None,
+                },
+                Op::Add { dst: sp, value: 8 },
+            ]
+        } else if [
+            X86Insn::X86_INS_STOSB,
+            X86Insn::X86_INS_STOSW,
+            X86Insn::X86_INS_STOSD,
+            X86Insn::X86_INS_STOSQ,
+            X86Insn::X86_INS_MOVSB,
+            X86Insn::X86_INS_MOVSW,
+            X86Insn::X86_INS_MOVSD,
+            X86Insn::X86_INS_MOVSQ,
+        ]
+        .into_iter()
+        .any(is)
+            && written
+                .iter()
+                .chain(&read)
+                .any(|x| matches!(x, Operand::Mem(..)))
+            && !written
+                .iter()
+                .chain(&read)
+                .any(|x| matches!(x, Operand::Reg(_, 16)))
+        {
+            // Whether repeated or not, each writes at %rdi, and moves it,
+            // repeating as many times as %rcx says.  Capstone has the
+            // destination of `stos` only read.
+            let size = written
+                .iter()
+                .chain(&read)
+                .find_map(|x| match x {
+                    Operand::Mem(_, size) => Some(*size),
+                    _ => None,
+                })
+                .unwrap_or(1);
+            let count = matches!(x86.prefix()[0], 0xf2 | 0xf3);
+            writes.push(Register(5));
+            if count {
+                writes.push(Register(2));
+            }
+            vec![Op::Fill {
+                dst: Register(5),
+                count: count.then_some(Register(2)),
+                size,
+            }]
+        } else if is(X86Insn::X86_INS_CDQE) {
+            vec![Op::Move {
+                dst: Operand::Reg(Register(0), 8),
+                src: Operand::Reg(Register(0), 4),
+                extend: Some(Extend::Sign),
+            }]
+        } else {
+            let extend = if is(X86Insn::X86_INS_MOVSX) || is(X86Insn::X86_INS_MOVSXD) {
+                Some(Some(Extend::Sign))
+            } else if [
+                X86Insn::X86_INS_MOVZX,
+                X86Insn::X86_INS_MOVQ,
+                X86Insn::X86_INS_MOVD,
+            ]
+            .into_iter()
+            .any(is)
+            {
+                Some(Some(Extend::Zero))
+            } else if [
+                X86Insn::X86_INS_MOV,
+                X86Insn::X86_INS_MOVABS,
+                X86Insn::X86_INS_MOVAPS,
+                X86Insn::X86_INS_MOVAPD,
+                X86Insn::X86_INS_MOVDQA,
+                X86Insn::X86_INS_MOVDQU,
+                X86Insn::X86_INS_MOVUPS,
+                X86Insn::X86_INS_MOVUPD,
+            ]
+            .into_iter()
+            .any(is)
+            {
+                // Immediates are sign extended
+                Some(Some(Extend::Sign))
+            } else {
+                None
+            };
+
+            match (extend, &read[..], &written[..]) {
+                (Some(extend), [src], [dst]) => vec![Op::Move {
+                    dst: *dst,
+                    src: *src,
+                    extend,
+                }],
+                (None, [Operand::Mem(mem, _)], [Operand::Reg(dst, 8)])
+                    if is(X86Insn::X86_INS_LEA) =>
+                {
+                    vec![Op::Address {
+                        dst: *dst,
+                        mem: *mem,
+                    }]
+                }
+                (None, [Operand::Imm(x)], [Operand::Reg(dst, 8)])
+                    if is(X86Insn::X86_INS_ADD) || is(X86Insn::X86_INS_SUB) =>
+                {
+                    let value = if is(X86Insn::X86_INS_SUB) { -x } else { *x };
+                    vec![Op::Add { dst: *dst, value }]
+                }
+                // xor of a register with itself clears it
+                (None, [src], [dst @ Operand::Reg(..)])
+                    if src == dst && (is(X86Insn::X86_INS_XOR) || is(X86Insn::X86_INS_PXOR)) =>
+                {
+                    vec![Op::Move {
+                        dst: *dst,
+                        src: Operand::Imm(0),
+                        extend: Some(Extend::Zero),
+                    }]
+                }
+                _ => written
+                    .iter()
+                    .filter_map(|x| match x {
+                        Operand::Mem(mem, size) => Some(Op::Clobber {
+                            mem: *mem,
+                            size: *size,
+                        }),
+                        _ => None,
+                    })
+                    .collect(),
+            }
+        };
+
+        (ops, writes)
+    }
+
+    fn aarch64(&self, mnemonic: &str, detail: &InsnDetail) -> (Vec<Op>, Vec<Register>) {
+        let ArchDetail::Arm64Detail(arm64) = detail.arch_detail() else {
+            return (Vec::new(), Vec::new());
+        };
+
+        let mut operands = Vec::new();
+        for op in arm64.operands() {
+            operands.push(match op.op_type {
+                Arm64OperandType::Reg(x) => match self.register(x) {
+                    Some(x) => x,
+                    None => continue,
+                },
+                Arm64OperandType::Imm(x) => Operand::Imm(match op.shift {
+                    Arm64Shift::Lsl(n) => x << n,
+                    _ => x,
+                }),
+                Arm64OperandType::Mem(m) => {
+                    let reg = |x: RegId| match self.register(x) {
+                        Some(Operand::Reg(r, _)) if x.0 != 0 => Some(r),
+                        _ => None,
+                    };
+                    Operand::Mem(
+                        Mem {
+                            base: reg(m.base()),
+                            index: reg(m.index()),
+                            disp: m.disp() as i64,
+                        },
+                        0,
+                    )
+                }
+                _ => Operand::Imm(0),
+            });
+        }
+
+        let regs = operands
+            .iter()
+            .filter_map(|x| match x {
+                Operand::Reg(r, size) => Some((*r, *size)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mem = operands.iter().find_map(|x| match x {
+            Operand::Mem(m, _) => Some(*m),
+            _ => None,
+        });
+        let last_imm = match operands.last() {
+            Some(Operand::Imm(x)) => Some(*x),
+            _ => None,
+        };
+
+        let conditional = |m: &str| {
+            m.starts_with("b.")
+                || matches!(m, "cbz" | "cbnz" | "tbz" | "tbnz")
+                || (m == "b"
+                    && !matches!(
+                        arm64.cc(),
+                        arch::arm64::Arm64CC::ARM64_CC_INVALID | arch::arm64::Arm64CC::ARM64_CC_AL
+                    ))
+        };
+        match mnemonic {
+            "bl" | "blr" => return (vec![Op::Call], Vec::new()),
+            "ret" => return (vec![Op::Return], Vec::new()),
+            "br" => {
+                return (
+                    vec![Op::Jump {
+                        target: None,
+                        conditional: false,
+                    }],
+                    Vec::new(),
+                )
+            }
+            m if m == "b" || conditional(m) => {
+                return (
+                    vec![Op::Jump {
+                        target: last_imm.map(|x| x as u64),
+                        conditional: conditional(m),
+                    }],
+                    Vec::new(),
+                )
+            }
+            _ => (),
+        }
+
+        // Loads and stores, of one or a pair of registers, perhaps first or
+        // afterwards moving the base register
+        let load = mnemonic.starts_with("ld");
+        let store = mnemonic.starts_with("st");
+        if let (true, Some(mem)) = (load || store, mem) {
+            let (size, extend) = match mnemonic {
+                x if x.ends_with("sb") => (1, Some(Extend::Sign)),
+                x if x.ends_with("sh") => (2, Some(Extend::Sign)),
+                x if x.ends_with("sw") => (4, Some(Extend::Sign)),
+                x if x.ends_with('b') => (1, Some(Extend::Zero)),
+                x if x.ends_with('h') => (2, Some(Extend::Zero)),
+                _ => (0, None),
+            };
+            // Exclusive stores first write their status
+            let (status, regs) = match mnemonic {
+                "stxr" | "stlxr" | "stxp" | "stlxp" | "stxrb" | "stlxrb" | "stxrh" | "stlxrh" => {
+                    (regs.first().map(|x| x.0), &regs[1..])
+                }
+                _ => (None, &regs[..]),
+            };
+            let transfers = regs.len().min(2);
+
+            let mut ops = Vec::new();
+            let mut writes = status.into_iter().collect::<Vec<_>>();
+            let writeback = arm64.writeback();
+            let post = writeback && last_imm.is_some() && operands.len() > transfers + 1;
+            let base = mem.base;
+            let mut at = mem;
+            if writeback && !post {
+                if let Some(base) = base {
+                    ops.push(Op::Add {
+                        dst: base,
+                        value: mem.disp,
+                    });
+                }
+                at.disp = 0;
+            }
+
+            for (i, &(reg, reg_size)) in regs[..transfers].iter().enumerate() {
+                let size = if size == 0 { reg_size } else { size };
+                let place = Operand::Mem(
+                    Mem {
+                        disp: at.disp + i as i64 * size as i64,
+                        ..at
+                    },
+                    size,
+                );
+                if load {
+                    writes.push(reg);
+                    ops.push(Op::Move {
+                        dst: Operand::Reg(reg, reg_size),
+                        src: place,
+                        extend,
+                    });
+                } else {
+                    ops.push(Op::Move {
+                        dst: place,
+                        src: Operand::Reg(reg, size),
+                        extend: None,
+                    });
+                }
+            }
+
+            if let (true, Some(base), Some(value)) = (post, base, last_imm) {
+                ops.push(Op::Add { dst: base, value });
+            }
+            if writeback {
+                writes.extend(base);
+            }
+
+            return (ops, writes);
+        }
+
+        if matches!(
+            mnemonic,
+            "cmp"
+                | "cmn"
+                | "tst"
+                | "ccmp"
+                | "ccmn"
+                | "fcmp"
+                | "fcmpe"
+                | "fccmp"
+                | "prfm"
+                | "nop"
+                | "hint"
+                | "paciasp"
+                | "autiasp"
+                | "pacibsp"
+                | "autibsp"
+                | "bti"
+        ) {
+            return (Vec::new(), Vec::new());
+        }
+
+        let Some(&(dst, dst_size)) = regs.first() else {
+            return (Vec::new(), Vec::new());
+        };
+        let dst_op = match operands.first() {
+            Some(x @ Operand::Reg(..)) => *x,
+            _ => return (Vec::new(), Vec::new()),
+        };
+        let src = operands.get(1).copied();
+
+        let ops = match (mnemonic, src, operands.get(2)) {
+            ("mov", Some(src), None) => vec![Op::Move {
+                dst: dst_op,
+                src,
+                extend: Some(Extend::Zero),
+            }],
+            ("sxtw" | "sxth" | "sxtb" | "uxth" | "uxtb", Some(Operand::Reg(src, _)), None) => {
+                let size = match &mnemonic[3..] {
+                    "w" => 4,
+                    "h" => 2,
+                    _ => 1,
+                };
+                let extend = if mnemonic.starts_with('s') {
+                    Extend::Sign
+                } else {
+                    Extend::Zero
+                };
+                vec![Op::Move {
+                    dst: dst_op,
+                    src: Operand::Reg(src, size),
+                    extend: Some(extend),
+                }]
+            }
+            ("add" | "sub", Some(Operand::Reg(src, 8)), Some(Operand::Imm(x))) if dst_size == 8 => {
+                let value = if mnemonic == "sub" { -x } else { *x };
+                if src == dst {
+                    vec![Op::Add { dst, value }]
+                } else {
+                    vec![Op::Address {
+                        dst,
+                        mem: Mem {
+                            base: Some(src),
+                            index: None,
+                            disp: value,
+                        },
+                    }]
+                }
+            }
+            _ => Vec::new(),
+        };
+
+        (ops, vec![dst])
+    }
+}
+
+/// The DWARF number and size of the x86-64 register `name`
+fn x86_register(name: &str) -> Option<Operand> {
+    const GPRS: [[&str; 5]; 8] = [
+        ["rax", "eax", "ax", "al", "ah"],
+        ["rdx", "edx", "dx", "dl", "dh"],
+        ["rcx", "ecx", "cx", "cl", "ch"],
+        ["rbx", "ebx", "bx", "bl", "bh"],
+        ["rsi", "esi", "si", "sil", ""],
+        ["rdi", "edi", "di", "dil", ""],
+        ["rbp", "ebp", "bp", "bpl", ""],
+        ["rsp", "esp", "sp", "spl", ""],
+    ];
+
+    for (i, names) in GPRS.iter().enumerate() {
+        if let Some(j) = names.iter().position(|x| *x == name) {
+            return Some(Operand::Reg(Register(i as u16), [8, 4, 2, 1, 1][j]));
+        }
+    }
+
+    if let Some(n) = name.strip_prefix("xmm") {
+        let n = n.parse::<u16>().ok().filter(|x| *x < 16)?;
+        return Some(Operand::Reg(Register(17 + n), 16));
+    }
+
+    let n = name.strip_prefix('r')?;
+    let (n, size) = match n.as_bytes().last()? {
+        b'd' => (&n[..n.len() - 1], 4),
+        b'w' => (&n[..n.len() - 1], 2),
+        b'b' => (&n[..n.len() - 1], 1),
+        _ => (n, 8),
+    };
+    let n = n.parse::<u16>().ok().filter(|x| (8..16).contains(x))?;
+    Some(Operand::Reg(Register(n), size))
+}
+
+/// The DWARF number and size of the AArch64 register `name`
+fn aarch64_register(name: &str) -> Option<Operand> {
+    match name {
+        "xzr" | "wzr" => return Some(Operand::Imm(0)),
+        "sp" => return Some(Operand::Reg(Register(31), 8)),
+        "wsp" => return Some(Operand::Reg(Register(31), 4)),
+        "fp" => return Some(Operand::Reg(Register(29), 8)),
+        "lr" => return Some(Operand::Reg(Register(30), 8)),
+        _ => (),
+    }
+
+    let (kind, n) = name.split_at(1);
+    let n = n.parse::<u16>().ok().filter(|x| *x < 32)?;
+    match kind {
+        "x" => Some(Operand::Reg(Register(n), 8)),
+        "w" => Some(Operand::Reg(Register(n), 4)),
+        "v" | "q" => Some(Operand::Reg(Register(64 + n), 16)),
+        "d" => Some(Operand::Reg(Register(64 + n), 8)),
+        "s" => Some(Operand::Reg(Register(64 + n), 4)),
+        "h" => Some(Operand::Reg(Register(64 + n), 2)),
+        "b" => Some(Operand::Reg(Register(64 + n), 1)),
+        _ => None,
+    }
+}
+
+/// How many of the arguments passed on the stack we follow
+const STACK_ARGUMENTS: usize = 16;
+
+/// What is somewhere, as far as we know
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Value {
+    Unknown,
+    /// The low `bits` of integer argument `index` as it was at entry, made
+    /// wider as `extend` says, or with what is above them unknown
+    Arg {
+        index: usize,
+        bits: u8,
+        extend: Option<Extend>,
+    },
+    /// The stack pointer at entry, plus this
+    Stack(i64),
+    Const(i64),
+}
+
+impl Value {
+    /// The low `size` bytes of this
+    fn truncate(self, size: u8) -> Value {
+        let bits = size.min(8) * 8;
+        match self {
+            Value::Arg { index, bits: b, .. } if bits < b => Value::Arg {
+                index,
+                bits,
+                extend: None,
+            },
+            Value::Const(x) if bits < 64 => Value::Const(x & ((1 << bits) - 1)),
+            Value::Stack(_) if bits < 64 => Value::Unknown,
+            x => x,
+        }
+    }
+
+    /// This, of `from` bytes, made `to` bytes wide as `extend` says
+    fn resize(self, from: u8, to: u8, extend: Option<Extend>) -> Value {
+        let v = self.truncate(from);
+        if from >= to {
+            return v.truncate(to);
+        }
+
+        let bits = from * 8;
+        match (v, extend) {
+            (_, None) => Value::Unknown,
+            (Value::Arg { index, bits: b, .. }, Some(extend)) if b == bits => Value::Arg {
+                index,
+                bits,
+                extend: Some(extend),
+            },
+            (x @ Value::Arg { .. }, Some(_)) => x,
+            (Value::Const(x), Some(Extend::Zero)) => Value::Const(x),
+            (Value::Const(x), Some(Extend::Sign)) => {
+                let shift = 64 - bits as u32;
+                Value::Const((x << shift) >> shift)
+            }
+            _ => Value::Unknown,
+        }
+    }
+
+    /// The offset from the stack pointer at entry, if this is one
+    pub(crate) fn stack(self) -> Option<i64> {
+        match self {
+            Value::Stack(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+/// A write to memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Store {
+    /// Relative to the stack pointer at entry, if known
+    pub address: Option<i64>,
+    /// In bytes, if known
+    pub size: Option<u64>,
+    pub value: Value,
+}
+
+/// What is in each register and in the stack, at some point in a function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct State {
+    /// The low and high eight bytes of each register not unknown
+    regs: HashMap<Register, [Value; 2]>,
+    /// Each part of the stack written, by its offset from the stack pointer
+    /// at entry, and its size
+    memory: BTreeMap<i64, (Value, u8)>,
+    /// The parts of the stack written, with whatever, as ordered and
+    /// disjoint half-open ranges
+    written: Vec<(i64, i64)>,
+}
+
+impl State {
+    /// The state at the entry of a function on `arch`
+    pub(crate) fn entry(arch: Arch) -> State {
+        let arg = |index| Value::Arg {
+            index,
+            bits: 64,
+            extend: None,
+        };
+
+        let mut regs = HashMap::new();
+        let registers = arch.argument_registers();
+        for (i, &reg) in registers.iter().enumerate() {
+            regs.insert(reg, [arg(i), Value::Unknown]);
+        }
+        regs.insert(arch.stack_pointer(), [Value::Stack(0), Value::Unknown]);
+
+        // The arguments after those in registers are on the stack, above
+        // the return address on x86-64
+        let first = match arch {
+            Arch::X86_64 => 8,
+            Arch::Aarch64 => 0,
+        };
+        let memory = (0..STACK_ARGUMENTS)
+            .map(|i| (first + 8 * i as i64, (arg(registers.len() + i), 8)))
+            .collect();
+
+        State {
+            regs,
+            memory,
+            written: Vec::new(),
+        }
+    }
+
+    /// True if every byte from `start` to `end` has been written
+    pub(crate) fn written(&self, start: i64, end: i64) -> bool {
+        self.written.iter().any(|x| x.0 <= start && end <= x.1)
+    }
+
+    /// Mark the bytes from `start` to `end` as written
+    fn write(&mut self, start: i64, end: i64) {
+        let (mut start, mut end) = (start, end);
+        self.written.retain(|x| {
+            if x.1 < start || end < x.0 {
+                return true;
+            }
+            start = start.min(x.0);
+            end = end.max(x.1);
+            false
+        });
+        let at = self.written.partition_point(|x| x.0 < start);
+        self.written.insert(at, (start, end));
+    }
+
+    /// Make this what is known both here and in `other`, returning true if
+    /// that is less than was known here
+    pub(crate) fn join(&mut self, other: &State) -> bool {
+        let (regs, memory) = (self.regs.len(), self.memory.len());
+        self.regs.retain(|r, x| other.regs.get(r) == Some(x));
+        self.memory.retain(|a, x| other.memory.get(a) == Some(x));
+
+        let mut written = Vec::new();
+        for x in &self.written {
+            for y in &other.written {
+                let (start, end) = (x.0.max(y.0), x.1.min(y.1));
+                if start < end {
+                    written.push((start, end));
+                }
+            }
+        }
+        let changed = written != self.written;
+        self.written = written;
+
+        changed || regs != self.regs.len() || memory != self.memory.len()
+    }
+
+    /// The value of `reg`
+    pub(crate) fn register(&self, reg: Register) -> Value {
+        self.regs.get(&reg).map_or(Value::Unknown, |x| x[0])
+    }
+
+    /// The value of `size` bytes at `address`, relative to the stack pointer
+    /// at entry
+    pub(crate) fn load(&self, address: i64, size: u8) -> Value {
+        match self.memory.get(&address) {
+            Some(&(v, s)) if s >= size => v.truncate(size),
+            _ => Value::Unknown,
+        }
+    }
+
+    /// The address of `mem`, relative to the stack pointer at entry, if it
+    /// is known
+    pub(crate) fn address(&self, mem: &Mem) -> Option<i64> {
+        if let Some(index) = mem.index {
+            if self.register(index) != Value::Const(0) {
+                return None;
+            }
+        }
+        Some(self.register(mem.base?).stack()? + mem.disp)
+    }
+
+    fn read(&self, operand: &Operand) -> [Value; 2] {
+        match *operand {
+            Operand::Reg(r, 16) => self.regs.get(&r).copied().unwrap_or([Value::Unknown; 2]),
+            Operand::Reg(r, size) => [self.register(r).truncate(size), Value::Unknown],
+            Operand::Imm(x) => [Value::Const(x), Value::Unknown],
+            Operand::Mem(mem, size) => match self.address(&mem) {
+                Some(a) if size == 16 => [self.load(a, 8), self.load(a + 8, 8)],
+                Some(a) => [self.load(a, size), Value::Unknown],
+                None => [Value::Unknown; 2],
+            },
+        }
+    }
+
+    /// Forget whatever is in the `size` bytes at `address`
+    fn forget(&mut self, address: i64, size: i64) {
+        let overlapping = self
+            .memory
+            .range(address - 16..address + size)
+            .filter(|(a, (_, s))| **a + *s as i64 > address)
+            .map(|(a, _)| *a)
+            .collect::<Vec<_>>();
+        for a in overlapping {
+            self.memory.remove(&a);
+        }
+    }
+
+    fn store(&mut self, address: i64, value: Value, size: u8, stores: &mut Vec<Store>) {
+        self.forget(address, size as i64);
+        self.memory.insert(address, (value.truncate(size), size));
+        stores.push(Store {
+            address: Some(address),
+            size: Some(size as u64),
+            value: value.truncate(size),
+        });
+    }
+
+    /// Follow `insn`, returning what it wrote to memory
+    pub(crate) fn step(&mut self, insn: &Insn) -> Vec<Store> {
+        let mut stores = Vec::new();
+        let mut set = Vec::new();
+
+        for op in &insn.ops {
+            match *op {
+                Op::Move { dst, src, extend } => {
+                    let size = |x: &Operand| match x {
+                        Operand::Reg(_, s) | Operand::Mem(_, s) => *s,
+                        Operand::Imm(_) => 8,
+                    };
+                    let (from, to) = (size(&src), size(&dst));
+                    let [lo, hi] = self.read(&src);
+
+                    match dst {
+                        Operand::Reg(r, 16) => {
+                            let hi = if from == 16 { hi } else { Value::Unknown };
+                            self.regs.insert(r, [lo.resize(from, 8, extend), hi]);
+                            set.push(r);
+                        }
+                        // Writing a four byte register clears the rest
+                        Operand::Reg(r, 4 | 8) => {
+                            let v = lo
+                                .resize(from, to, extend)
+                                .resize(to, 8, Some(Extend::Zero));
+                            self.regs.insert(r, [v, Value::Unknown]);
+                            set.push(r);
+                        }
+                        Operand::Reg(..) | Operand::Imm(_) => (),
+                        Operand::Mem(mem, size) => match self.address(&mem) {
+                            Some(a) if size == 16 => {
+                                self.store(a, lo, 8, &mut stores);
+                                self.store(a + 8, hi, 8, &mut stores);
+                            }
+                            Some(a) => {
+                                self.store(a, lo.resize(from, to, extend), size, &mut stores)
+                            }
+                            None => stores.push(Store {
+                                address: None,
+                                size: Some(size as u64),
+                                value: lo,
+                            }),
+                        },
+                    }
+                }
+                Op::Address { dst, mem } => {
+                    let v = match mem.base.map(|x| self.register(x)) {
+                        Some(Value::Stack(x)) if mem.index.is_none() => Value::Stack(x + mem.disp),
+                        _ => Value::Unknown,
+                    };
+                    self.regs.insert(dst, [v, Value::Unknown]);
+                    set.push(dst);
+                }
+                Op::Add { dst, value } => {
+                    let v = match self.register(dst) {
+                        Value::Stack(x) => Value::Stack(x + value),
+                        Value::Const(x) => Value::Const(x.wrapping_add(value)),
+                        _ => Value::Unknown,
+                    };
+                    self.regs.insert(dst, [v, Value::Unknown]);
+                    set.push(dst);
+                }
+                Op::Clobber { mem, size } => {
+                    let address = self.address(&mem);
+                    if let Some(a) = address {
+                        self.forget(a, size as i64);
+                    }
+                    stores.push(Store {
+                        address,
+                        size: Some(size as u64),
+                        value: Value::Unknown,
+                    });
+                }
+                Op::Fill { dst, count, size } => {
+                    let address = self.register(dst).stack();
+                    let length = match count.map(|x| self.register(x)) {
+                        None => Some(size as i64),
+                        Some(Value::Const(n)) if n >= 0 => Some(n * size as i64),
+                        Some(_) => None,
+                    };
+                    match (address, length) {
+                        (Some(a), Some(n)) => self.forget(a, n),
+                        (Some(a), None) => {
+                            let above = self.memory.range(a..).map(|x| *x.0).collect::<Vec<_>>();
+                            for x in above {
+                                self.memory.remove(&x);
+                            }
+                        }
+                        (None, _) => (),
+                    }
+                    stores.push(Store {
+                        address,
+                        size: length.map(|x| x as u64),
+                        value: Value::Unknown,
+                    });
+                }
+                Op::Call | Op::Return | Op::Jump { .. } => (),
+            }
+        }
+
+        for reg in &insn.writes {
+            if !set.contains(reg) {
+                self.regs.remove(reg);
+            }
+        }
+        self.regs.retain(|_, x| *x != [Value::Unknown; 2]);
+
+        for x in &stores {
+            if let (Some(address), Some(size)) = (x.address, x.size) {
+                self.write(address, address + size as i64);
+            }
+        }
+
+        stores
+    }
+}
+
+/// The state at the start of each of `insns`, the code of a function on
+/// `arch`, as it is on every path from the entry reaching it, or None if no
+/// path we can follow does
+pub(crate) fn flow(arch: Arch, insns: &[Insn]) -> Vec<Option<State>> {
+    let index = insns
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (x.address, i))
+        .collect::<HashMap<_, _>>();
+
+    let mut states = vec![None; insns.len()];
+    if insns.is_empty() {
+        return states;
+    }
+    states[0] = Some(State::entry(arch));
+
+    let mut work = vec![0];
+    while let Some(i) = work.pop() {
+        let Some(mut state) = states[i].clone() else {
+            continue;
+        };
+        state.step(&insns[i]);
+
+        // Jumps out of the function are as good as returns
+        let fall = Some(i + 1).filter(|x| insns[i].falls_through() && *x < insns.len());
+        let jump = insns[i].target().and_then(|x| index.get(&x).copied());
+        for next in fall.into_iter().chain(jump) {
+            let changed = match &mut states[next] {
+                Some(x) => x.join(&state),
+                x @ None => {
+                    *x = Some(state.clone());
+                    true
+                }
+            };
+            if changed && !work.contains(&next) {
+                work.push(next);
+            }
+        }
+    }
+
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAX: Register = Register(0);
+    const RSI: Register = Register(4);
+    const RDI: Register = Register(5);
+    const RBP: Register = Register(6);
+    const RSP: Register = Register(7);
+
+    /// `pushq %rbp; movq %rsp,%rbp`
+    const PROLOGUE: [u8; 4] = [0x55, 0x48, 0x89, 0xe5];
+
+    fn decode(arch: Arch, code: &[u8]) -> Vec<Insn> {
+        Decoder::new(arch).unwrap().decode(code, 0x1000).unwrap()
+    }
+
+    /// The state at the start of the last instruction of `code`
+    fn last(arch: Arch, code: &[u8]) -> State {
+        let insns = decode(arch, code);
+        flow(arch, &insns).pop().unwrap().unwrap()
+    }
+
+    fn arg(index: usize, bits: u8, extend: Option<Extend>) -> Value {
+        Value::Arg {
+            index,
+            bits,
+            extend,
+        }
+    }
+
+    fn at(base: Register, disp: i64) -> Mem {
+        Mem {
+            base: Some(base),
+            index: None,
+            disp,
+        }
+    }
+
+    #[test]
+    fn decode_x86() {
+        let code = [
+            PROLOGUE.as_slice(),
+            &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+            &[0x48, 0x8d, 0x45, 0xf8], // leaq -0x8(%rbp),%rax
+            &[0xf3, 0x48, 0xab],       // rep stosq
+            &[0x74, 0x01],             // je .+3
+            &[0xc3],                   // retq
+            &[0xe8, 0, 0, 0, 0],       // callq .+5
+        ]
+        .concat();
+        let insns = decode(Arch::X86_64, &code);
+
+        assert_eq!(
+            insns.iter().map(|x| x.address - 0x1000).collect::<Vec<_>>(),
+            [0, 1, 4, 8, 12, 15, 17, 18]
+        );
+        assert_eq!(
+            insns[0].ops,
+            [
+                Op::Add {
+                    dst: RSP,
+                    value: -8
+                },
+                Op::Move {
+                    dst: Operand::Mem(at(RSP, 0), 8),
+                    src: Operand::Reg(RBP, 8),
+                    extend: Some(Extend::Sign),
+                },
+            ]
+        );
+        assert_eq!(
+            insns[2].ops,
+            [Op::Move {
+                dst: Operand::Mem(at(RBP, -8), 8),
+                src: Operand::Reg(RDI, 8),
+                extend: Some(Extend::Sign),
+            }]
+        );
+        assert_eq!(
+            insns[3].ops,
+            [Op::Address {
+                dst: RAX,
+                mem: at(RBP, -8),
+            }]
+        );
+        assert_eq!(
+            insns[4].ops,
+            [Op::Fill {
+                dst: RDI,
+                count: Some(Register(2)),
+                size: 8,
+            }]
+        );
+        assert!(insns[5].falls_through());
+        assert_eq!(insns[5].target(), Some(0x1012));
+        assert!(!insns[6].falls_through());
+        assert_eq!(insns[7].ops, [Op::Call]);
+        assert!(insns[7].writes.contains(&RDI));
+        assert!(!insns[7].writes.contains(&RBP));
+    }
+
+    #[test]
+    fn decode_aarch64() {
+        let code = [
+            0xfd, 0x7b, 0xbe, 0xa9, // stp x29, x30, [sp, #-32]!
+            0xfd, 0x03, 0x00, 0x91, // mov x29, sp
+            0xa0, 0x07, 0x01, 0xa9, // stp x0, x1, [x29, #16]
+            0xc0, 0x03, 0x5f, 0xd6, // ret
+        ];
+        let insns = decode(Arch::Aarch64, &code);
+        let (sp, fp) = (Register(31), Register(29));
+
+        assert_eq!(insns.len(), 4);
+        assert_eq!(
+            insns[0].ops[0],
+            Op::Add {
+                dst: sp,
+                value: -32
+            }
+        );
+        assert_eq!(
+            insns[2].ops,
+            [(16, 0), (24, 1)].map(|(disp, reg)| Op::Move {
+                dst: Operand::Mem(at(fp, disp), 8),
+                src: Operand::Reg(Register(reg), 8),
+                extend: None,
+            })
+        );
+        assert_eq!(insns[3].ops, [Op::Return]);
+    }
+
+    #[test]
+    fn flow_x86() {
+        let code = [
+            PROLOGUE.as_slice(),
+            &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+            &[0x48, 0x89, 0x75, 0xf0], // movq %rsi,-0x10(%rbp)
+            &[0x5d],                   // popq %rbp
+            &[0xc3],                   // retq
+        ]
+        .concat();
+        let state = last(Arch::X86_64, &code);
+
+        assert_eq!(state.register(RSP), Value::Stack(0));
+        assert_eq!(state.load(-16, 8), arg(0, 64, None));
+        assert_eq!(state.load(-24, 8), arg(1, 64, None));
+        assert_eq!(state.load(-16, 4), arg(0, 32, None));
+        assert!(state.written(-24, -8));
+        assert!(!state.written(-32, -8));
+        // The caller's %rbp, which we know nothing of
+        assert_eq!(state.register(RBP), Value::Unknown);
+        assert_eq!(state.load(-8, 8), Value::Unknown);
+    }
+
+    #[test]
+    fn flow_aarch64() {
+        let code = [
+            0xfd, 0x7b, 0xbe, 0xa9, // stp x29, x30, [sp, #-32]!
+            0xfd, 0x03, 0x00, 0x91, // mov x29, sp
+            0xa0, 0x07, 0x01, 0xa9, // stp x0, x1, [x29, #16]
+            0xa0, 0x00, 0x80, 0xd2, // mov x0, #5
+            0xc0, 0x03, 0x5f, 0xd6, // ret
+        ];
+        let state = last(Arch::Aarch64, &code);
+
+        assert_eq!(state.register(Register(29)), Value::Stack(-32));
+        assert_eq!(state.register(Register(0)), Value::Const(5));
+        assert_eq!(state.load(-16, 8), arg(0, 64, None));
+        assert_eq!(state.load(-8, 8), arg(1, 64, None));
+        assert!(state.written(-32, 0));
+    }
+
+    #[test]
+    fn extended() {
+        let code = [
+            PROLOGUE.as_slice(),
+            &[0x89, 0xff],             // movl %edi,%edi
+            &[0x48, 0x63, 0xf6],       // movslq %esi,%rsi
+            &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+            &[0x48, 0x89, 0x75, 0xf0], // movq %rsi,-0x10(%rbp)
+            &[0x89, 0x7d, 0xe8],       // movl %edi,-0x18(%rbp)
+            &[0xc3],                   // retq
+        ]
+        .concat();
+        let state = last(Arch::X86_64, &code);
+
+        assert_eq!(state.register(RDI), arg(0, 32, Some(Extend::Zero)));
+        assert_eq!(state.register(RSI), arg(1, 32, Some(Extend::Sign)));
+        assert_eq!(state.load(-16, 8), arg(0, 32, Some(Extend::Zero)));
+        assert_eq!(state.load(-24, 8), arg(1, 32, Some(Extend::Sign)));
+        // Only four bytes were stored here, and no more may be read
+        assert_eq!(state.load(-32, 4), arg(0, 32, Some(Extend::Zero)));
+        assert_eq!(state.load(-32, 8), Value::Unknown);
+    }
+
+    #[test]
+    fn paths_join() {
+        let code = [
+            PROLOGUE.as_slice(),
+            &[0x48, 0x85, 0xff],                   // testq %rdi,%rdi
+            &[0x74, 0x06],                         // je 1f
+            &[0x48, 0x89, 0x7d, 0xf8],             // movq %rdi,-0x8(%rbp)
+            &[0xeb, 0x08],                         // jmp 2f
+            &[0x48, 0xc7, 0x45, 0xf8, 0, 0, 0, 0], // 1: movq $0,-0x8(%rbp)
+            &[0x5d],                               // 2: popq %rbp
+            &[0xc3],                               // retq
+        ]
+        .concat();
+        let insns = decode(Arch::X86_64, &code);
+        let states = flow(Arch::X86_64, &insns);
+        let join = states[7].as_ref().unwrap();
+
+        assert_eq!(states[5].as_ref().unwrap().load(-16, 8), arg(0, 64, None));
+        // Written on both paths, but with something different on each
+        assert!(join.written(-16, -8));
+        assert_eq!(join.load(-16, 8), Value::Unknown);
+
+        // Without the store on the second path, it is not written at all
+        let mut code = code;
+        code[15..23].copy_from_slice(&[0x90; 8]);
+        let insns = decode(Arch::X86_64, &code);
+        let states = flow(Arch::X86_64, &insns);
+        assert!(!states.last().unwrap().as_ref().unwrap().written(-16, -8));
+    }
+
+    #[test]
+    fn unreachable() {
+        let code = [
+            &[0xeb, 0x01][..], // jmp .+3
+            &[0x90],           // nop
+            &[0xc3],           // retq
+        ]
+        .concat();
+        let insns = decode(Arch::X86_64, &code);
+        let states = flow(Arch::X86_64, &insns);
+
+        assert!(states[1].is_none());
+        assert!(states[2].is_some());
+    }
+
+    #[test]
+    fn stores() {
+        let code = [
+            PROLOGUE.as_slice(),
+            &[0x48, 0x8d, 0x45, 0xf8], // leaq -0x8(%rbp),%rax
+            &[0x48, 0xc7, 0x00, 0x01, 0x00, 0x00, 0x00], // movq $1,(%rax)
+            &[0xf3, 0x48, 0xab],       // rep stosq
+        ]
+        .concat();
+        let insns = decode(Arch::X86_64, &code);
+        let states = flow(Arch::X86_64, &insns);
+
+        let mut state = states[3].clone().unwrap();
+        assert_eq!(state.register(RAX), Value::Stack(-16));
+        assert_eq!(
+            state.step(&insns[3]),
+            [Store {
+                address: Some(-16),
+                size: Some(8),
+                value: Value::Const(1),
+            }]
+        );
+        // Through %rdi, which is an argument and not on the stack
+        assert_eq!(
+            states[4].clone().unwrap().step(&insns[4]),
+            [Store {
+                address: None,
+                size: None,
+                value: Value::Unknown,
+            }]
+        );
+    }
+}
@@ -0,0 +1,1081 @@
+//! The Compact C Type Format, as found in `.SUNW_ctf`
+//!
+//! This is where the README would have the offset of each function's saved
+//! arguments live.  A [`Ctf`] container is read whole and may be written back,
+//! with the offsets carried in an extension which follows the container:
+//!
+//! ```text
+//! records     nrecords of: u32 symbol; i32 offset; u16 register, nargs
+//! trailer     u32 nrecords, version; magic "SAVEARGS"
+//! ```
+//!
+//! Existing readers find everything they understand by the offsets in the
+//! header, and so never look beyond the string table, where the extension
+//! is.  Each record is keyed by the index of the function's symbol in the
+//! symbol table, the same symbol by which the function section is indexed,
+//! and gives the offset of the saved arguments from the frame register and
+//! how many there are.
+//!
+//! Versions 2 and 3 are understood, compressed or not, but only in the byte
+//! order of amd64 and AArch64.
+//!
+//! The arguments CTF gives each function may be [checked](cross_check)
+//! against those the DWARF gives, since a consumer of the saved arguments
+//! has only the CTF to tell it what they are.  Most containers are the
+//! children of another, from which they were uniquified, and name the types
+//! they share by IDs in the parent, up to the label the child gives.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Context, Result};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind, SymbolSection};
+
+use crate::{elf, Diagnostic, DiagnosticKind, FunctionRecord, ScanReport, Status};
+
+/// The name of the section
+pub const SECTION_NAME: &str = ".SUNW_ctf";
+pub const MAGIC: u16 = 0xcff1;
+/// The data following the header is compressed with zlib
+pub const F_COMPRESS: u8 = 0x1;
+
+pub const K_UNKNOWN: u32 = 0;
+pub const K_INTEGER: u32 = 1;
+pub const K_FLOAT: u32 = 2;
+pub const K_POINTER: u32 = 3;
+pub const K_ARRAY: u32 = 4;
+pub const K_FUNCTION: u32 = 5;
+pub const K_STRUCT: u32 = 6;
+pub const K_UNION: u32 = 7;
+pub const K_ENUM: u32 = 8;
+pub const K_FORWARD: u32 = 9;
+pub const K_TYPEDEF: u32 = 10;
+pub const K_VOLATILE: u32 = 11;
+pub const K_CONST: u32 = 12;
+pub const K_RESTRICT: u32 = 13;
+
+const HEADER_SIZE: usize = 36;
+
+const EXT_MAGIC: [u8; 8] = *b"SAVEARGS";
+const EXT_VERSION: u32 = 1;
+const EXT_RECORD_SIZE: usize = 12;
+const EXT_TRAILER_SIZE: usize = 16;
+
+/// How the parts of a container are laid out in each version
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    version: u8,
+}
+
+impl Layout {
+    fn new(version: u8) -> Result<Self> {
+        match version {
+            2 | 3 => Ok(Layout { version }),
+            x => Err(anyhow!("unsupported CTF version {x}")),
+        }
+    }
+
+    fn v2(&self) -> bool {
+        self.version == 2
+    }
+
+    /// The kind, root flag, and variable length of a type information word
+    fn info(&self, info: u32) -> (u32, bool, u32) {
+        if self.v2() {
+            ((info >> 11) & 0x1f, info & 0x400 != 0, info & 0x3ff)
+        } else {
+            (
+                (info >> 26) & 0x3f,
+                info & 0x0200_0000 != 0,
+                info & 0x00ff_ffff,
+            )
+        }
+    }
+
+    fn make_info(&self, kind: u32, root: bool, vlen: u32) -> u32 {
+        if self.v2() {
+            (kind << 11) | (u32::from(root) << 10) | (vlen & 0x3ff)
+        } else {
+            (kind << 26) | (u32::from(root) << 25) | (vlen & 0x00ff_ffff)
+        }
+    }
+
+    /// The size which says that the true size follows, in two words
+    fn lsize_sent(&self) -> u64 {
+        if self.v2() {
+            0xffff
+        } else {
+            0xffff_ffff
+        }
+    }
+
+    /// The size from which structure members are described at length
+    fn lstruct_thresh(&self) -> u64 {
+        if self.v2() {
+            8192
+        } else {
+            1 << 29
+        }
+    }
+
+    /// The size of the data following a type of `kind`
+    fn data_size(&self, kind: u32, vlen: u32, size: u64) -> Result<usize> {
+        let vlen = vlen as usize;
+
+        Ok(match kind {
+            K_INTEGER | K_FLOAT => 4,
+            K_ARRAY if self.v2() => 8,
+            K_ARRAY => 12,
+            K_FUNCTION if self.v2() => 2 * (vlen + (vlen & 1)),
+            K_FUNCTION => 4 * vlen,
+            K_STRUCT | K_UNION if size >= self.lstruct_thresh() => 16 * vlen,
+            K_STRUCT | K_UNION => vlen * if self.v2() { 8 } else { 12 },
+            K_ENUM => 8 * vlen,
+            K_UNKNOWN | K_POINTER | K_FORWARD | K_TYPEDEF | K_VOLATILE | K_CONST | K_RESTRICT => 0,
+            x => return Err(anyhow!("unknown kind of type {x}")),
+        })
+    }
+}
+
+/// Reads little-endian values from the front of a slice
+struct Cursor<'data> {
+    data: &'data [u8],
+}
+
+impl<'data> Cursor<'data> {
+    fn bytes(&mut self, n: usize) -> Result<&'data [u8]> {
+        if self.data.len() < n {
+            return Err(anyhow!("CTF is truncated"));
+        }
+
+        let (x, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(x)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// A type ID or information word, of the size `layout` gives
+    fn id(&mut self, layout: Layout) -> Result<u32> {
+        if layout.v2() {
+            Ok(u32::from(self.u16()?))
+        } else {
+            self.u32()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+fn put_id(v: &mut Vec<u8>, layout: Layout, id: u32) {
+    if layout.v2() {
+        v.extend((id as u16).to_le_bytes());
+    } else {
+        v.extend(id.to_le_bytes());
+    }
+}
+
+/// A label, naming the types up to and including `type_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub name: u32,
+    pub type_id: u32,
+}
+
+/// The type of a function with a symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionInfo {
+    pub root: bool,
+    pub ret: u32,
+    /// The type of each argument, the last being 0 if the function takes
+    /// variable arguments
+    pub args: Vec<u32>,
+}
+
+/// A type, with what follows it left undecoded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Type {
+    pub name: u32,
+    pub kind: u32,
+    pub root: bool,
+    pub vlen: u32,
+    /// The size of the type, or the type it refers to
+    pub size: u64,
+    pub data: Vec<u8>,
+}
+
+impl Type {
+    /// The type this refers to, if it is a kind which refers to another
+    pub fn referenced(&self) -> Option<u32> {
+        matches!(
+            self.kind,
+            K_POINTER | K_TYPEDEF | K_VOLATILE | K_CONST | K_RESTRICT
+        )
+        .then_some(self.size as u32)
+    }
+
+    /// The type of each member of a structure or union, in a container of
+    /// `version`
+    pub fn member_types(&self, version: u8) -> Vec<u32> {
+        if !matches!(self.kind, K_STRUCT | K_UNION) || self.vlen == 0 {
+            return Vec::new();
+        }
+
+        // Short and long, every form of member has its type after its name
+        let v2 = version == 2;
+        self.data
+            .chunks_exact(self.data.len() / self.vlen as usize)
+            .map(|x| {
+                if v2 {
+                    u32::from(u16::from_le_bytes([x[4], x[5]]))
+                } else {
+                    u32::from_le_bytes(x[4..8].try_into().unwrap())
+                }
+            })
+            .collect()
+    }
+}
+
+/// Where the saved arguments of a function are, from the extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedArgs {
+    /// The index of the function's symbol
+    pub symbol: u32,
+    /// The offset of the saved arguments from `register`
+    pub offset: i32,
+    pub register: u16,
+    pub nargs: u16,
+}
+
+/// A CTF container, and our extension to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ctf {
+    pub version: u8,
+    pub flags: u8,
+    pub parent_label: u32,
+    pub parent_name: u32,
+    pub labels: Vec<Label>,
+    /// The type of each data object, in the order of their symbols
+    pub objects: Vec<u32>,
+    /// The type of each function, in the order of their symbols, if it has
+    /// one
+    pub functions: Vec<Option<FunctionInfo>>,
+    /// Every type, the first having ID 1
+    pub types: Vec<Type>,
+    pub strings: Vec<u8>,
+    pub saved_args: Vec<SavedArgs>,
+}
+
+impl Ctf {
+    /// Read the container in `data`, and any extension following it
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut c = Cursor { data };
+        if data.len() < HEADER_SIZE || c.u16()? != MAGIC {
+            return Err(anyhow!("not a CTF container"));
+        }
+
+        let (version, flags) = (data[2], data[3]);
+        let layout = Layout::new(version)?;
+        c.bytes(2)?;
+        let mut h = [0usize; 8];
+        for x in &mut h {
+            *x = c.u32()? as usize;
+        }
+        let [parent_label, parent_name, lbloff, objtoff, funcoff, typeoff, stroff, strlen] = h;
+
+        let end = stroff + strlen;
+        if !(lbloff <= objtoff && objtoff <= funcoff && funcoff <= typeoff && typeoff <= stroff) {
+            return Err(anyhow!("CTF sections are out of order"));
+        }
+
+        // The extension follows whatever is stored, compressed or not
+        let (body, extension) = if flags & F_COMPRESS != 0 {
+            let mut z = ZlibDecoder::new(&data[HEADER_SIZE..]);
+            let mut body = Vec::with_capacity(end);
+            z.by_ref()
+                .take(end as u64)
+                .read_to_end(&mut body)
+                .context("CTF could not be decompressed")?;
+            // Reach the end of the stream, so that we know where it is
+            if z.read(&mut [0])? != 0 {
+                return Err(anyhow!("CTF decompresses to more than it should"));
+            }
+            let used = z.total_in() as usize;
+            (Cow::Owned(body), &data[HEADER_SIZE + used..])
+        } else {
+            let body = &data[HEADER_SIZE..];
+            (Cow::Borrowed(body), body.get(end..).unwrap_or_default())
+        };
+        if body.len() < end {
+            return Err(anyhow!("CTF is truncated"));
+        }
+
+        let part = |start: usize, end: usize| Cursor {
+            data: &body[start..end],
+        };
+
+        let mut labels = Vec::new();
+        let mut c = part(lbloff, objtoff);
+        while !c.is_empty() {
+            labels.push(Label {
+                name: c.u32()?,
+                type_id: c.u32()?,
+            });
+        }
+
+        let mut objects = Vec::new();
+        let mut c = part(objtoff, funcoff);
+        while !c.is_empty() {
+            objects.push(c.id(layout)?);
+        }
+
+        let mut functions = Vec::new();
+        let mut c = part(funcoff, typeoff);
+        while !c.is_empty() {
+            let (kind, root, vlen) = layout.info(c.id(layout)?);
+            if kind == K_UNKNOWN && vlen == 0 {
+                functions.push(None);
+                continue;
+            }
+
+            functions.push(Some(FunctionInfo {
+                root,
+                ret: c.id(layout)?,
+                args: (0..vlen).map(|_| c.id(layout)).collect::<Result<_>>()?,
+            }));
+        }
+
+        let mut types = Vec::new();
+        let mut c = part(typeoff, stroff);
+        while !c.is_empty() {
+            let name = c.u32()?;
+            let (kind, root, vlen) = layout.info(c.id(layout)?);
+            let mut size = u64::from(c.id(layout)?);
+            if size == layout.lsize_sent() {
+                size = (u64::from(c.u32()?) << 32) | u64::from(c.u32()?);
+            }
+
+            let data = c.bytes(layout.data_size(kind, vlen, size)?)?.to_vec();
+            types.push(Type {
+                name,
+                kind,
+                root,
+                vlen,
+                size,
+                data,
+            });
+        }
+
+        Ok(Ctf {
+            version,
+            flags,
+            parent_label: parent_label as u32,
+            parent_name: parent_name as u32,
+            labels,
+            objects,
+            functions,
+            types,
+            strings: body[stroff..end].to_vec(),
+            saved_args: parse_extension(extension)?,
+        })
+    }
+
+    /// The container, followed by the extension if there is anything in it
+    pub fn write(&self) -> Result<Vec<u8>> {
+        let layout = Layout::new(self.version)?;
+
+        let mut body = Vec::new();
+        let mut offsets = Vec::new();
+
+        offsets.push(body.len());
+        for x in &self.labels {
+            body.extend(x.name.to_le_bytes());
+            body.extend(x.type_id.to_le_bytes());
+        }
+
+        offsets.push(body.len());
+        for &x in &self.objects {
+            put_id(&mut body, layout, x);
+        }
+
+        offsets.push(body.len());
+        for x in &self.functions {
+            let Some(x) = x else {
+                put_id(&mut body, layout, 0);
+                continue;
+            };
+
+            let info = layout.make_info(K_FUNCTION, x.root, x.args.len() as u32);
+            put_id(&mut body, layout, info);
+            put_id(&mut body, layout, x.ret);
+            for &arg in &x.args {
+                put_id(&mut body, layout, arg);
+            }
+        }
+
+        offsets.push(body.len());
+        for x in &self.types {
+            body.extend(x.name.to_le_bytes());
+            put_id(&mut body, layout, layout.make_info(x.kind, x.root, x.vlen));
+            if x.size >= layout.lsize_sent() {
+                put_id(&mut body, layout, layout.lsize_sent() as u32);
+                body.extend(((x.size >> 32) as u32).to_le_bytes());
+                body.extend((x.size as u32).to_le_bytes());
+            } else {
+                put_id(&mut body, layout, x.size as u32);
+            }
+            body.extend(&x.data);
+        }
+
+        offsets.push(body.len());
+        body.extend(&self.strings);
+
+        let mut out = Vec::with_capacity(HEADER_SIZE + body.len());
+        out.extend(MAGIC.to_le_bytes());
+        out.extend([self.version, self.flags]);
+        out.extend(self.parent_label.to_le_bytes());
+        out.extend(self.parent_name.to_le_bytes());
+        for x in offsets {
+            out.extend((x as u32).to_le_bytes());
+        }
+        out.extend((self.strings.len() as u32).to_le_bytes());
+        if self.flags & F_COMPRESS != 0 {
+            let mut z = ZlibEncoder::new(out, Compression::default());
+            z.write_all(&body)?;
+            out = z.finish()?;
+        } else {
+            out.extend(body);
+        }
+
+        if !self.saved_args.is_empty() {
+            for x in &self.saved_args {
+                out.extend(x.symbol.to_le_bytes());
+                out.extend(x.offset.to_le_bytes());
+                out.extend(x.register.to_le_bytes());
+                out.extend(x.nargs.to_le_bytes());
+            }
+            out.extend((self.saved_args.len() as u32).to_le_bytes());
+            out.extend(EXT_VERSION.to_le_bytes());
+            out.extend(EXT_MAGIC);
+        }
+
+        Ok(out)
+    }
+
+    /// The string at `name`, if it is in the container's own string table
+    pub fn string(&self, name: u32) -> Option<&str> {
+        if name & 0x8000_0000 != 0 {
+            return None; // In the ELF string table
+        }
+
+        let s = self.strings.get(name as usize..)?;
+        let end = s.iter().position(|&c| c == 0)?;
+        std::str::from_utf8(&s[..end]).ok()
+    }
+
+    /// The type with ID `id`, if it is in this container
+    pub fn type_by_id(&self, id: u32) -> Option<&Type> {
+        let child = if self.version == 2 {
+            id & 0x8000 != 0
+        } else {
+            id & 0x8000_0000 != 0
+        };
+        let index = id
+            & if self.version == 2 {
+                0x7fff
+            } else {
+                0x7fff_ffff
+            };
+
+        if child != (self.parent_name != 0) || index == 0 {
+            return None;
+        }
+
+        self.types.get(index as usize - 1)
+    }
+
+    /// The type with ID `id`, and the container it is in, which if this is a
+    /// child may be `parent`
+    ///
+    /// Only the types of the parent up to the label the child names are
+    /// found there; the rest were not there when the child was uniquified.
+    pub fn type_in<'a>(&'a self, parent: Option<&'a Ctf>, id: u32) -> Option<(&'a Ctf, &'a Type)> {
+        if self.parent_name == 0 || self.type_by_id(id).is_some() {
+            return self.type_by_id(id).map(|x| (self, x));
+        }
+
+        let parent = parent?;
+        let limit = self.parent_limit(parent)?;
+        let t = parent.type_by_id(id).filter(|_| id <= limit)?;
+        Some((parent, t))
+    }
+
+    /// The last type of `parent` which this child may refer to, if `parent`
+    /// has the label it names
+    pub fn parent_limit(&self, parent: &Ctf) -> Option<u32> {
+        let label = self.string(self.parent_label).filter(|x| !x.is_empty());
+        let Some(label) = label else {
+            // Unlabelled, the child may refer to anything in the parent
+            return Some(u32::MAX);
+        };
+
+        parent
+            .labels
+            .iter()
+            .find(|x| parent.string(x.name) == Some(label))
+            .map(|x| x.type_id)
+    }
+
+    /// Whether a value of the type with ID `id` is passed in an integer
+    /// register, if that can be known, by the same rules as for DWARF
+    pub fn is_integer(&self, parent: Option<&Ctf>, id: u32) -> Option<bool> {
+        self.is_integer_at(parent, id, 0)
+    }
+
+    fn is_integer_at(&self, parent: Option<&Ctf>, id: u32, depth: usize) -> Option<bool> {
+        // Types may be cyclic only by mistake, but mistakes are made
+        if depth > 64 {
+            return None;
+        }
+
+        let (owner, t) = self.type_in(parent, id)?;
+        match t.kind {
+            K_INTEGER | K_POINTER | K_ARRAY | K_ENUM => Some(true),
+            K_FLOAT | K_STRUCT => Some(false),
+            K_TYPEDEF | K_VOLATILE | K_CONST | K_RESTRICT => {
+                self.is_integer_at(parent, t.referenced()?, depth + 1)
+            }
+            K_UNION => {
+                let mut integer = true;
+                for x in t.member_types(owner.version) {
+                    integer &= self.is_integer_at(parent, x, depth + 1)?;
+                }
+                Some(integer)
+            }
+            _ => None,
+        }
+    }
+
+    /// A C-like name for the type with ID `id`, which if this is a child may
+    /// be in `parent`
+    pub fn type_name(&self, parent: Option<&Ctf>, id: u32) -> String {
+        let Some((owner, t)) = self.type_in(parent, id) else {
+            return format!("<type {id:#x}>");
+        };
+
+        if let Some(name) = owner.string(t.name).filter(|x| !x.is_empty()) {
+            return match t.kind {
+                K_STRUCT => format!("struct {name}"),
+                K_UNION => format!("union {name}"),
+                K_ENUM => format!("enum {name}"),
+                _ => name.to_string(),
+            };
+        }
+
+        match (t.kind, t.referenced()) {
+            (K_POINTER, Some(x)) => match self.type_name(parent, x) {
+                x if x.ends_with('*') => format!("{x}*"),
+                x => format!("{x} *"),
+            },
+            (K_CONST, Some(x)) => format!("const {}", self.type_name(parent, x)),
+            (K_VOLATILE, Some(x)) => format!("volatile {}", self.type_name(parent, x)),
+            (K_RESTRICT, Some(x)) => format!("{} restrict", self.type_name(parent, x)),
+            (K_FUNCTION, _) => String::from("<function>"),
+            (K_ARRAY, _) => String::from("<array>"),
+            (K_STRUCT, _) => String::from("struct <anonymous>"),
+            (K_UNION, _) => String::from("union <anonymous>"),
+            (K_ENUM, _) => String::from("enum <anonymous>"),
+            _ => format!("<type {id:#x}>"),
+        }
+    }
+}
+
+/// The saved argument records following a container, if there are any
+fn parse_extension(data: &[u8]) -> Result<Vec<SavedArgs>> {
+    if data.len() < EXT_TRAILER_SIZE || !data.ends_with(&EXT_MAGIC) {
+        return Ok(Vec::new());
+    }
+
+    let mut c = Cursor {
+        data: &data[data.len() - EXT_TRAILER_SIZE..],
+    };
+    let (count, version) = (c.u32()? as usize, c.u32()?);
+    if version != EXT_VERSION {
+        return Err(anyhow!(
+            "unsupported saved argument extension version {version}"
+        ));
+    }
+
+    let records = data.len() - EXT_TRAILER_SIZE;
+    if records < count * EXT_RECORD_SIZE {
+        return Err(anyhow!("saved argument extension is truncated"));
+    }
+
+    let mut c = Cursor {
+        data: &data[records - count * EXT_RECORD_SIZE..records],
+    };
+    (0..count)
+        .map(|_| {
+            Ok(SavedArgs {
+                symbol: c.u32()?,
+                offset: c.u32()? as i32,
+                register: c.u16()?,
+                nargs: c.u16()?,
+            })
+        })
+        .collect()
+}
+
+/// The index and name of the symbol which each entry of the function section
+/// describes, in order
+///
+/// As with every CTF consumer, these are the defined, named, function
+/// symbols of the symbol table.
+pub fn function_symbols<'data>(object: &object::File<'data>) -> Vec<(usize, &'data str)> {
+    object
+        .symbols()
+        .filter(|x| x.kind() == SymbolKind::Text && !x.is_undefined())
+        .filter_map(|x| Some((x.index().0, x.name().ok()?)))
+        .filter(|(_, name)| !name.is_empty() && *name != "_START_" && *name != "_END_")
+        .collect()
+}
+
+/// The index and name of the symbol which each entry of the object section
+/// describes, in order
+pub fn object_symbols<'data>(object: &object::File<'data>) -> Vec<(usize, &'data str)> {
+    object
+        .symbols()
+        .filter(|x| x.kind() == SymbolKind::Data && !x.is_undefined())
+        .filter(|x| !(x.section() == SymbolSection::Absolute && x.address() == 0))
+        .filter_map(|x| Some((x.index().0, x.name().ok()?)))
+        .filter(|(_, name)| !name.is_empty() && *name != "_START_" && *name != "_END_")
+        .collect()
+}
+
+/// The container in `object`, if it has one
+pub fn read(object: &object::File) -> Result<Option<Ctf>> {
+    match object.section_by_name(SECTION_NAME) {
+        Some(x) if x.size() > 0 => Ok(Some(Ctf::parse(x.data()?)?)),
+        _ => Ok(None),
+    }
+}
+
+/// The index, address and name of each defined function symbol
+fn text_symbols<'data>(object: &object::File<'data>) -> Vec<(usize, u64, &'data str)> {
+    object
+        .symbols()
+        .filter(|x| x.kind() == SymbolKind::Text && !x.is_undefined())
+        .filter_map(|x| Some((x.index().0, x.address(), x.name().ok()?)))
+        .collect()
+}
+
+/// The index of the symbol of `func`, preferring that of the same name, as
+/// there may be aliases
+fn symbol_of(symbols: &[(usize, u64, &str)], func: &FunctionRecord) -> Option<usize> {
+    let at = symbols
+        .iter()
+        .filter(|x| x.1 == func.range.start)
+        .collect::<Vec<_>>();
+
+    at.iter()
+        .find(|x| x.2 == func.name)
+        .or(at.first())
+        .map(|x| x.0)
+}
+
+/// A diagnostic concerning `func`, or the whole object
+fn diag(func: Option<&FunctionRecord>, kind: DiagnosticKind) -> Diagnostic {
+    Diagnostic {
+        die: func.map(|x| x.die),
+        function: func.map(|x| x.name.clone()),
+        source: func.map(|x| x.source.clone()).unwrap_or_default(),
+        severity: kind.default_severity(),
+        kind,
+    }
+}
+
+/// Each way in which the CTF of `object`, if it has any, disagrees with the
+/// DWARF, as [`cross_check`], or that it could not be read
+pub fn check(object: &object::File, parent: Option<&Ctf>, report: &ScanReport) -> Vec<Diagnostic> {
+    match read(object) {
+        Ok(Some(ctf)) => cross_check(object, &ctf, parent, report),
+        Ok(None) => Vec::new(),
+        Err(x) => vec![diag(None, DiagnosticKind::BadCtf(format!("{x:#}")))],
+    }
+}
+
+/// Each way in which `ctf`, with its `parent` if it has one, disagrees with
+/// the DWARF about the arguments of the functions in `report`
+///
+/// Functions without CTF are not compared, nor are arguments of types which
+/// CTF cannot find, as with a child whose parent we were not given.
+pub fn cross_check(
+    object: &object::File,
+    ctf: &Ctf,
+    parent: Option<&Ctf>,
+    report: &ScanReport,
+) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+
+    if let Some(p) = parent.filter(|p| ctf.parent_limit(p).is_none()) {
+        let label = ctf.string(ctf.parent_label).unwrap_or("<unknown>");
+        diags.push(diag(
+            None,
+            DiagnosticKind::BadCtf(format!(
+                "parent has no label {label} ({} labels)",
+                p.labels.len()
+            )),
+        ));
+        return diags;
+    }
+
+    // Entries of the function section, by the index of their symbol
+    let entries = function_symbols(object)
+        .into_iter()
+        .zip(&ctf.functions)
+        .filter_map(|((index, _), info)| Some((index, info.as_ref()?)))
+        .collect::<HashMap<_, _>>();
+    let symbols = text_symbols(object);
+
+    for func in &report.functions {
+        let Some(info) = symbol_of(&symbols, func).and_then(|x| entries.get(&x)) else {
+            continue;
+        };
+        let args = info.args.strip_suffix(&[0]).unwrap_or(&info.args);
+
+        if args.len() != func.parameters.len() {
+            diags.push(diag(
+                Some(func),
+                DiagnosticKind::CtfArgCount {
+                    ctf: args.len(),
+                    dwarf: func.parameters.len(),
+                },
+            ));
+            continue;
+        }
+
+        for (index, (&arg, param)) in args.iter().zip(&func.parameters).enumerate() {
+            match ctf.is_integer(parent, arg) {
+                Some(x) if x != param.integer => diags.push(diag(
+                    Some(func),
+                    DiagnosticKind::CtfArgType {
+                        index,
+                        name: param.name.clone(),
+                        integer: param.integer,
+                    },
+                )),
+                _ => (),
+            }
+        }
+    }
+
+    diags
+}
+
+/// A copy of the object in `data`, with its CTF extended to say where the
+/// arguments of each function in `report` are saved
+///
+/// A function which may write to its slots once they are stored is left
+/// out, as what a debugger would find in them is not to be trusted.
+pub fn annotate(data: &[u8], report: &ScanReport) -> Result<Vec<u8>> {
+    let object = object::File::parse(data)?;
+    let mut ctf = read(&object)?.ok_or_else(|| anyhow!("object has no CTF"))?;
+    let symbols = text_symbols(&object);
+
+    ctf.saved_args.clear();
+    for func in &report.functions {
+        let (Status::Saved { offset }, None) = (&func.status, func.clobbered) else {
+            continue;
+        };
+        let Some(symbol) = symbol_of(&symbols, func) else {
+            continue;
+        };
+
+        ctf.saved_args.push(SavedArgs {
+            symbol: symbol as u32,
+            offset: i32::try_from(*offset)
+                .with_context(|| format!("{}() saves arguments too far away", func.name))?,
+            register: func.base.register.0,
+            nargs: func.nparams() as u16,
+        });
+    }
+    ctf.saved_args.sort_by_key(|x| x.symbol);
+
+    elf::replace_section(data, SECTION_NAME, &ctf.write()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The offset in `strings` of `name`
+    fn name(strings: &[u8], name: &str) -> u32 {
+        let mut s = name.as_bytes().to_vec();
+        s.push(0);
+        strings.windows(s.len()).position(|x| x == s).unwrap() as u32
+    }
+
+    /// A container of `version`, with an integer, a pointer to it, and a
+    /// structure holding one, and functions taking each
+    fn sample(version: u8) -> Ctf {
+        let strings = b"\0int\0s\0x\0L\0".to_vec();
+        let member = |ty: u32| {
+            let mut data = name(&strings, "x").to_le_bytes().to_vec();
+            if version == 2 {
+                data.extend((ty as u16).to_le_bytes());
+                data.extend(0u16.to_le_bytes());
+            } else {
+                data.extend(ty.to_le_bytes());
+                data.extend(0u32.to_le_bytes());
+            }
+            data
+        };
+        let simple = |name, kind, size, data| Type {
+            name,
+            kind,
+            root: true,
+            vlen: 0,
+            size,
+            data,
+        };
+
+        Ctf {
+            version,
+            flags: 0,
+            parent_label: 0,
+            parent_name: 0,
+            labels: vec![Label {
+                name: name(&strings, "L"),
+                type_id: 3,
+            }],
+            objects: vec![1, 2],
+            functions: vec![
+                Some(FunctionInfo {
+                    root: true,
+                    ret: 1,
+                    args: vec![1, 2],
+                }),
+                None,
+                Some(FunctionInfo {
+                    root: false,
+                    ret: 0,
+                    args: vec![3, 0],
+                }),
+            ],
+            types: vec![
+                simple(name(&strings, "int"), K_INTEGER, 4, vec![0, 0, 0, 0x20]),
+                simple(0, K_POINTER, 1, Vec::new()),
+                Type {
+                    name: name(&strings, "s"),
+                    kind: K_STRUCT,
+                    root: true,
+                    vlen: 1,
+                    size: 4,
+                    data: member(1),
+                },
+            ],
+            strings,
+            saved_args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        for (version, flags) in [(2, 0), (2, F_COMPRESS), (3, 0), (3, F_COMPRESS)] {
+            let ctf = Ctf {
+                flags,
+                ..sample(version)
+            };
+            let data = ctf.write().unwrap();
+
+            assert_eq!(Ctf::parse(&data).unwrap(), ctf, "version {version}");
+            assert_eq!(data[3], flags);
+        }
+    }
+
+    #[test]
+    fn extension_round_trip() {
+        for flags in [0, F_COMPRESS] {
+            let mut ctf = Ctf { flags, ..sample(3) };
+            let plain = ctf.write().unwrap();
+            ctf.saved_args = vec![
+                SavedArgs {
+                    symbol: 3,
+                    offset: -40,
+                    register: 6,
+                    nargs: 3,
+                },
+                SavedArgs {
+                    symbol: 9,
+                    offset: -8,
+                    register: 29,
+                    nargs: 1,
+                },
+            ];
+            let data = ctf.write().unwrap();
+
+            // The container is untouched, and the extension follows it
+            assert_eq!(data[..plain.len()], plain);
+            assert_eq!(
+                data.len(),
+                plain.len() + 2 * EXT_RECORD_SIZE + EXT_TRAILER_SIZE
+            );
+            assert!(data.ends_with(&EXT_MAGIC));
+            assert_eq!(Ctf::parse(&data).unwrap(), ctf);
+        }
+    }
+
+    #[test]
+    fn extension_damage() {
+        let mut ctf = sample(3);
+        ctf.saved_args = vec![SavedArgs {
+            symbol: 3,
+            offset: -40,
+            register: 6,
+            nargs: 3,
+        }];
+        let data = ctf.write().unwrap();
+        let trailer = data.len() - EXT_TRAILER_SIZE;
+
+        // Whatever follows without the magic is none of ours
+        let mut other = data.clone();
+        *other.last_mut().unwrap() = b'X';
+        assert_eq!(Ctf::parse(&other).unwrap().saved_args, Vec::new());
+
+        let mut version = data.clone();
+        version[trailer + 4] = 2;
+        assert!(Ctf::parse(&version).is_err());
+
+        let mut count = data;
+        count[trailer] = 100;
+        assert!(Ctf::parse(&count).is_err());
+    }
+
+    #[test]
+    fn integer_types() {
+        let mut ctf = sample(3);
+        let referring = |kind, to| Type {
+            name: 0,
+            kind,
+            root: true,
+            vlen: 0,
+            size: to,
+            data: Vec::new(),
+        };
+        ctf.types.extend([
+            // 4, 5: a typedef of the structure, and a const of that
+            referring(K_TYPEDEF, 3),
+            referring(K_CONST, 4),
+            // 6: a float, and 7: a union of it and an integer
+            Type {
+                name: 0,
+                kind: K_FLOAT,
+                root: true,
+                vlen: 0,
+                size: 8,
+                data: vec![0; 4],
+            },
+            Type {
+                name: 0,
+                kind: K_UNION,
+                root: true,
+                vlen: 2,
+                size: 8,
+                data: [0u32, 1, 0, 0, 6, 0]
+                    .iter()
+                    .flat_map(|x| x.to_le_bytes())
+                    .collect(),
+            },
+            // 8: a typedef of itself
+            referring(K_TYPEDEF, 8),
+        ]);
+
+        assert_eq!(ctf.is_integer(None, 1), Some(true));
+        assert_eq!(ctf.is_integer(None, 2), Some(true));
+        assert_eq!(ctf.is_integer(None, 3), Some(false));
+        assert_eq!(ctf.is_integer(None, 5), Some(false));
+        assert_eq!(ctf.is_integer(None, 6), Some(false));
+        assert_eq!(ctf.is_integer(None, 7), Some(false));
+        assert_eq!(ctf.is_integer(None, 8), None);
+        assert_eq!(ctf.is_integer(None, 9), None);
+
+        assert_eq!(ctf.type_name(None, 2), "int *");
+        assert_eq!(ctf.type_name(None, 3), "struct s");
+        assert_eq!(ctf.type_name(None, 7), "union <anonymous>");
+        assert_eq!(ctf.type_name(None, 9), "<type 0x9>");
+    }
+
+    #[test]
+    fn parent() {
+        let mut parent = sample(3);
+        parent.labels[0].type_id = 2;
+
+        let strings = b"\0p\0L\0t\0".to_vec();
+        let typedef = |to| Type {
+            name: name(&strings, "t"),
+            kind: K_TYPEDEF,
+            root: true,
+            vlen: 0,
+            size: to,
+            data: Vec::new(),
+        };
+        let child = Ctf {
+            parent_label: name(&strings, "L"),
+            parent_name: name(&strings, "p"),
+            labels: Vec::new(),
+            objects: Vec::new(),
+            functions: Vec::new(),
+            types: vec![typedef(1), typedef(3)],
+            strings,
+            ..sample(3)
+        };
+        let (first, second) = (0x8000_0001, 0x8000_0002);
+
+        assert_eq!(child.parent_limit(&parent), Some(2));
+        assert_eq!(child.type_by_id(1), None);
+        assert_eq!(child.type_in(Some(&parent), 1).unwrap().1, &parent.types[0]);
+        // Beyond the label, the type was not there to be shared
+        assert!(child.type_in(Some(&parent), 3).is_none());
+
+        assert_eq!(child.is_integer(Some(&parent), first), Some(true));
+        assert_eq!(child.is_integer(None, first), None);
+        assert_eq!(child.is_integer(Some(&parent), second), None);
+        assert_eq!(child.type_name(Some(&parent), first), "t");
+
+        parent.labels.clear();
+        assert_eq!(child.parent_limit(&parent), None);
+    }
+
+    #[test]
+    fn container_damage() {
+        let data = sample(3).write().unwrap();
+
+        assert!(Ctf::parse(&data[..HEADER_SIZE - 1]).is_err());
+        assert!(Ctf::parse(&data[..data.len() - 1]).is_err());
+
+        let mut magic = data.clone();
+        magic[0] = 0;
+        assert!(Ctf::parse(&magic).is_err());
+
+        let mut version = data.clone();
+        version[2] = 4;
+        assert!(Ctf::parse(&version).is_err());
+
+        // The type section before the function section
+        let mut order = data;
+        order[24..28].copy_from_slice(&0u32.to_le_bytes());
+        assert!(Ctf::parse(&order).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+//! Comparison of two sets of results, to find where saved arguments were
+//! lost (or gained)
+//!
+//! Functions are matched by the object containing them, their name, and the
+//! compilation unit containing them, relative to the directory in which it
+//! was compiled.  Diagnostics are matched by object, function and code, as
+//! in a [baseline](crate::baseline), so that those which name an address do
+//! not differ merely because the code has moved.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::schema::{self, FunctionStatus, Record};
+
+/// How functions are matched between the two sides
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub object: String,
+    pub name: String,
+    pub unit: Option<String>,
+}
+
+/// `unit` relative to `comp_dir`, if it is within it
+fn relative(unit: &str, comp_dir: Option<&str>) -> String {
+    match comp_dir.and_then(|dir| Path::new(unit).strip_prefix(dir).ok()) {
+        Some(x) => x.to_string_lossy().into_owned(),
+        None => unit.to_string(),
+    }
+}
+
+impl Key {
+    fn of(func: &schema::Function) -> Self {
+        Key {
+            object: func.path.clone(),
+            name: func.name.clone(),
+            unit: func
+                .unit
+                .as_deref()
+                .map(|x| relative(x, func.comp_dir.as_deref())),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}()", self.object, self.name)?;
+        if let Some(unit) = &self.unit {
+            write!(f, " ({unit})")?;
+        }
+        Ok(())
+    }
+}
+
+/// A difference between the two sides
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The function now saves its arguments
+    Added { key: Key },
+    /// The function no longer saves its arguments
+    Removed { key: Key },
+    /// A function which saved its arguments is no longer present at all
+    Vanished { key: Key },
+    /// The saved arguments have moved
+    Offset { key: Key, old: i64, new: i64 },
+    /// The part of the function in which the arguments can be found has
+    /// changed by more than the threshold
+    Coverage { key: Key, old: f64, new: f64 },
+    /// A diagnostic which was not previously issued, or not as often, in
+    /// the function
+    Diagnostic {
+        object: String,
+        function: Option<String>,
+        code: String,
+        message: String,
+    },
+}
+
+impl Change {
+    /// True if this makes arguments harder to find
+    pub fn is_regression(&self) -> bool {
+        match self {
+            Change::Removed { .. } | Change::Diagnostic { .. } => true,
+            Change::Coverage { old, new, .. } => new < old,
+            Change::Added { .. } | Change::Vanished { .. } | Change::Offset { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added { key } => write!(f, "added: {key} now saves arguments"),
+            Change::Removed { key } => write!(f, "removed: {key} no longer saves arguments"),
+            Change::Vanished { key } => write!(f, "vanished: {key} is no longer present"),
+            Change::Offset { key, old, new } => {
+                write!(f, "offset: {key} saved arguments moved from {old} to {new}")
+            }
+            Change::Coverage { key, old, new } => {
+                write!(f, "coverage: {key} valid in {old:2.2}% -> {new:2.2}%")
+            }
+            Change::Diagnostic {
+                object,
+                function,
+                code,
+                message,
+            } => {
+                write!(f, "diagnostic: {object}: ")?;
+                if let Some(function) = function {
+                    write!(f, "{function}(): ")?;
+                }
+                write!(f, "{message} [{code}]")
+            }
+        }
+    }
+}
+
+fn functions(records: &[Record]) -> HashMap<Key, &schema::Function> {
+    let mut ret = HashMap::new();
+
+    for record in records {
+        if let Record::Function(func) = record {
+            ret.entry(Key::of(func)).or_insert(func);
+        }
+    }
+
+    ret
+}
+
+/// How diagnostics are matched between the two sides
+type DiagnosticKey<'a> = (&'a str, Option<&'a str>, &'a str);
+
+fn diagnostics(records: &[Record]) -> impl Iterator<Item = &schema::Diagnostic> {
+    records.iter().filter_map(|x| match x {
+        Record::Diagnostic(d) => Some(d),
+        _ => None,
+    })
+}
+
+/// The key of `diag`, by its message if it was written before diagnostics
+/// had codes
+fn diagnostic_key(diag: &schema::Diagnostic) -> DiagnosticKey<'_> {
+    let code = match diag.code.as_str() {
+        "" => &diag.message,
+        x => x,
+    };
+
+    (&diag.path, diag.function.as_deref(), code)
+}
+
+/// Compare `old` with `new`, reporting changes in coverage of more than
+/// `threshold` percentage points, where it means the same on both sides
+///
+/// Changes are reported in the order the functions appear in `new`, then
+/// those which vanished in the order they appear in `old`.
+pub fn diff(old: &[Record], new: &[Record], threshold: f64) -> Vec<Change> {
+    let old_funcs = functions(old);
+    let mut changes = Vec::new();
+    let mut seen = HashSet::new();
+
+    for record in new {
+        let Record::Function(new_func) = record else {
+            continue;
+        };
+        let key = Key::of(new_func);
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+
+        let old_func = old_funcs.get(&key);
+        let was_saved = old_func.is_some_and(|x| x.status == FunctionStatus::Saved);
+
+        match (was_saved, new_func.status) {
+            (false, FunctionStatus::Saved) => changes.push(Change::Added { key }),
+            (true, FunctionStatus::Missing) => changes.push(Change::Removed { key }),
+            (true, FunctionStatus::Saved) => {
+                let old_func = old_func.unwrap();
+
+                if let (Some(o), Some(n)) = (old_func.offset, new_func.offset) {
+                    if o != n {
+                        changes.push(Change::Offset {
+                            key: key.clone(),
+                            old: o,
+                            new: n,
+                        });
+                    }
+                }
+
+                if new_func.same_coverage(old_func)
+                    && (new_func.coverage - old_func.coverage).abs() > threshold
+                {
+                    changes.push(Change::Coverage {
+                        key,
+                        old: old_func.coverage,
+                        new: new_func.coverage,
+                    });
+                }
+            }
+            (false, FunctionStatus::Missing) => (),
+        }
+    }
+
+    for record in old {
+        if let Record::Function(func) = record {
+            let key = Key::of(func);
+            if func.status == FunctionStatus::Saved && seen.insert(key.clone()) {
+                changes.push(Change::Vanished { key });
+            }
+        }
+    }
+
+    let mut old_diags = HashMap::<DiagnosticKey, usize>::new();
+    for diag in diagnostics(old) {
+        *old_diags.entry(diagnostic_key(diag)).or_default() += 1;
+    }
+
+    for diag in diagnostics(new) {
+        match old_diags.get_mut(&diagnostic_key(diag)) {
+            Some(n) if *n > 0 => *n -= 1,
+            _ => changes.push(Change::Diagnostic {
+                object: diag.path.clone(),
+                function: diag.function.clone(),
+                code: diag.code.clone(),
+                message: diag.message.clone(),
+            }),
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::function;
+    use crate::{ScanReport, Severity, Status};
+
+    /// The record of a function `name` in `t`, saving its arguments at
+    /// `offset` if there is one, and valid in `coverage` percent of it
+    fn func(name: &str, offset: Option<i64>, coverage: f64) -> Record {
+        let status = match offset {
+            Some(offset) => Status::Saved { offset },
+            None => Status::Missing,
+        };
+        let report = ScanReport {
+            functions: vec![function(name, 0x1000, 0x1040, status)],
+            ..Default::default()
+        };
+
+        let mut ret = schema::records("t", &report).remove(0);
+        if let Record::Function(x) = &mut ret {
+            x.unit = Some("/build/a/t.c".to_string());
+            x.comp_dir = Some("/build/a".to_string());
+            x.coverage = coverage;
+        }
+        ret
+    }
+
+    fn diag(function: &str, code: &str, message: &str) -> Record {
+        Record::Diagnostic(schema::Diagnostic {
+            path: "t".to_string(),
+            die: Some(1),
+            function: Some(function.to_string()),
+            unit: None,
+            comp_dir: None,
+            file: None,
+            line: None,
+            code: code.to_string(),
+            severity: Severity::Error,
+            message: message.to_string(),
+        })
+    }
+
+    fn key(name: &str) -> Key {
+        Key {
+            object: "t".to_string(),
+            name: name.to_string(),
+            unit: Some("t.c".to_string()),
+        }
+    }
+
+    #[test]
+    fn changes() {
+        let old = [
+            func("added", None, 0.0),
+            func("removed", Some(-40), 50.0),
+            func("vanished", Some(-40), 50.0),
+            func("moved", Some(-40), 50.0),
+            func("worse", Some(-40), 50.0),
+            func("better", Some(-40), 50.0),
+            func("same", Some(-40), 50.0),
+        ];
+        let new = [
+            func("added", Some(-40), 50.0),
+            func("removed", None, 0.0),
+            func("moved", Some(-48), 50.0),
+            func("worse", Some(-40), 40.0),
+            func("better", Some(-40), 60.0),
+            func("same", Some(-40), 54.0),
+            func("new", None, 0.0),
+        ];
+
+        let changes = diff(&old, &new, 5.0);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Added { key: key("added") },
+                Change::Removed {
+                    key: key("removed")
+                },
+                Change::Offset {
+                    key: key("moved"),
+                    old: -40,
+                    new: -48
+                },
+                Change::Coverage {
+                    key: key("worse"),
+                    old: 50.0,
+                    new: 40.0
+                },
+                Change::Coverage {
+                    key: key("better"),
+                    old: 50.0,
+                    new: 60.0
+                },
+                Change::Vanished {
+                    key: key("vanished")
+                },
+            ]
+        );
+        assert_eq!(
+            changes
+                .iter()
+                .map(Change::is_regression)
+                .collect::<Vec<_>>(),
+            vec![false, true, false, true, false, false]
+        );
+
+        // Nothing is so small a change that it is not reported at all
+        assert_eq!(diff(&old, &new, 0.0).len(), 7);
+    }
+
+    #[test]
+    fn units_elsewhere() {
+        // The same unit, built in another place
+        let old = [func("f", Some(-40), 50.0)];
+        let mut new = [func("f", Some(-40), 50.0)];
+        if let Record::Function(x) = &mut new[0] {
+            x.unit = Some("/build/b/t.c".to_string());
+            x.comp_dir = Some("/build/b".to_string());
+        }
+        assert_eq!(diff(&old, &new, 0.0), vec![]);
+
+        // And a different one
+        if let Record::Function(x) = &mut new[0] {
+            x.unit = Some("/build/b/u.c".to_string());
+        }
+        assert_eq!(diff(&old, &new, 0.0).len(), 2);
+    }
+
+    #[test]
+    fn coverage_versions() {
+        // Coverage meant something else before version 3
+        let mut old = [func("f", Some(-40), 80.0)];
+        if let Record::Function(x) = &mut old[0] {
+            x.version = 2;
+        }
+        let new = [func("f", Some(-40), 50.0)];
+        assert_eq!(diff(&old, &new, 5.0), vec![]);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let old = [
+            diag("f", "saved-clobbered", "written at 0x1010"),
+            diag("g", "no-saved-args", "g(): 1 parameters"),
+        ];
+
+        // The code having moved is no change at all
+        let new = [
+            diag("f", "saved-clobbered", "written at 0x1020"),
+            diag("g", "no-saved-args", "g(): 1 parameters"),
+        ];
+        assert_eq!(diff(&old, &new, 0.0), vec![]);
+
+        // But more of them, or of another kind or function, is
+        let new = [
+            diag("f", "saved-clobbered", "written at 0x1020"),
+            diag("f", "saved-clobbered", "written at 0x1030"),
+            diag("f", "no-saved-args", "f(): 1 parameters"),
+            diag("h", "no-saved-args", "h(): 1 parameters"),
+        ];
+        let changes = diff(&old, &new, 0.0);
+        assert_eq!(changes.len(), 3);
+        assert!(changes.iter().all(Change::is_regression));
+        assert_eq!(
+            changes[0].to_string(),
+            "diagnostic: t: f(): written at 0x1030 [saved-clobbered]"
+        );
+
+        // Those without a code are known by their message
+        let old = [diag("f", "", "f(): bad")];
+        let new = [diag("f", "", "f(): bad"), diag("f", "", "f(): worse")];
+        assert_eq!(diff(&old, &new, 0.0).len(), 1);
+    }
+}
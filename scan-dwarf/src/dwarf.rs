@@ -0,0 +1,204 @@
+// Helpers for asking the questions we care about of DWARF entries
+
+use anyhow::{anyhow, Result};
+
+use crate::scan::Outcome;
+
+/// Return the offset of a given DebuggingInformationEntry
+pub(crate) fn entry_to_die_offset<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+    unit: &gimli::Unit<T>,
+) -> Option<<T as gimli::Reader>::Offset> {
+    entry
+        .offset()
+        .to_debug_info_offset(&unit.header)
+        .map(|x| x.0)
+}
+
+/// Translate various kinds of DWARF attribute values to strings
+pub(crate) fn attr_to_string<T: gimli::Reader>(
+    attr: gimli::AttributeValue<T>,
+    dwarf: &gimli::Dwarf<T>,
+    unit: &gimli::Unit<T>,
+) -> Option<String> {
+    match attr {
+        gimli::AttributeValue::DebugStrRef(_) => dwarf
+            .attr_string(unit, attr)
+            .unwrap()
+            .to_string()
+            .ok()
+            .map(|x| x.into_owned()),
+        gimli::AttributeValue::String(_) => dwarf
+            .attr_string(unit, attr)
+            .unwrap()
+            .to_string()
+            .ok()
+            .map(|x| x.into_owned()),
+        gimli::AttributeValue::DebugLineStrRef(_) => dwarf
+            .attr_string(unit, attr)
+            .unwrap()
+            .to_string()
+            .ok()
+            .map(|x| x.into_owned()),
+        gimli::AttributeValue::FileIndex(n) => {
+            let nameref = unit
+                .line_program
+                .as_ref()
+                .and_then(|x| x.header().file(n).map(|f| f.path_name()))
+                .unwrap();
+
+            attr_to_string(nameref, dwarf, unit)
+        }
+        _ => panic!("Unknown attribute for string conversion: {attr:?}"),
+    }
+}
+
+/// Why this function is not concrete, meaning in our terms that it is a
+/// prototype, an abstract parent of an inlined call, or itself inlined, or
+/// `None` if it is concrete
+pub(crate) fn non_concrete_outcome<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+) -> Result<Option<Outcome>> {
+    assert!(entry.tag() == gimli::DW_TAG_subprogram);
+
+    if entry.attr_value(gimli::DW_AT_declaration)?.is_some() {
+        Ok(Some(Outcome::Declaration))
+    } else if entry.attr_value(gimli::DW_AT_abstract_origin)?.is_some() {
+        Ok(Some(Outcome::Inlined))
+    } else {
+        match entry.attr_value(gimli::DW_AT_inline)? {
+            Some(gimli::AttributeValue::Inline(x)) => match x {
+                gimli::DW_INL_inlined => Ok(Some(Outcome::Abstract)),
+                gimli::DW_INL_declared_inlined => Ok(Some(Outcome::Abstract)),
+                gimli::DW_INL_not_inlined => Ok(None),
+                gimli::DW_INL_declared_not_inlined => Ok(None),
+                _ => Err(anyhow!("function has weird inline attribute: {x:?}")),
+            },
+            Some(x) => Err(anyhow!("function has weird inline attribute type: {x:?}")),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Source file of the given DebuggingInformationEntry, with its directory if
+/// the line program knows it
+pub(crate) fn die_source_file<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+    dwarf: &gimli::Dwarf<T>,
+    unit: &gimli::Unit<T>,
+) -> Result<Option<String>> {
+    let index = match entry.attr_value(gimli::DW_AT_decl_file)? {
+        Some(gimli::AttributeValue::FileIndex(n)) => n,
+        Some(x) => return Ok(attr_to_string(x, dwarf, unit)),
+        None => return Ok(None),
+    };
+
+    let header = unit.line_program.as_ref().map(|x| x.header()).unwrap();
+    let file = header
+        .file(index)
+        .ok_or_else(|| anyhow!("no file with index {index}"))?;
+    let name = attr_to_string(file.path_name(), dwarf, unit);
+    let dir = file
+        .directory(header)
+        .and_then(|x| attr_to_string(x, dwarf, unit));
+
+    Ok(name.map(|name| match dir {
+        Some(dir) if !dir.is_empty() && !name.starts_with('/') => format!("{dir}/{name}"),
+        _ => name,
+    }))
+}
+
+/// The line on which the given DebuggingInformationEntry is declared
+pub(crate) fn die_source_line<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+) -> Result<Option<u64>> {
+    Ok(entry
+        .attr_value(gimli::DW_AT_decl_line)?
+        .and_then(|x| x.udata_value()))
+}
+
+/// True if a DebuggingInformationEntry is not assembler (we're lax about what "C source" means)
+pub(crate) fn die_has_c_source<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+    dwarf: &gimli::Dwarf<T>,
+    unit: &gimli::Unit<T>,
+) -> Result<bool> {
+    match die_source_file(entry, dwarf, unit)? {
+        Some(file) if file.ends_with(".s") || file.ends_with(".S") => Ok(false),
+        Some(_) => Ok(true),
+        None => Ok(false), // GAS has no source file names
+    }
+}
+
+/// True if the type of this entry would be passed in an integer register
+pub(crate) fn is_register_type<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+    unit: &gimli::Unit<T>,
+) -> Result<bool> {
+    let tipe = match entry.attr_value(gimli::DW_AT_type)? {
+        Some(gimli::AttributeValue::UnitRef(x)) => unit.entry(x)?,
+        Some(x) => return Err(anyhow!("type has weird value type: {x:?}")),
+        None => return Err(anyhow!("entry has no value?")),
+    };
+
+    match tipe.tag() {
+        gimli::DW_TAG_base_type => match tipe.attr_value(gimli::DW_AT_encoding)? {
+            Some(gimli::AttributeValue::Encoding(x)) => match x {
+                gimli::DW_ATE_complex_float | gimli::DW_ATE_float => Ok(false),
+                gimli::DW_ATE_signed | gimli::DW_ATE_unsigned => Ok(true),
+                gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char => Ok(true),
+                gimli::DW_ATE_boolean => Ok(true),
+                _ => Err(anyhow!("base type has unknown encoding: {x:?}")),
+            },
+            Some(x) => Err(anyhow!("base type has weird encoding: {x:?}")),
+            None => Err(anyhow!("base type has no encoding!")),
+        },
+        gimli::DW_TAG_pointer_type => Ok(true),
+        gimli::DW_TAG_array_type => Ok(true),
+        gimli::DW_TAG_enumeration_type => Ok(true),
+        gimli::DW_TAG_volatile_type => is_register_type(&tipe, unit),
+        gimli::DW_TAG_typedef => is_register_type(&tipe, unit),
+        gimli::DW_TAG_const_type => is_register_type(&tipe, unit),
+        gimli::DW_TAG_structure_type => Ok(false),
+        gimli::DW_TAG_union_type => {
+            // A union fits in a register if all of its variants do
+            let mut tree = unit.entries_tree(Some(tipe.offset()))?;
+            let root = tree.root()?;
+            let mut children = root.children();
+
+            while let Some(child) = children.next()? {
+                if !is_register_type(child.entry(), unit)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        }
+        x => Err(anyhow!("entry has unknown type type: {x:?}")),
+    }
+}
+
+/// True if the type of this entry is a structure or union too large to be
+/// returned in registers, which is returned in memory at an address passed
+/// before the arguments
+pub(crate) fn is_memory_type<T: gimli::Reader>(
+    entry: &gimli::DebuggingInformationEntry<T>,
+    unit: &gimli::Unit<T>,
+) -> Result<bool> {
+    let tipe = match entry.attr_value(gimli::DW_AT_type)? {
+        Some(gimli::AttributeValue::UnitRef(x)) => unit.entry(x)?,
+        Some(x) => return Err(anyhow!("type has weird value type: {x:?}")),
+        None => return Ok(false),
+    };
+
+    match tipe.tag() {
+        gimli::DW_TAG_volatile_type => is_memory_type(&tipe, unit),
+        gimli::DW_TAG_typedef => is_memory_type(&tipe, unit),
+        gimli::DW_TAG_const_type => is_memory_type(&tipe, unit),
+        gimli::DW_TAG_structure_type | gimli::DW_TAG_union_type => Ok(tipe
+            .attr_value(gimli::DW_AT_byte_size)?
+            .and_then(|x| x.udata_value())
+            .is_some_and(|x| x > 16)),
+        _ => Ok(false),
+    }
+}
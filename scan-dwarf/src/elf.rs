@@ -0,0 +1,329 @@
+// Just enough editing of 64-bit ELF objects to add sections to them, and
+// replace the contents of those they have.
+//
+// Rather than lay the object out afresh, everything we change is appended:
+// new section contents, a copy of the section name table with its name
+// added, and a copy of the section headers describing them.  The originals are
+// left where they were, unreferenced, so no offset or address in the object
+// moves.  Only non-allocated sections may be changed this way, since an
+// allocated section would need a segment to hold it.
+
+use std::mem;
+
+use anyhow::{anyhow, Result};
+use object::elf::{
+    FileHeader64, SectionHeader64, SHF_ALLOC, SHF_COMPRESSED, SHN_LORESERVE, SHT_PROGBITS,
+};
+use object::read::elf::{FileHeader, SectionHeader};
+use object::{pod, Endianness, U16, U32, U64};
+
+type Header = FileHeader64<Endianness>;
+type Section = SectionHeader64<Endianness>;
+
+/// The header and section headers of the 64-bit ELF object in `data`
+fn headers(data: &[u8]) -> Result<(&Header, Endianness, Vec<Section>)> {
+    let header = Header::parse(data)
+        .map_err(|x| anyhow!("only 64-bit ELF objects may be rewritten: {x}"))?;
+    let endian = header.endian()?;
+
+    if header.e_shnum.get(endian) == 0 {
+        return Err(anyhow!(
+            "objects with extended section numbering cannot be rewritten"
+        ));
+    }
+
+    Ok((
+        header,
+        endian,
+        header.section_headers(endian, data)?.to_vec(),
+    ))
+}
+
+/// The name of each section of the object in `data`
+fn names<'data>(
+    data: &'data [u8],
+    header: &Header,
+    endian: Endianness,
+) -> Result<Vec<&'data [u8]>> {
+    let table = header.sections(endian, data)?;
+
+    table
+        .iter()
+        .map(|x| Ok(table.section_name(endian, x)?))
+        .collect()
+}
+
+fn pad(v: &mut Vec<u8>, align: usize) {
+    v.resize(v.len().next_multiple_of(align), 0);
+}
+
+/// `data` with `headers` in place of its section headers, appended after
+/// whatever else has been appended to it
+fn finish(
+    mut data: Vec<u8>,
+    header: &Header,
+    endian: Endianness,
+    headers: &[Section],
+) -> Result<Vec<u8>> {
+    if headers.len() >= usize::from(SHN_LORESERVE) {
+        return Err(anyhow!("too many sections"));
+    }
+
+    pad(&mut data, mem::align_of::<u64>());
+    let mut header = *header;
+    header.e_shoff = U64::new(endian, data.len() as u64);
+    header.e_shnum = U16::new(endian, headers.len() as u16);
+
+    for x in headers {
+        data.extend_from_slice(pod::bytes_of(x));
+    }
+    data[..mem::size_of_val(&header)].copy_from_slice(pod::bytes_of(&header));
+
+    Ok(data)
+}
+
+/// A copy of the object in `data`, with each section named in `contents`
+/// holding what is given there
+///
+/// Those sections the object has must not be allocated.  Those it does not
+/// are added, not allocated either.
+pub(crate) fn update_sections(data: &[u8], contents: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+    let (header, endian, mut headers) = headers(data)?;
+    let shstrndx = header.shstrndx(endian, data)? as usize;
+    let names = names(data, header, endian)?;
+    let mut strings = None;
+    let mut out = data.to_vec();
+
+    for &(name, contents) in contents {
+        let section = match names.iter().position(|x| *x == name.as_bytes()) {
+            Some(i) => {
+                let section = &mut headers[i];
+                let flags = section.sh_flags.get(endian);
+                if flags & u64::from(SHF_ALLOC) != 0 {
+                    return Err(anyhow!("{name} is allocated, and cannot be replaced"));
+                }
+                // What we write is never compressed
+                section.sh_flags = U64::new(endian, flags & !u64::from(SHF_COMPRESSED));
+                section
+            }
+            None => {
+                let strings = match &mut strings {
+                    Some(x) => x,
+                    None => strings.insert(headers[shstrndx].data(endian, data)?.to_vec()),
+                };
+                let name_offset = strings.len();
+                strings.extend_from_slice(name.as_bytes());
+                strings.push(0);
+
+                headers.push(Section {
+                    sh_name: U32::new(endian, name_offset as u32),
+                    sh_type: U32::new(endian, SHT_PROGBITS),
+                    sh_flags: U64::new(endian, 0),
+                    sh_addr: U64::new(endian, 0),
+                    sh_offset: U64::new(endian, 0),
+                    sh_size: U64::new(endian, 0),
+                    sh_link: U32::new(endian, 0),
+                    sh_info: U32::new(endian, 0),
+                    sh_addralign: U64::new(endian, mem::align_of::<u64>() as u64),
+                    sh_entsize: U64::new(endian, 0),
+                });
+                headers.last_mut().unwrap()
+            }
+        };
+
+        pad(&mut out, section.sh_addralign.get(endian).max(1) as usize);
+        section.sh_offset = U64::new(endian, out.len() as u64);
+        section.sh_size = U64::new(endian, contents.len() as u64);
+        out.extend_from_slice(contents);
+    }
+
+    if let Some(strings) = strings {
+        headers[shstrndx].sh_offset = U64::new(endian, out.len() as u64);
+        headers[shstrndx].sh_size = U64::new(endian, strings.len() as u64);
+        out.extend_from_slice(&strings);
+    }
+
+    finish(out, header, endian, &headers)
+}
+
+/// A copy of the object in `data`, with a non-allocated section `name`
+/// holding `contents`
+pub(crate) fn add_section(data: &[u8], name: &str, contents: &[u8]) -> Result<Vec<u8>> {
+    let (header, endian, _) = headers(data)?;
+    if names(data, header, endian)?.contains(&name.as_bytes()) {
+        return Err(anyhow!("object already has a {name} section"));
+    }
+
+    update_sections(data, &[(name, contents)])
+}
+
+/// A copy of the object in `data`, with the contents of the non-allocated
+/// section `name` replaced by `contents`
+pub(crate) fn replace_section(data: &[u8], name: &str, contents: &[u8]) -> Result<Vec<u8>> {
+    let (header, endian, _) = headers(data)?;
+    if !names(data, header, endian)?.contains(&name.as_bytes()) {
+        return Err(anyhow!("object has no {name} section"));
+    }
+
+    update_sections(data, &[(name, contents)])
+}
+
+/// A copy of the object in `data` in which each section whose name `hide`
+/// accepts is null, as if it had been stripped
+///
+/// The sections keep their indices, so nothing referring to other sections
+/// need change, but neither their names nor their contents can be found.
+pub(crate) fn hide_sections(data: &[u8], hide: impl Fn(&[u8]) -> bool) -> Result<Vec<u8>> {
+    let (header, endian, mut headers) = headers(data)?;
+    let names = names(data, header, endian)?;
+
+    for (x, name) in headers.iter_mut().zip(names) {
+        if hide(name) {
+            // A header of all zeros is SHT_NULL
+            pod::bytes_of_mut(x).fill(0);
+        }
+    }
+
+    finish(data.to_vec(), header, endian, &headers)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use object::elf::{
+        Ident, ELFCLASS64, ELFDATA2LSB, EM_X86_64, ET_REL, EV_CURRENT, SHF_EXECINSTR, SHT_NOTE,
+        SHT_STRTAB,
+    };
+    use object::{Object, ObjectSection};
+
+    use super::*;
+
+    /// An amd64 object of ELF type `kind`, with `code` in `.text` and a
+    /// non-allocated section for each of `sections`, a note if it is named
+    /// as one
+    pub(crate) fn object_with(kind: u16, code: &[u8], sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let endian = Endianness::Little;
+        let section = |name: usize, kind, flags: u32, offset: usize, size: usize| Section {
+            sh_name: U32::new(endian, name as u32),
+            sh_type: U32::new(endian, kind),
+            sh_flags: U64::new(endian, u64::from(flags)),
+            sh_addr: U64::new(endian, 0),
+            sh_offset: U64::new(endian, offset as u64),
+            sh_size: U64::new(endian, size as u64),
+            sh_link: U32::new(endian, 0),
+            sh_info: U32::new(endian, 0),
+            sh_addralign: U64::new(endian, 1),
+            sh_entsize: U64::new(endian, 0),
+        };
+
+        let mut data = vec![0; mem::size_of::<Header>()];
+        let mut strings = vec![0];
+        let mut headers = vec![section(0, 0, 0, 0, 0)];
+        let mut add = |name: &str, kind, flags, contents: &[u8]| {
+            headers.push(section(
+                strings.len(),
+                kind,
+                flags,
+                data.len(),
+                contents.len(),
+            ));
+            strings.extend_from_slice(name.as_bytes());
+            strings.push(0);
+            data.extend_from_slice(contents);
+        };
+
+        add(".text", SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR, code);
+        for (name, contents) in sections {
+            let kind = match name.starts_with(".note") {
+                true => SHT_NOTE,
+                false => SHT_PROGBITS,
+            };
+            add(name, kind, 0, contents);
+        }
+        // The names are all known only once it is named
+        add(".shstrtab", SHT_STRTAB, 0, &[]);
+        let shstrndx = headers.len() - 1;
+        headers[shstrndx].sh_offset = U64::new(endian, data.len() as u64);
+        headers[shstrndx].sh_size = U64::new(endian, strings.len() as u64);
+        data.extend_from_slice(&strings);
+
+        let header = Header {
+            e_ident: Ident {
+                magic: object::elf::ELFMAG,
+                class: ELFCLASS64,
+                data: ELFDATA2LSB,
+                version: EV_CURRENT,
+                os_abi: 0,
+                abi_version: 0,
+                padding: [0; 7],
+            },
+            e_type: U16::new(endian, kind),
+            e_machine: U16::new(endian, EM_X86_64),
+            e_version: U32::new(endian, u32::from(EV_CURRENT)),
+            e_entry: U64::new(endian, 0),
+            e_phoff: U64::new(endian, 0),
+            e_shoff: U64::new(endian, 0),
+            e_flags: U32::new(endian, 0),
+            e_ehsize: U16::new(endian, mem::size_of::<Header>() as u16),
+            e_phentsize: U16::new(endian, 0),
+            e_phnum: U16::new(endian, 0),
+            e_shentsize: U16::new(endian, mem::size_of::<Section>() as u16),
+            e_shnum: U16::new(endian, 0),
+            e_shstrndx: U16::new(endian, shstrndx as u16),
+        };
+
+        finish(data, &header, endian, &headers).unwrap()
+    }
+
+    /// A relocatable amd64 object with `code` in `.text`, and a
+    /// `.debug_info` holding only its name
+    pub(crate) fn object(code: &[u8]) -> Vec<u8> {
+        object_with(ET_REL, code, &[(".debug_info", b".debug_info")])
+    }
+
+    /// The contents of the section `name` of the object in `data`
+    fn contents(data: &[u8], name: &str) -> Option<Vec<u8>> {
+        let object = object::File::parse(data).unwrap();
+        let section = object.section_by_name(name)?;
+        Some(section.data().unwrap().to_vec())
+    }
+
+    #[test]
+    fn add() {
+        let data = object(&[0x55, 0xc3]);
+        let added = add_section(&data, ".SUNW_test", b"contents").unwrap();
+
+        assert_eq!(contents(&added, ".SUNW_test").unwrap(), b"contents");
+        assert_eq!(contents(&added, ".text").unwrap(), [0x55, 0xc3]);
+        assert_eq!(contents(&added, ".debug_info").unwrap(), b".debug_info");
+        // Nothing that was there has moved
+        assert_eq!(added[mem::size_of::<Header>()..][..2], [0x55, 0xc3]);
+
+        assert!(add_section(&added, ".SUNW_test", b"again").is_err());
+        assert!(add_section(&added, ".text", b"").is_err());
+    }
+
+    #[test]
+    fn replace() {
+        let data = object(&[0x55, 0xc3]);
+        let replaced = replace_section(&data, ".debug_info", b"replaced").unwrap();
+
+        assert_eq!(contents(&replaced, ".debug_info").unwrap(), b"replaced");
+        assert_eq!(contents(&replaced, ".text").unwrap(), [0x55, 0xc3]);
+
+        assert!(replace_section(&data, ".text", b"").is_err());
+        assert!(replace_section(&data, ".SUNW_test", b"").is_err());
+    }
+
+    #[test]
+    fn hide() {
+        let data = object(&[0x55, 0xc3]);
+        let hidden = hide_sections(&data, |x| x.starts_with(b".debug_")).unwrap();
+
+        assert_eq!(contents(&hidden, ".debug_info"), None);
+        assert_eq!(contents(&hidden, ".text").unwrap(), [0x55, 0xc3]);
+        assert_eq!(
+            object::File::parse(&*hidden).unwrap().sections().count(),
+            object::File::parse(&*data).unwrap().sections().count()
+        );
+    }
+}
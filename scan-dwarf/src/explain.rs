@@ -0,0 +1,409 @@
+//! A detailed account of how we reached our conclusions about a function
+//!
+//! For each function chosen, we show its entry in `.debug_info` and all
+//! beneath it, each of its frame bases and what we made of them, the
+//! location of its saved arguments and the address of each slot, and its
+//! code with each instruction marked by whether the arguments may be
+//! recovered there, or are yet to be stored.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use gimli::ReaderOffset;
+use itertools::Itertools;
+use object::Object;
+use typed_arena::Arena;
+
+use crate::code::{function_code, Arch, Decoder};
+use crate::range::{frame_register, register_name, treat_base_offsets};
+use crate::select::Selector;
+use crate::{
+    reloc, saved_args_version, scan_object_with, BaseOffset, CodeRange, Diagnostic, FunctionRecord,
+    Source, Status,
+};
+
+/// Describe each operation of `expr`
+fn expression<R: gimli::Reader>(
+    object: &object::File,
+    expr: gimli::Expression<R>,
+    unit: &gimli::Unit<R>,
+) -> Result<String> {
+    let mut ops = expr.operations(unit.encoding());
+    let mut ret = Vec::new();
+
+    while let Some(op) = ops.next()? {
+        ret.push(match op {
+            gimli::Operation::RegisterOffset {
+                register, offset, ..
+            } => format!(
+                "DW_OP_breg{} ({}) {offset}",
+                register.0,
+                register_name(object.architecture(), register)
+            ),
+            gimli::Operation::FrameOffset { offset } => format!("DW_OP_fbreg {offset}"),
+            gimli::Operation::Register { register } => format!(
+                "DW_OP_reg{} ({})",
+                register.0,
+                register_name(object.architecture(), register)
+            ),
+            x => format!("{x:?}"),
+        });
+    }
+
+    Ok(ret.join("; "))
+}
+
+/// Describe the value of an attribute
+fn value<R: gimli::Reader>(
+    object: &object::File,
+    value: gimli::AttributeValue<R>,
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+) -> String {
+    if let Ok(s) = dwarf.attr_string(unit, value.clone()) {
+        if let Ok(s) = s.to_string_lossy() {
+            return format!("\"{s}\"");
+        }
+    }
+
+    match value {
+        gimli::AttributeValue::Exprloc(e) => {
+            expression(object, e, unit).unwrap_or_else(|x| format!("<{x}>"))
+        }
+        gimli::AttributeValue::Addr(x) => format!("{x:#x}"),
+        gimli::AttributeValue::UnitRef(x) => match x.to_debug_info_offset(&unit.header) {
+            Some(x) => format!("<{:#x}>", x.0.into_u64()),
+            None => format!("{x:?}"),
+        },
+        x => match x.udata_value() {
+            Some(n) => n.to_string(),
+            None => format!("{x:?}"),
+        },
+    }
+}
+
+/// Write the entry at `node`, and all beneath it
+fn tree<R: gimli::Reader>(
+    object: &object::File,
+    node: gimli::EntriesTreeNode<R>,
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    depth: usize,
+    w: &mut dyn Write,
+) -> Result<()> {
+    let indent = "  ".repeat(depth + 1);
+    let entry = node.entry();
+    let offset = entry
+        .offset()
+        .to_debug_info_offset(&unit.header)
+        .map_or(0, |x| x.0.into_u64());
+
+    writeln!(w, "{indent}<{offset:#x}> {}", entry.tag())?;
+    let mut attrs = entry.attrs();
+    while let Some(attr) = attrs.next()? {
+        writeln!(
+            w,
+            "{indent}    {}: {}",
+            attr.name(),
+            value(object, attr.value(), dwarf, unit)
+        )?;
+    }
+
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        tree(object, child, dwarf, unit, depth + 1, w)?;
+    }
+
+    Ok(())
+}
+
+/// Write each of the frame bases of the function at `entry`, and what we
+/// made of them
+fn frame_bases<R: gimli::Reader>(
+    object: &object::File,
+    entry: &gimli::DebuggingInformationEntry<R>,
+    range: &CodeRange,
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    w: &mut dyn Write,
+) -> Result<()> {
+    writeln!(w, "frame base:")?;
+
+    let ll = match entry.attr_value(gimli::DW_AT_frame_base)? {
+        None => return Ok(writeln!(w, "    none")?),
+        Some(gimli::AttributeValue::Exprloc(e)) => {
+            writeln!(w, "    whole function: {}", expression(object, e, unit)?)?;
+            return Ok(());
+        }
+        Some(gimli::AttributeValue::LocationListsRef(x)) => x,
+        Some(x) => return Ok(writeln!(w, "    unexpected: {x:?}")?),
+    };
+
+    let mut entries = Vec::new();
+    let mut locs = dwarf.locations(unit, ll)?;
+    while let Some(loc) = locs.next()? {
+        let r = CodeRange {
+            start: loc.range.begin,
+            end: loc.range.end,
+        };
+        let base = BaseOffset::from_exprloc(loc.data.clone(), &r, unit);
+        entries.push((r, expression(object, loc.data, unit)?, base));
+    }
+
+    let parsed = entries
+        .iter()
+        .filter_map(|(_, _, x)| x.as_ref().ok().cloned())
+        .collect::<Vec<_>>();
+    let mut treatments = treat_base_offsets(frame_register(object)?, object, &parsed).into_iter();
+
+    for (r, expr, base) in entries {
+        let treatment = match base {
+            Ok(_) => treatments.next().unwrap().to_string(),
+            Err(x) => format!("not understood: {x}"),
+        };
+
+        writeln!(
+            w,
+            "    [{:#x}, {:#x}) [+{:#x}, +{:#x}): {expr}",
+            r.start,
+            r.end,
+            r.start.wrapping_sub(range.start),
+            r.end.wrapping_sub(range.start)
+        )?;
+        writeln!(w, "        {treatment}")?;
+    }
+
+    Ok(())
+}
+
+/// Write the location of the saved argument array of the function at `node`,
+/// and the address of each slot
+fn saved_args<R: gimli::Reader>(
+    object: &object::File,
+    node: gimli::EntriesTreeNode<R>,
+    func: &FunctionRecord,
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    w: &mut dyn Write,
+) -> Result<()> {
+    writeln!(w, "saved arguments:")?;
+
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        let entry = child.entry();
+        if entry.tag() != gimli::DW_TAG_variable {
+            continue;
+        }
+
+        let name = entry
+            .attr_value(gimli::DW_AT_name)?
+            .and_then(|x| dwarf.attr_string(unit, x).ok())
+            .and_then(|x| x.to_string_lossy().ok().map(|x| x.into_owned()));
+        let Some(name) = name.filter(|x| saved_args_version(x).is_some()) else {
+            continue;
+        };
+
+        match entry
+            .attr_value(gimli::DW_AT_location)?
+            .and_then(|x| x.exprloc_value())
+        {
+            Some(e) => writeln!(w, "    {name}: {}", expression(object, e, unit)?)?,
+            None => writeln!(w, "    {name}: no location expression")?,
+        }
+    }
+
+    if let Some(x) = func.protocol {
+        writeln!(w, "    saved by {x}")?;
+    }
+
+    if !matches!(func.status, Status::Saved { .. }) {
+        writeln!(w, "    none")?;
+        return Ok(());
+    }
+
+    let reg = register_name(object.architecture(), func.base.register);
+    for (i, (slot, param)) in func.slots().into_iter().enumerate() {
+        writeln!(
+            w,
+            "    {slot:+}({reg}): slot {i}, {}",
+            param.name.as_deref().unwrap_or("<unnamed>")
+        )?;
+    }
+
+    match &func.unstored[..] {
+        [] => writeln!(
+            w,
+            "    every slot stored on every path wherever the frame base is known"
+        )?,
+        x => writeln!(
+            w,
+            "    not every slot stored on every path in {}",
+            x.iter()
+                .map(|x| format!("[+{:#x},+{:#x})", x.start, x.end))
+                .join(", ")
+        )?,
+    }
+
+    Ok(())
+}
+
+/// Write the instructions of `func`, each marked by whether the saved
+/// arguments may be found there
+fn disassembly(object: &object::File, func: &FunctionRecord, w: &mut dyn Write) -> Result<()> {
+    writeln!(w, "code:")?;
+
+    let range = &func.range;
+    let Some(code) = function_code(object, &func.name, range) else {
+        writeln!(w, "    not found")?;
+        return Ok(());
+    };
+
+    let saved = matches!(func.status, Status::Saved { .. });
+    let decoder = Decoder::new(Arch::of(object)?)?;
+    for insn in decoder.decode(code, range.start)? {
+        let pc = insn.address - range.start;
+        let valid = saved && func.base.valid.iter().any(|x| x.start <= pc && pc < x.end);
+        let stored = !func.unstored.iter().any(|x| x.start <= pc && pc < x.end);
+
+        writeln!(
+            w,
+            "    {} {:#x} <+{pc:#x}>: {}",
+            match (valid, stored) {
+                (true, true) => "valid  ",
+                (true, false) => "UNSAVED",
+                (false, _) => "INVALID",
+            },
+            insn.address,
+            insn.text
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Find the unit containing the entry at `die`, and the entry's offset
+/// within it
+fn find_unit<R: gimli::Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    die: u64,
+) -> Result<(gimli::Unit<R>, gimli::UnitOffset<R::Offset>)> {
+    let offset = gimli::DebugInfoOffset(R::Offset::from_u64(die)?);
+    let mut units = dwarf.units();
+
+    while let Some(header) = units.next()? {
+        if let Some(x) = offset.to_unit_offset(&header) {
+            return Ok((dwarf.unit(header)?, x));
+        }
+    }
+
+    Err(anyhow!("no unit contains DIE {die:#x}"))
+}
+
+/// Describe the function at `die`, with the diagnostics concerning it and
+/// what we concluded about it in `func` if we examined it
+fn function<R: gimli::Reader>(
+    object: &object::File,
+    dwarf: &gimli::Dwarf<R>,
+    die: u64,
+    diagnostics: &[&Diagnostic],
+    func: Option<&FunctionRecord>,
+    w: &mut dyn Write,
+) -> Result<()> {
+    let (unit, offset) = find_unit(dwarf, die)?;
+    let mut entries = unit.entries_tree(Some(offset))?;
+
+    writeln!(w, "entry:")?;
+    tree(object, entries.root()?, dwarf, &unit, 0, w)?;
+
+    for diag in diagnostics {
+        writeln!(w, "{}: {diag} [{}]", diag.severity, diag.code())?;
+    }
+
+    let Some(func) = func else {
+        return Ok(());
+    };
+
+    let mut entries = unit.entries_tree(Some(offset))?;
+    let root = entries.root()?;
+    frame_bases(object, root.entry(), &func.range, dwarf, &unit, w)?;
+    saved_args(object, root, func, dwarf, &unit, w)?;
+    disassembly(object, func, w)
+}
+
+/// Explain what we found of each function in `object` chosen by `selector`,
+/// returning how many there were
+pub fn explain(object: &object::File, selector: &Selector, w: &mut dyn Write) -> Result<usize> {
+    let report = scan_object_with(object, selector);
+    let endian = if object.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+
+    let arena_data = Arena::new();
+    let arena_relocations = Arena::new();
+    let load_section = |id: gimli::SectionId| -> Result<_> {
+        reloc::load_file_section(id, object, endian, false, &arena_data, &arena_relocations)
+    };
+    let dwarf = gimli::Dwarf::load(&load_section)?;
+
+    // Functions are only known to diagnostics by name, which a static
+    // function may share with others elsewhere.
+    let concerning = |name: Option<&str>, source: &Source| {
+        report
+            .diagnostics
+            .iter()
+            .filter(|x| {
+                x.function.is_some() && x.function.as_deref() == name && x.source == *source
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for func in &report.functions {
+        writeln!(
+            w,
+            "{}() at DIE {:#x}, [{:#x}, {:#x}): {}",
+            func.name,
+            func.die,
+            func.range.start,
+            func.range.end,
+            func.outcome()
+        )?;
+        if let Some(path) = func.source.path() {
+            writeln!(
+                w,
+                "declared at {}:{}",
+                path.display(),
+                func.source.line.unwrap_or(0)
+            )?;
+        }
+
+        function(
+            object,
+            &dwarf,
+            func.die,
+            &concerning(Some(&func.name), &func.source),
+            Some(func),
+            w,
+        )
+        .with_context(|| format!("explaining {}()", func.name))?;
+        writeln!(w)?;
+    }
+
+    for skip in &report.skipped {
+        let name = skip.name.as_deref().unwrap_or("<unknown>");
+        writeln!(w, "{name}() at DIE {:#x}: {}", skip.die, skip.outcome)?;
+        function(
+            object,
+            &dwarf,
+            skip.die,
+            &concerning(skip.name.as_deref(), &skip.source),
+            None,
+            w,
+        )
+        .with_context(|| format!("explaining {name}()"))?;
+        writeln!(w)?;
+    }
+
+    Ok(report.functions.len() + report.skipped.len())
+}
@@ -0,0 +1,78 @@
+//! One JSON object per line, as described by [`schema`](crate::schema)
+
+use std::io::{self, Write};
+
+use crate::lookup::Lookup;
+use crate::prologue::Function;
+use crate::schema;
+use crate::stats::Stats;
+use crate::ScanReport;
+
+/// Describe each function in `report`, each diagnostic, and then the object
+/// as a whole
+pub fn report(path: &str, report: &ScanReport, w: &mut dyn Write) -> io::Result<()> {
+    for record in schema::records(path, report) {
+        schema::write(w, &record)?;
+    }
+
+    Ok(())
+}
+
+/// Describe `stats` as a single object
+pub fn stats(stats: &Stats, w: &mut dyn Write) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, stats)?;
+    writeln!(w)
+}
+
+/// Describe what we know of the saved arguments at the address given by the
+/// user as `query`, with `lookup` null if it is in no function we examined
+pub fn lookup(
+    path: &str,
+    query: &str,
+    lookup: Option<&Lookup>,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let record = serde_json::json!({
+        "path": path,
+        "query": query,
+        "lookup": lookup,
+    });
+
+    serde_json::to_writer(&mut *w, &record)?;
+    writeln!(w)
+}
+
+/// Describe by which protocols the arguments of each function in `report`
+/// would be recovered, one function per line
+pub fn protocols(path: &str, report: &ScanReport, w: &mut dyn Write) -> io::Result<()> {
+    for func in &report.functions {
+        let record = serde_json::json!({
+            "path": path,
+            "name": func.name,
+            "die": func.die,
+            "protocol": func.protocol,
+            "protocols": func.protocols,
+        });
+
+        serde_json::to_writer(&mut *w, &record)?;
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
+
+/// Describe where the code of each function of `functions` seems to save its
+/// arguments, one function per line
+pub fn prologue(path: &str, functions: &[Function], w: &mut dyn Write) -> io::Result<()> {
+    for func in functions {
+        let record = serde_json::json!({
+            "path": path,
+            "function": func,
+        });
+
+        serde_json::to_writer(&mut *w, &record)?;
+        writeln!(w)?;
+    }
+
+    Ok(())
+}
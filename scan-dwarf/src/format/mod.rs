@@ -0,0 +1,5 @@
+//! Rendering of [`ScanReport`](crate::ScanReport)s for people and programs
+
+pub mod json;
+pub mod sarif;
+pub mod text;
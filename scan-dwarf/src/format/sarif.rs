@@ -0,0 +1,114 @@
+//! A SARIF 2.1.0 log of the diagnostics in many reports, for code review
+//! tools
+//!
+//! Each diagnostic code is a rule, including `low-coverage`, of which there
+//! are results only if a minimum coverage was asked for.  Results are
+//! located physically at the declaration of the function concerned, and
+//! logically at the object and the offset of the entry in `.debug_info`, each
+//! object being described once, in the run's logical locations.
+
+use std::io::{self, Write};
+
+use serde_json::{json, Value};
+
+use crate::{Diagnostic, DiagnosticKind, ScanReport, Severity};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn rules() -> Vec<Value> {
+    DiagnosticKind::CODES
+        .iter()
+        .map(|code| {
+            json!({
+                "id": code,
+                "shortDescription": {
+                    "text": DiagnosticKind::describe(code).unwrap_or(code),
+                },
+            })
+        })
+        .collect()
+}
+
+/// The locations of `diag`, found in the object at `path`, which is at
+/// `module` in the run's logical locations
+fn locations(path: &str, module: usize, diag: &Diagnostic) -> Value {
+    let mut location = json!({});
+
+    if let Some(file) = diag.source.path() {
+        let file = file.display().to_string();
+        let uri = if file.starts_with('/') {
+            format!("file://{file}")
+        } else {
+            file
+        };
+
+        location["physicalLocation"] = json!({ "artifactLocation": { "uri": uri } });
+        if let Some(line) = diag.source.line.filter(|x| *x != 0) {
+            location["physicalLocation"]["region"] = json!({ "startLine": line });
+        }
+    }
+
+    let mut logical = vec![json!({ "name": path, "kind": "module", "index": module })];
+    if let Some(die) = diag.die {
+        let name = diag.function.as_deref().unwrap_or("<unknown>");
+        logical.push(json!({
+            "name": name,
+            "fullyQualifiedName": format!("{path}+{die:#x}"),
+            "kind": "function",
+            "parentIndex": module,
+        }));
+    }
+    location["logicalLocations"] = Value::Array(logical);
+
+    json!([location])
+}
+
+/// Describe the diagnostics in each of `reports`, which are named by the
+/// object they describe
+pub fn log(reports: &[(String, ScanReport)], w: &mut dyn Write) -> io::Result<()> {
+    let mut results = Vec::new();
+    let mut modules = Vec::new();
+
+    for (module, (path, report)) in reports.iter().enumerate() {
+        modules.push(json!({ "name": path, "kind": "module" }));
+
+        for diag in &report.diagnostics {
+            let code = diag.code();
+            let mut result = json!({
+                "ruleId": code,
+                "ruleIndex": DiagnosticKind::CODES.iter().position(|x| *x == code),
+                "level": match diag.severity {
+                    Severity::Warning => "warning",
+                    Severity::Error => "error",
+                },
+                "message": { "text": diag.to_string() },
+                "locations": locations(path, module, diag),
+            });
+
+            if let Some(die) = diag.die {
+                result["properties"] = json!({ "object": path, "die": die });
+            }
+
+            results.push(result);
+        }
+    }
+
+    let log = json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules(),
+                },
+            },
+            "logicalLocations": modules,
+            "results": results,
+        }],
+    });
+
+    serde_json::to_writer_pretty(&mut *w, &log)?;
+    writeln!(w)
+}
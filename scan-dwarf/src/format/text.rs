@@ -0,0 +1,379 @@
+//! One line of text per function, and one per diagnostic, or a table of
+//! statistics
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use itertools::Itertools;
+
+use crate::ctf::Ctf;
+use crate::lookup::Lookup;
+use crate::prologue::{Agreement, Function, Inference};
+use crate::range::register_name;
+use crate::schema;
+use crate::stats::{Counts, Stats};
+use crate::{CodeRange, Outcome, ScanReport, Status};
+
+fn ranges(v: &[CodeRange]) -> String {
+    v.iter()
+        .map(|x| format!("[+{:#x},+{:#x})", x.start, x.end))
+        .join(", ")
+}
+
+/// Describe each function in `report` which saves its arguments, and by
+/// which protocol
+pub fn functions(path: &str, report: &ScanReport, w: &mut dyn Write) -> io::Result<()> {
+    for func in &report.functions {
+        let Some(protocol) = func.protocol else {
+            continue;
+        };
+        let Status::Saved { offset } = func.status else {
+            writeln!(
+                w,
+                "{path}+{:#x} {}() saves {} arguments by {protocol} in its prologue",
+                func.die,
+                func.name,
+                func.nparams(),
+            )?;
+            continue;
+        };
+
+        let goodperc = func.coverage();
+        let unstoredperc = func.unstored_coverage();
+        let badperc = 100.0 - goodperc - unstoredperc;
+        let unstored = match &func.unstored[..] {
+            [] => String::new(),
+            x => format!(" unstored in {} ({unstoredperc:2.2}%)", ranges(x)),
+        };
+
+        writeln!(
+            w,
+            "{path}+{:#x} {}() has {} saved arguments by {protocol} at frame offset {offset} \
+             valid in {} ({goodperc:2.2}%){unstored} invalid in {} ({badperc:2.2}%)",
+            func.die,
+            func.name,
+            func.nparams(),
+            ranges(&func.recoverable()),
+            ranges(&func.invalid()),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Describe what became of each function in `report` which did not save its
+/// arguments
+pub fn outcomes(path: &str, report: &ScanReport, w: &mut dyn Write) -> io::Result<()> {
+    for func in &report.functions {
+        if func.status == Status::Missing {
+            writeln!(
+                w,
+                "{path}+{:#x} {}(): {}",
+                func.die,
+                func.name,
+                func.outcome()
+            )?;
+        }
+    }
+
+    for skip in &report.skipped {
+        let name = skip.name.as_deref().unwrap_or("<unknown>");
+        writeln!(w, "{path}+{:#x} {name}(): {}", skip.die, skip.outcome)?;
+    }
+
+    Ok(())
+}
+
+/// Describe each diagnostic in `report`, with its severity and code
+pub fn diagnostics(path: &str, report: &ScanReport, w: &mut dyn Write) -> io::Result<()> {
+    for diag in &report.diagnostics {
+        let severity = diag.severity.to_string().to_uppercase();
+        let code = diag.code();
+
+        match diag.die {
+            Some(die) => writeln!(w, "{path}+{die:#x}: {severity}: {diag} [{code}]")?,
+            None => writeln!(w, "{path}: {severity}: {diag} [{code}]")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Write each of `rollup` as a row of a table, those most in need of
+/// attention first
+fn rollup(title: &str, rollup: &BTreeMap<String, Counts>, w: &mut dyn Write) -> io::Result<()> {
+    writeln!(w)?;
+    writeln!(
+        w,
+        "{:>8} {:>8} {:>8} {:>8} {:>9}  {title}",
+        "saved", "missing", "skipped", "saved%", "coverage%"
+    )?;
+
+    let rows = rollup.iter().sorted_by(|(_, a), (_, b)| {
+        a.saved_percent()
+            .total_cmp(&b.saved_percent())
+            .then(b.missing.cmp(&a.missing))
+    });
+
+    for (name, x) in rows {
+        writeln!(
+            w,
+            "{:>8} {:>8} {:>8} {:>7.2}% {:>8.2}%  {name}",
+            x.saved,
+            x.missing,
+            x.skipped,
+            x.saved_percent(),
+            x.mean_coverage()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Describe `stats`, as totals, histograms, and then per object and per
+/// source directory
+pub fn stats(stats: &Stats, w: &mut dyn Write) -> io::Result<()> {
+    let totals = &stats.totals;
+
+    writeln!(w, "saved:       {}", totals.saved)?;
+    writeln!(w, "missing:     {}", totals.missing)?;
+    writeln!(w, "skipped:     {}", totals.skipped)?;
+    writeln!(w, "outcomes:")?;
+    for outcome in Outcome::ALL {
+        let n = stats.outcomes.get(&outcome).copied().unwrap_or(0);
+        writeln!(w, "    {:<24}{n}", format!("{outcome}:"))?;
+    }
+    writeln!(w, "diagnostics: {}", stats.diagnostics)?;
+    writeln!(
+        w,
+        "saved in {:.2}% of functions, valid in {:.2}% of each on average",
+        totals.saved_percent(),
+        totals.mean_coverage()
+    )?;
+
+    writeln!(w)?;
+    writeln!(w, "coverage:")?;
+    for (i, n) in stats.coverage.iter().enumerate() {
+        let end = if i == 9 { "]" } else { ")" };
+        writeln!(w, "    [{:>3}%, {:>3}%{end} {n}", i * 10, (i + 1) * 10)?;
+    }
+
+    writeln!(w)?;
+    writeln!(w, "frame offset magnitude:")?;
+    for (bound, n) in &stats.offsets {
+        writeln!(w, "    <= {bound:<6} {n}")?;
+    }
+
+    rollup("object", &stats.objects, w)?;
+    rollup("directory", &stats.directories, w)
+}
+
+/// Describe each of `diagnostics`, read back from their stable form, as
+/// [`diagnostics`] would
+pub fn findings(diagnostics: &[schema::Diagnostic], w: &mut dyn Write) -> io::Result<()> {
+    for diag in diagnostics {
+        let path = &diag.path;
+        let severity = diag.severity.to_string().to_uppercase();
+        let message = &diag.message;
+        let code = &diag.code;
+
+        match diag.die {
+            Some(die) => writeln!(w, "{path}+{die:#x}: {severity}: {message} [{code}]")?,
+            None => writeln!(w, "{path}: {severity}: {message} [{code}]")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Describe what we know of the saved arguments at `pc`, as given by the user
+/// as `query`
+pub fn lookup(
+    path: &str,
+    query: &str,
+    lookup: Option<&Lookup>,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let Some(x) = lookup else {
+        return writeln!(w, "{path}: {query}: not in any function examined");
+    };
+
+    let prefix = format!("{path}: {query}: {}()+{:#x}", x.function, x.offset);
+    let Some(reg) = &x.register else {
+        return writeln!(w, "{prefix}: arguments not saved");
+    };
+
+    let slots = x
+        .slots
+        .iter()
+        .map(|s| {
+            format!(
+                "{} at {:+}({reg})",
+                s.name.as_deref().unwrap_or("<unnamed>"),
+                s.offset
+            )
+        })
+        .join(", ");
+
+    writeln!(
+        w,
+        "{prefix}: saved arguments {}: {slots}",
+        if x.valid { "valid" } else { "INVALID" }
+    )
+}
+
+/// Describe the CTF of an object for `arch`, and the type of each function
+/// with a symbol in `symbols`, as given by
+/// [`function_symbols`](crate::ctf::function_symbols), with where its
+/// arguments are saved if the CTF says
+pub fn ctf(
+    path: &str,
+    ctf: &Ctf,
+    parent: Option<&Ctf>,
+    arch: object::Architecture,
+    symbols: &[(usize, &str)],
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(
+        w,
+        "{path}: CTF version {}, {} labels, {} objects, {} functions, {} types, \
+         {} bytes of strings, {} functions with saved arguments",
+        ctf.version,
+        ctf.labels.len(),
+        ctf.objects.len(),
+        ctf.functions.len(),
+        ctf.types.len(),
+        ctf.strings.len(),
+        ctf.saved_args.len()
+    )?;
+
+    if ctf.parent_name != 0 {
+        writeln!(
+            w,
+            "{path}: parent {}, at label {}",
+            ctf.string(ctf.parent_name).unwrap_or("<unknown>"),
+            ctf.string(ctf.parent_label).unwrap_or("<unknown>")
+        )?;
+    }
+
+    for (&(index, name), info) in symbols.iter().zip(&ctf.functions) {
+        let saved = ctf.saved_args.iter().find(|x| x.symbol as usize == index);
+        let signature = match info {
+            Some(info) => {
+                let args = info
+                    .args
+                    .iter()
+                    .map(|&x| match x {
+                        0 => String::from("..."),
+                        x => ctf.type_name(parent, x),
+                    })
+                    .join(", ");
+                format!("{} {name}({args})", ctf.type_name(parent, info.ret))
+            }
+            None if saved.is_some() => format!("{name}()"),
+            None => continue,
+        };
+
+        match saved {
+            Some(x) => writeln!(
+                w,
+                "{path}: {signature} has {} saved arguments at {:+}({})",
+                x.nargs,
+                x.offset,
+                register_name(arch, gimli::Register(x.register))
+            )?,
+            None => writeln!(w, "{path}: {signature}")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Describe where the code of each function of `functions` seems to save its
+/// arguments, and where the DWARF says they are if it differs, and then how
+/// often the two agree
+pub fn prologue(path: &str, functions: &[Function], w: &mut dyn Write) -> io::Result<()> {
+    let describe = |x: Option<_>| match x {
+        Some(Inference { offset, slots }) => {
+            format!("{slots} arguments at frame offset {offset}")
+        }
+        None => String::from("no arguments"),
+    };
+
+    for func in functions {
+        let dwarf = match func.agreement {
+            None | Some(Agreement::Neither) if func.inferred.is_none() => continue,
+            None => String::new(),
+            Some(Agreement::Agrees) => String::from(", as the DWARF says"),
+            Some(Agreement::Disagrees(x) | Agreement::Missed(x)) => {
+                format!(", but the DWARF says {}", describe(Some(x)))
+            }
+            Some(Agreement::Spurious | Agreement::Neither) => {
+                String::from(", but the DWARF says it saves none")
+            }
+        };
+
+        writeln!(
+            w,
+            "{path}+{:#x} {}() saves {}{dwarf}",
+            func.range.start,
+            func.name,
+            describe(func.inferred)
+        )?;
+    }
+
+    let (mut agree, mut disagree, mut missed, mut spurious) = (0, 0, 0, 0);
+    for x in functions.iter().filter_map(|x| x.agreement) {
+        match x {
+            Agreement::Agrees => agree += 1,
+            Agreement::Disagrees(_) => disagree += 1,
+            Agreement::Missed(_) => missed += 1,
+            Agreement::Spurious => spurious += 1,
+            Agreement::Neither => (),
+        }
+    }
+
+    let total = agree + disagree + missed + spurious;
+    if total > 0 {
+        writeln!(
+            w,
+            "{path}: the code agrees with the DWARF for {agree} of {total} functions saving \
+             arguments by either: {disagree} disagree, {missed} missed, {spurious} spurious"
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Say by which protocols the arguments of each function in `report` would
+/// be recovered, and how many each would recover
+pub fn protocols(path: &str, report: &ScanReport, w: &mut dyn Write) -> io::Result<()> {
+    let mut counts = BTreeMap::<_, usize>::new();
+    for func in &report.functions {
+        let by = match &func.protocols[..] {
+            [] => String::from("neither"),
+            x => x.iter().join(" and "),
+        };
+        let protocol = match func.protocol {
+            Some(x) => format!("saved by {x}"),
+            None => String::from("not saved"),
+        };
+        writeln!(
+            w,
+            "{path}+{:#x} {}(): arguments {protocol}, recovered by {by}",
+            func.die, func.name
+        )?;
+        *counts.entry(by).or_default() += 1;
+    }
+
+    if !report.functions.is_empty() {
+        writeln!(
+            w,
+            "{path}: of {} functions with integer arguments, recovered by {}",
+            report.functions.len(),
+            counts.iter().map(|(by, n)| format!("{by} {n}")).join(", ")
+        )?;
+    }
+
+    Ok(())
+}
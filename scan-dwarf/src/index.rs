@@ -0,0 +1,623 @@
+//! A compact index of where the saved arguments of each function may be
+//! found, for debuggers to consult without reading any DWARF
+//!
+//! The index is written from the results of a scan, and may be read in
+//! place, as from a mapped file.  Every field is little-endian, and every
+//! table is aligned to the size of its largest field:
+//!
+//! ```text
+//! header      magic "SAVARGIX", then u32 version, machine, nfunctions,
+//!             nranges, nslots, strtab_size
+//! functions   nfunctions of: u64 start, end; i64 offset; u32 name;
+//!             u16 register, flags; u32 first_range, nranges, first_slot,
+//!             nslots; u64 die (sorted by start)
+//! ranges      nranges of: u32 start, end (relative to the function)
+//! slots       nslots of: u32 name (or NO_NAME)
+//! strtab      strtab_size bytes of NUL-terminated names
+//! ```
+//!
+//! The machine is that of the ELF header, and so says how registers are
+//! numbered.  Each function's ranges are those in which its saved arguments
+//! are valid, every slot having been stored, and there are none for a
+//! function which may write to its slots once they are.  Its slots are in
+//! order of address, at intervals of 8 bytes from `offset`.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use object::Object;
+
+use crate::lookup::{resolve_with, Lookup, Slot};
+use crate::range::register_name;
+use crate::scan::SLOT_SIZE;
+use crate::{scan_object, FunctionRecord, Status};
+
+/// The first bytes of every index
+pub const MAGIC: [u8; 8] = *b"SAVARGIX";
+/// The version of the format written, any other is refused
+pub const VERSION: u32 = 1;
+
+/// The name of an unnamed parameter
+const NO_NAME: u32 = u32::MAX;
+/// The function saved its arguments
+const FLAG_SAVED: u16 = 1;
+
+/// The sizes of the header, and of an entry in each table
+const HEADER_SIZE: usize = 32;
+const FUNCTION_ENTRY: usize = 56;
+const RANGE_ENTRY: usize = 8;
+const SLOT_ENTRY: usize = 4;
+
+/// The ELF machine number of `arch`
+fn machine(arch: object::Architecture) -> Result<u32> {
+    match arch {
+        object::Architecture::X86_64 => Ok(u32::from(object::elf::EM_X86_64)),
+        object::Architecture::Aarch64 => Ok(u32::from(object::elf::EM_AARCH64)),
+        x => Err(anyhow!("unknown architecture {x:?}")),
+    }
+}
+
+/// The architecture of ELF machine number `machine`
+fn architecture(machine: u32) -> object::Architecture {
+    match u16::try_from(machine) {
+        Ok(object::elf::EM_X86_64) => object::Architecture::X86_64,
+        Ok(object::elf::EM_AARCH64) => object::Architecture::Aarch64,
+        _ => object::Architecture::Unknown,
+    }
+}
+
+#[derive(Default)]
+struct StringTable {
+    data: Vec<u8>,
+}
+
+impl StringTable {
+    fn add(&mut self, s: Option<&str>) -> Result<u32> {
+        let Some(s) = s else {
+            return Ok(NO_NAME);
+        };
+
+        let offset = u32::try_from(self.data.len()).context("string table too large")?;
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        Ok(offset)
+    }
+}
+
+/// Write an index of `functions`, found in an object for `arch`, to `w`
+pub fn write(
+    arch: object::Architecture,
+    functions: &[FunctionRecord],
+    w: &mut dyn Write,
+) -> Result<()> {
+    let mut sorted = functions.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|x| x.range.start);
+
+    let mut strtab = StringTable::default();
+    let mut funcs = Vec::with_capacity(sorted.len() * FUNCTION_ENTRY);
+    let mut ranges = Vec::new();
+    let mut slots = Vec::new();
+    let (mut nranges, mut nslots) = (0u32, 0u32);
+
+    for func in sorted {
+        let (offset, flags) = match func.status {
+            Status::Saved { offset } => (offset, FLAG_SAVED),
+            Status::Missing => (0, 0),
+        };
+        let valid = func.trusted();
+        let name = strtab.add(Some(&func.name))?;
+        let params = func.slots();
+        let too_large = || format!("{}() is too large to index", func.name);
+
+        funcs.extend(func.range.start.to_le_bytes());
+        funcs.extend(func.range.end.to_le_bytes());
+        funcs.extend(offset.to_le_bytes());
+        funcs.extend(name.to_le_bytes());
+        funcs.extend(func.base.register.0.to_le_bytes());
+        funcs.extend(flags.to_le_bytes());
+        funcs.extend(nranges.to_le_bytes());
+        funcs.extend((valid.len() as u32).to_le_bytes());
+        funcs.extend(nslots.to_le_bytes());
+        funcs.extend((params.len() as u32).to_le_bytes());
+        funcs.extend(func.die.to_le_bytes());
+
+        for r in &valid {
+            ranges.extend(
+                u32::try_from(r.start)
+                    .with_context(too_large)?
+                    .to_le_bytes(),
+            );
+            ranges.extend(u32::try_from(r.end).with_context(too_large)?.to_le_bytes());
+        }
+        for (_, param) in &params {
+            slots.extend(strtab.add(param.name.as_deref())?.to_le_bytes());
+        }
+
+        nranges += valid.len() as u32;
+        nslots += params.len() as u32;
+    }
+
+    let nfunctions = (funcs.len() / FUNCTION_ENTRY) as u32;
+    w.write_all(&MAGIC)?;
+    for x in [
+        VERSION,
+        machine(arch)?,
+        nfunctions,
+        nranges,
+        nslots,
+        strtab.data.len() as u32,
+    ] {
+        w.write_all(&x.to_le_bytes())?;
+    }
+    w.write_all(&funcs)?;
+    w.write_all(&ranges)?;
+    w.write_all(&slots)?;
+    w.write_all(&strtab.data)?;
+
+    Ok(())
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// A function's entry in the index, undecoded
+struct Raw {
+    start: u64,
+    end: u64,
+    offset: i64,
+    name: u32,
+    register: u16,
+    flags: u16,
+    first_range: usize,
+    nranges: usize,
+    first_slot: usize,
+    nslots: usize,
+    die: u64,
+}
+
+impl Raw {
+    fn read(f: &[u8]) -> Self {
+        Raw {
+            start: u64_at(f, 0),
+            end: u64_at(f, 8),
+            offset: u64_at(f, 16) as i64,
+            name: u32_at(f, 24),
+            register: u16_at(f, 28),
+            flags: u16_at(f, 30),
+            first_range: u32_at(f, 32) as usize,
+            nranges: u32_at(f, 36) as usize,
+            first_slot: u32_at(f, 40) as usize,
+            nslots: u32_at(f, 44) as usize,
+            die: u64_at(f, 48),
+        }
+    }
+}
+
+/// A function, as described by an index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function<'data> {
+    pub name: &'data str,
+    /// Offset of the function's entry in `.debug_info`
+    pub die: u64,
+    pub start: u64,
+    pub end: u64,
+    /// The offset of the saved argument array from `register`, if it was
+    /// saved
+    pub offset: Option<i64>,
+    pub register: gimli::Register,
+    /// Where the saved arguments are valid, relative to `start`
+    pub valid: Vec<(u32, u32)>,
+    /// The name of the parameter in each slot, in order of address
+    pub slots: Vec<Option<&'data str>>,
+}
+
+/// An index, read in place
+#[derive(Debug, Clone, Copy)]
+pub struct IndexFile<'data> {
+    arch: object::Architecture,
+    nfunctions: usize,
+    functions: &'data [u8],
+    ranges: &'data [u8],
+    slots: &'data [u8],
+    strtab: &'data [u8],
+}
+
+impl<'data> IndexFile<'data> {
+    /// Read the index in `data`, checking that it is whole and consistent
+    pub fn parse(data: &'data [u8]) -> Result<Self> {
+        if data.len() < HEADER_SIZE || data[..8] != MAGIC {
+            return Err(anyhow!("not a saved argument index"));
+        }
+
+        let version = u32_at(data, 8);
+        if version != VERSION {
+            return Err(anyhow!("unsupported index version {version}"));
+        }
+
+        let count = |offset| u32_at(data, offset) as usize;
+        let (nfunctions, nranges, nslots, strsize) = (count(16), count(20), count(24), count(28));
+
+        let mut rest = &data[HEADER_SIZE..];
+        let mut table = |size: usize| -> Result<&'data [u8]> {
+            if rest.len() < size {
+                return Err(anyhow!("index is truncated"));
+            }
+            let (x, r) = rest.split_at(size);
+            rest = r;
+            Ok(x)
+        };
+
+        let index = IndexFile {
+            arch: architecture(u32_at(data, 12)),
+            nfunctions,
+            functions: table(nfunctions * FUNCTION_ENTRY)?,
+            ranges: table(nranges * RANGE_ENTRY)?,
+            slots: table(nslots * SLOT_ENTRY)?,
+            strtab: table(strsize)?,
+        };
+
+        // Check everything a lookup will rely upon, so that lookups need not
+        let mut last = 0;
+        for i in 0..nfunctions {
+            let f = index.raw(i);
+
+            if f.start < last || f.end < f.start {
+                return Err(anyhow!("function {i} is out of order"));
+            }
+            if f.first_range + f.nranges > nranges || f.first_slot + f.nslots > nslots {
+                return Err(anyhow!("function {i} refers beyond the index"));
+            }
+            index.string(f.name)?;
+            last = f.start;
+        }
+
+        for i in 0..nslots {
+            let name = u32_at(index.slots, i * SLOT_ENTRY);
+            if name != NO_NAME {
+                index.string(name)?;
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// The string at `offset` in the string table
+    fn string(&self, offset: u32) -> Result<&'data str> {
+        let s = self
+            .strtab
+            .get(offset as usize..)
+            .and_then(|x| x.split(|&c| c == 0).next().filter(|_| x.contains(&0)))
+            .ok_or_else(|| anyhow!("string {offset} is beyond the string table"))?;
+
+        std::str::from_utf8(s).with_context(|| format!("string {offset} is not UTF-8"))
+    }
+
+    /// The number of functions indexed
+    pub fn len(&self) -> usize {
+        self.nfunctions
+    }
+
+    /// True if no functions were indexed
+    pub fn is_empty(&self) -> bool {
+        self.nfunctions == 0
+    }
+
+    fn raw(&self, i: usize) -> Raw {
+        Raw::read(&self.functions[i * FUNCTION_ENTRY..])
+    }
+
+    /// The `i`th function, by address
+    pub fn function(&self, i: usize) -> Function<'data> {
+        let f = self.raw(i);
+        let string = |x| self.string(x).expect("checked by parse");
+
+        Function {
+            name: string(f.name),
+            die: f.die,
+            start: f.start,
+            end: f.end,
+            offset: (f.flags & FLAG_SAVED != 0).then_some(f.offset),
+            register: gimli::Register(f.register),
+            valid: (f.first_range..f.first_range + f.nranges)
+                .map(|x| {
+                    let r = &self.ranges[x * RANGE_ENTRY..];
+                    (u32_at(r, 0), u32_at(r, 4))
+                })
+                .collect(),
+            slots: (f.first_slot..f.first_slot + f.nslots)
+                .map(|x| match u32_at(self.slots, x * SLOT_ENTRY) {
+                    NO_NAME => None,
+                    x => Some(string(x)),
+                })
+                .collect(),
+        }
+    }
+
+    /// The address of the function named `name`, if it was indexed
+    pub fn address(&self, name: &str) -> Option<u64> {
+        (0..self.nfunctions)
+            .map(|i| self.raw(i))
+            .find(|f| self.string(f.name).ok() == Some(name))
+            .map(|f| f.start)
+    }
+
+    /// Parse `s` as an address, either a hex number, or `FUNCTION` or
+    /// `FUNCTION+OFFSET`
+    pub fn resolve(&self, s: &str) -> Result<u64> {
+        resolve_with(s, |name| self.address(name))
+    }
+
+    /// What we know of the saved arguments at `pc`, as
+    /// [`lookup::Index::lookup`](crate::lookup::Index::lookup) would say
+    pub fn lookup(&self, pc: u64) -> Option<Lookup> {
+        let (mut lo, mut hi) = (0, self.nfunctions);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if u64_at(self.functions, mid * FUNCTION_ENTRY) <= pc {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+
+        let func = self.function(lo.checked_sub(1)?);
+        if pc >= func.end {
+            return None;
+        }
+
+        let rel = pc - func.start;
+        Some(Lookup {
+            pc,
+            function: func.name.to_string(),
+            die: func.die,
+            offset: rel,
+            saved: func.offset.is_some(),
+            valid: func.offset.is_some()
+                && func
+                    .valid
+                    .iter()
+                    .any(|&(s, e)| u64::from(s) <= rel && rel < u64::from(e)),
+            register: func.offset.map(|_| register_name(self.arch, func.register)),
+            slots: func
+                .slots
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Slot {
+                    offset: func.offset.unwrap_or(0) + i as i64 * SLOT_SIZE,
+                    name: name.map(str::to_string),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Check `index` against the DWARF of `object` from which it was built,
+/// describing each way in which they differ
+pub fn verify(index: &IndexFile, object: &object::File) -> Result<Vec<String>> {
+    let report = scan_object(object);
+    let mut problems = Vec::new();
+
+    if !report.is_complete() {
+        return Err(anyhow!("could not examine the whole object"));
+    }
+    if index.arch != object.architecture() {
+        problems.push(format!(
+            "index is for {:?}, object is {:?}",
+            index.arch,
+            object.architecture()
+        ));
+    }
+
+    let mut functions = report.functions.iter().collect::<Vec<_>>();
+    functions.sort_by_key(|x| x.range.start);
+    if functions.len() != index.len() {
+        problems.push(format!(
+            "index has {} functions, object has {}",
+            index.len(),
+            functions.len()
+        ));
+    }
+
+    for (i, func) in functions.iter().enumerate().take(index.len()) {
+        let indexed = index.function(i);
+        let expected = Function {
+            name: &func.name,
+            die: func.die,
+            start: func.range.start,
+            end: func.range.end,
+            offset: match func.status {
+                Status::Saved { offset } => Some(offset),
+                Status::Missing => None,
+            },
+            register: func.base.register,
+            valid: func
+                .trusted()
+                .iter()
+                .map(|r| (r.start as u32, r.end as u32))
+                .collect(),
+            slots: func
+                .slots()
+                .into_iter()
+                .map(|(_, x)| x.name.as_deref())
+                .collect(),
+        };
+
+        if indexed != expected {
+            problems.push(format!(
+                "{}() at {:#x} differs: index has {indexed:?}, object has {expected:?}",
+                func.name, func.range.start
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{BaseOffset, CodeRange, Parameter, Source};
+
+    /// A function at `start`, saving three arguments if `status` says so,
+    /// whose frame base is valid from +0x4 and every slot stored from +0x8
+    pub(crate) fn function(name: &str, start: u64, end: u64, status: Status) -> FunctionRecord {
+        let param = |name: &str, die| Parameter {
+            name: Some(name.to_string()),
+            die,
+            integer: true,
+        };
+
+        FunctionRecord {
+            name: name.to_string(),
+            die: start * 2,
+            source: Source::default(),
+            range: CodeRange { start, end },
+            base: BaseOffset {
+                valid: vec![CodeRange {
+                    start: 4,
+                    end: 0x20,
+                }],
+                offset: 16,
+                register: gimli::X86_64::RBP,
+            },
+            parameters: vec![
+                param("a", 1),
+                Parameter {
+                    name: None,
+                    die: 2,
+                    integer: true,
+                },
+                param("d", 3),
+            ],
+            status,
+            unstored: vec![CodeRange { start: 4, end: 8 }],
+            clobbered: None,
+            protocol: None,
+            protocols: Vec::new(),
+        }
+    }
+
+    fn written(functions: &[FunctionRecord]) -> Vec<u8> {
+        let mut data = Vec::new();
+        write(object::Architecture::X86_64, functions, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trip() {
+        let functions = [
+            function("two", 0x1100, 0x1130, Status::Missing),
+            function("one", 0x1000, 0x1040, Status::Saved { offset: -40 }),
+        ];
+        let data = written(&functions);
+        let index = IndexFile::parse(&data).unwrap();
+
+        assert_eq!(index.len(), 2);
+        assert_eq!(
+            index.function(0),
+            Function {
+                name: "one",
+                die: 0x2000,
+                start: 0x1000,
+                end: 0x1040,
+                offset: Some(-40),
+                register: gimli::X86_64::RBP,
+                valid: vec![(8, 0x20)],
+                slots: vec![Some("d"), None, Some("a")],
+            }
+        );
+        assert_eq!(
+            index.function(1),
+            Function {
+                name: "two",
+                die: 0x2200,
+                start: 0x1100,
+                end: 0x1130,
+                offset: None,
+                register: gimli::X86_64::RBP,
+                valid: Vec::new(),
+                slots: Vec::new(),
+            }
+        );
+        assert_eq!(index.address("two"), Some(0x1100));
+        assert_eq!(index.resolve("one+0x10").unwrap(), 0x1010);
+    }
+
+    #[test]
+    fn clobbered_is_never_valid() {
+        let mut func = function("one", 0x1000, 0x1040, Status::Saved { offset: -40 });
+        func.clobbered = Some(0x10);
+        let data = written(&[func]);
+        let index = IndexFile::parse(&data).unwrap();
+
+        assert_eq!(index.function(0).valid, Vec::new());
+        assert_eq!(index.function(0).offset, Some(-40));
+    }
+
+    #[test]
+    fn lookup() {
+        let data = written(&[function(
+            "one",
+            0x1000,
+            0x1040,
+            Status::Saved { offset: -40 },
+        )]);
+        let index = IndexFile::parse(&data).unwrap();
+
+        assert_eq!(index.lookup(0xfff), None);
+        assert_eq!(index.lookup(0x1040), None);
+        assert!(!index.lookup(0x1004).unwrap().valid);
+        assert!(!index.lookup(0x1020).unwrap().valid);
+
+        let found = index.lookup(0x1008).unwrap();
+        assert_eq!(found.function, "one");
+        assert_eq!(found.offset, 8);
+        assert!(found.saved && found.valid);
+        assert_eq!(found.register.as_deref(), Some("%rbp"));
+        assert_eq!(
+            found.slots,
+            [(-40, Some("d")), (-32, None), (-24, Some("a"))]
+                .map(|(offset, name)| Slot {
+                    offset,
+                    name: name.map(str::to_string),
+                })
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn refuses_damage() {
+        let data = written(&[function(
+            "one",
+            0x1000,
+            0x1040,
+            Status::Saved { offset: -40 },
+        )]);
+
+        assert!(IndexFile::parse(&data[..data.len() - 1]).is_err());
+        assert!(IndexFile::parse(&data[1..]).is_err());
+
+        let mut version = data.clone();
+        version[8] = 2;
+        assert!(IndexFile::parse(&version).is_err());
+
+        // The first range of the only function, beyond the one range
+        let mut ranges = data.clone();
+        ranges[HEADER_SIZE + 32] = 1;
+        assert!(IndexFile::parse(&ranges).is_err());
+
+        // Its name, beyond the string table
+        let mut name = data;
+        name[HEADER_SIZE + 24] = 0xff;
+        assert!(IndexFile::parse(&name).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+//! Find the argument blocks saved by the save-args GCC plugin, and judge how
+//! useful they would be to a debugger.
+//!
+//! [`scan_object`] examines the DWARF of an object, and describes each
+//! function in a [`ScanReport`], or [`scan_object_with`] each function chosen
+//! by a [`select::Selector`].  The [`format`] modules render those
+//! reports, [`schema`] describes (and reads back) their stable JSON form, and
+//! [`cache`] remembers them between runs.  [`diff`] compares two sets of
+//! results, and [`stats`] totals them across many objects.  A [`policy`]
+//! decides which diagnostics are errors, and a [`baseline`] which are
+//! already known.  [`check`] holds results to the rules of a policy file, and
+//! [`explain`] shows in detail how we came to our conclusions.  A
+//! [`lookup::Index`] answers whether the saved arguments may be found at an
+//! address, as does an [`index`] file without needing the DWARF, and
+//! [`saveargs`] writes such an index into the object itself.  [`ctf`] reads
+//! and writes the CTF of an object, extends it with the same, and checks
+//! what it says of each function's arguments against the DWARF.
+//! [`locations`] rewrites the DWARF so that debuggers find parameters in
+//! their slots, and [`prologue`] infers where arguments are saved from the
+//! code alone.  [`protocol`] says whether `-msave-args`, before this plugin,
+//! would have recovered them instead.
+
+pub mod baseline;
+pub mod cache;
+pub mod check;
+mod code;
+pub mod ctf;
+pub mod diff;
+mod dwarf;
+mod elf;
+pub mod explain;
+pub mod format;
+pub mod index;
+pub mod locations;
+pub mod lookup;
+pub mod policy;
+pub mod prologue;
+pub mod protocol;
+mod range;
+mod reloc;
+pub mod saveargs;
+mod saving;
+mod scan;
+pub mod schema;
+pub mod select;
+pub mod stats;
+
+pub use range::{BaseOffset, CodeRange};
+pub use scan::{
+    saved_args_version, scan_object, scan_object_with, Diagnostic, DiagnosticKind, FunctionRecord,
+    Outcome, Parameter, ScanReport, Severity, Skipped, Source, Status, SAVED_ARGS_NAME,
+};
@@ -0,0 +1,437 @@
+//! Location lists for parameters, pointing at where their arguments were
+//! saved
+//!
+//! Where the compiler could not say where a parameter is, a debugger says it
+//! was optimized out, though the saved arguments have it.  [`rewrite`] gives
+//! each integer parameter of each function which saved its arguments a
+//! location list: whatever locations the compiler gave it are kept, and
+//! wherever they leave a gap while the saved arguments are valid, the
+//! parameter is in its slot, `DW_OP_breg<N> <offset>` from the frame
+//! register.
+//!
+//! The debug information is converted whole by `gimli::write`, and written
+//! back in place of the original.  Anything else which refers into
+//! `.debug_info` by offset would then be wrong, so `.debug_aranges` and the
+//! indices of names are removed, and debuggers do without them.  Relocatable
+//! objects cannot be rewritten, as their debug information would need new
+//! relocations.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use gimli::write::{
+    self, Address, EndianVec, Expression, Location, LocationList, Sections, UnitEntryId, UnitId,
+};
+use gimli::{DebugInfoOffset, Endianity, Section, SectionId, UnitSectionOffset};
+use object::{Object, ObjectKind, ObjectSection};
+use typed_arena::Arena;
+
+use crate::{elf, FunctionRecord, ScanReport};
+
+/// The sections written from the converted debug information
+const WRITTEN: [SectionId; 9] = [
+    SectionId::DebugAbbrev,
+    SectionId::DebugInfo,
+    SectionId::DebugLine,
+    SectionId::DebugLineStr,
+    SectionId::DebugLoc,
+    SectionId::DebugLocLists,
+    SectionId::DebugRanges,
+    SectionId::DebugRngLists,
+    SectionId::DebugStr,
+];
+
+/// Sections which index `.debug_info` by offset
+const INDICES: [&str; 7] = [
+    ".debug_aranges",
+    ".debug_pubnames",
+    ".debug_pubtypes",
+    ".debug_gnu_pubnames",
+    ".debug_gnu_pubtypes",
+    ".debug_names",
+    ".gdb_index",
+];
+
+type EntryIds = HashMap<UnitSectionOffset, (UnitId, UnitEntryId)>;
+
+fn convert_address(address: u64) -> Option<Address> {
+    Some(Address::Constant(address))
+}
+
+/// Record the converted entry of `node` and each of its descendants, which
+/// are converted in the same order as they are read
+fn map_entries<R: gimli::Reader<Offset = usize>>(
+    unit: &gimli::Unit<R>,
+    node: gimli::EntriesTreeNode<R>,
+    converted: &write::Unit,
+    ids: (UnitId, UnitEntryId),
+    map: &mut EntryIds,
+) -> Result<()> {
+    map.insert(node.entry().offset().to_unit_section_offset(unit), ids);
+
+    let mut children = node.children();
+    let mut converted_children = converted.get(ids.1).children();
+    while let Some(child) = children.next()? {
+        let id = converted_children
+            .next()
+            .ok_or_else(|| anyhow!("converted entries do not match those read"))?;
+        map_entries(unit, child, converted, (ids.0, *id), map)?;
+    }
+
+    Ok(())
+}
+
+/// `.debug_line`, with the line number program of each version 2 unit made
+/// version 2 as well
+///
+/// GCC gives version 2 units version 3 line number programs, which
+/// `gimli::write` will not write, but the two differ only in which opcodes
+/// they may use, which are described in the header of either.
+fn downgrade_line_programs<R: gimli::Reader<Offset = usize>>(
+    dwarf: &gimli::Dwarf<R>,
+) -> Result<Vec<u8>> {
+    let reader = dwarf.debug_line.reader();
+    let mut data = reader.to_slice()?.into_owned();
+    let version = if reader.endian().is_big_endian() {
+        2u16.to_be_bytes()
+    } else {
+        2u16.to_le_bytes()
+    };
+
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        if header.version() != 2 {
+            continue;
+        }
+        let unit = dwarf.unit(header)?;
+        let Some(program) = &unit.line_program else {
+            continue;
+        };
+
+        // The version follows the length, which may be 64-bit
+        let offset = program.header().offset().0;
+        let at = match data.get(offset..offset + 4) {
+            Some([0xff, 0xff, 0xff, 0xff]) => offset + 12,
+            _ => offset + 4,
+        };
+        if program.header().version() == 3 {
+            data[at..at + 2].copy_from_slice(&version);
+        }
+    }
+
+    Ok(data)
+}
+
+/// The location list of the parameter at `entry` of `func`, saved at
+/// `offset` from the frame register, if it needs one
+///
+/// A parameter with a single location, or a constant value, is known
+/// everywhere already, as is one whose locations leave no gap in which the
+/// saved arguments are valid.
+fn location_list<R: gimli::Reader<Offset = usize>>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    entry: &gimli::DebuggingInformationEntry<R>,
+    ids: &EntryIds,
+    func: &FunctionRecord,
+    offset: i64,
+) -> Result<Option<LocationList>> {
+    if entry.attr(gimli::DW_AT_const_value)?.is_some() {
+        return Ok(None);
+    }
+
+    let mut kept = Vec::new();
+    match entry.attr_value(gimli::DW_AT_location)? {
+        None => (),
+        Some(gimli::AttributeValue::Exprloc(_)) => return Ok(None),
+        Some(x) => {
+            let mut locations = dwarf
+                .attr_locations(unit, x)?
+                .ok_or_else(|| anyhow!("parameter has an unexpected location"))?;
+            while let Some(x) = locations.next()? {
+                // An empty location says the parameter is nowhere
+                if x.data.0.is_empty() || x.range.begin >= x.range.end {
+                    continue;
+                }
+
+                let data = Expression::from(
+                    x.data,
+                    unit.encoding(),
+                    Some(dwarf),
+                    Some(unit),
+                    Some(ids),
+                    &convert_address,
+                )?;
+                kept.push((x.range.begin, x.range.end, data));
+            }
+        }
+    }
+    kept.sort_by_key(|x| x.0);
+
+    let mut holes = Vec::new();
+    for r in &func.trusted() {
+        let (mut start, end) = (func.range.start + r.start, func.range.start + r.end);
+        for &(b, e, _) in &kept {
+            if e <= start || b >= end {
+                continue;
+            }
+            if b > start {
+                holes.push((start, b));
+            }
+            start = start.max(e);
+        }
+        if start < end {
+            holes.push((start, end));
+        }
+    }
+
+    if holes.is_empty() {
+        return Ok(None);
+    }
+
+    let mut slot = Expression::new();
+    slot.op_breg(func.base.register, offset);
+
+    let mut entries = kept;
+    entries.extend(holes.into_iter().map(|(b, e)| (b, e, slot.clone())));
+    entries.sort_by_key(|x| x.0);
+
+    // Before version 5 a start and end are relative to the base address,
+    // which is the unit's unless we say otherwise
+    let mut list = vec![Location::BaseAddress {
+        address: Address::Constant(0),
+    }];
+    list.extend(entries.into_iter().map(|(b, e, data)| Location::StartEnd {
+        begin: Address::Constant(b),
+        end: Address::Constant(e),
+        data,
+    }));
+
+    Ok(Some(LocationList(list)))
+}
+
+/// A copy of the object in `data`, in which each integer parameter of each
+/// function in `report` which saved its arguments may be found in its slot
+/// wherever the compiler did not say where it is
+pub fn rewrite(data: &[u8], report: &ScanReport) -> Result<Vec<u8>> {
+    let object = object::File::parse(data)?;
+    if object.kind() == ObjectKind::Relocatable {
+        return Err(anyhow!("relocatable objects cannot be rewritten"));
+    }
+
+    let endian = if object.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+
+    // Having no relocations, the sections may be read as they are
+    let arena = Arena::new();
+    let load_section = |id: SectionId| -> Result<_> {
+        let data = match object.section_by_name(id.name()) {
+            Some(x) => x.uncompressed_data()?,
+            None => Cow::Borrowed(&[][..]),
+        };
+        Ok(gimli::EndianSlice::new(arena.alloc(data), endian))
+    };
+    let mut dwarf = gimli::Dwarf::load(&load_section)?;
+    let line = downgrade_line_programs(&dwarf)?;
+    dwarf.debug_line = gimli::DebugLine::new(arena.alloc(Cow::Owned(line)), endian);
+    let mut converted = write::Dwarf::from(&dwarf, &convert_address)?;
+
+    let mut units = Vec::new();
+    let mut ids = EntryIds::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next()? {
+        let unit = dwarf.unit(header)?;
+        let id = converted.units.id(units.len());
+        let out = converted.units.get(id);
+        let mut tree = unit.entries_tree(None)?;
+        map_entries(&unit, tree.root()?, out, (id, out.root()), &mut ids)?;
+        units.push((id, unit));
+    }
+
+    for func in &report.functions {
+        for (offset, param) in func.slots() {
+            let at = DebugInfoOffset(param.die as usize);
+            let Some((id, unit)) = units
+                .iter()
+                .find(|x| at.to_unit_offset(&x.1.header).is_some())
+            else {
+                return Err(anyhow!("{}(): parameter not in any unit", func.name));
+            };
+            let entry = unit.entry(at.to_unit_offset(&unit.header).unwrap())?;
+            let Some(list) = location_list(&dwarf, unit, &entry, &ids, func, offset)? else {
+                continue;
+            };
+
+            let entry_id = ids[&UnitSectionOffset::DebugInfoOffset(at)].1;
+            let out = converted.units.get_mut(*id);
+            let list = out.locations.add(list);
+            out.get_mut(entry_id).set(
+                gimli::DW_AT_location,
+                write::AttributeValue::LocationListRef(list),
+            );
+        }
+    }
+
+    let mut sections = Sections::new(EndianVec::new(endian));
+    converted.write(&mut sections)?;
+
+    // Sections we have but wrote nothing to are emptied, lest they be
+    // read instead
+    let mut contents = Vec::new();
+    sections.for_each(|id, x| {
+        if WRITTEN.contains(&id)
+            && (!x.slice().is_empty() || object.section_by_name(id.name()).is_some())
+        {
+            contents.push((id.name(), x.slice().to_vec()));
+        }
+        Ok::<_, anyhow::Error>(())
+    })?;
+
+    let contents = contents
+        .iter()
+        .map(|(name, x)| (*name, x.as_slice()))
+        .collect::<Vec<_>>();
+    let rewritten = elf::update_sections(data, &contents)?;
+    elf::hide_sections(&rewritten, |name| {
+        INDICES.iter().any(|x| x.as_bytes() == name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use gimli::write::AttributeValue;
+    use gimli::{EndianSlice, LittleEndian};
+    use object::elf::ET_EXEC;
+
+    use super::*;
+    use crate::index::tests::function;
+    use crate::{Parameter, Status};
+
+    /// An executable whose DWARF has one function, with one parameter in
+    /// `%rdi` for its first 0x10 bytes
+    fn object() -> Vec<u8> {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 8,
+        };
+        let mut dwarf = write::Dwarf::new();
+        let id = dwarf
+            .units
+            .add(write::Unit::new(encoding, write::LineProgram::none()));
+        let unit = dwarf.units.get_mut(id);
+        let func = unit.add(unit.root(), gimli::DW_TAG_subprogram);
+        let param = unit.add(func, gimli::DW_TAG_formal_parameter);
+
+        let mut rdi = Expression::new();
+        rdi.op_reg(gimli::X86_64::RDI);
+        let list = unit.locations.add(LocationList(vec![Location::StartEnd {
+            begin: Address::Constant(0x1000),
+            end: Address::Constant(0x1010),
+            data: rdi,
+        }]));
+        unit.get_mut(param)
+            .set(gimli::DW_AT_location, AttributeValue::LocationListRef(list));
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut contents = Vec::new();
+        sections
+            .for_each(|id, x| {
+                if !x.slice().is_empty() {
+                    contents.push((id.name(), x.slice().to_vec()));
+                }
+                Ok::<_, gimli::Error>(())
+            })
+            .unwrap();
+
+        let contents = contents
+            .iter()
+            .map(|(name, x)| (*name, x.as_slice()))
+            .collect::<Vec<_>>();
+        elf::tests::object_with(ET_EXEC, &[0xc3; 0x40], &contents)
+    }
+
+    /// The offset of the parameter in the DWARF of the object in `data`, and
+    /// each of its locations, with the operations of each
+    fn locations(data: &[u8]) -> (u64, Vec<(u64, u64, Vec<u8>)>) {
+        let object = object::File::parse(data).unwrap();
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_> {
+            let data = match object.section_by_name(id.name()) {
+                Some(x) => x.data()?,
+                None => &[],
+            };
+            Ok(EndianSlice::new(data, LittleEndian))
+        })
+        .unwrap();
+
+        let unit = dwarf.unit(dwarf.units().next().unwrap().unwrap()).unwrap();
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs().unwrap() {
+            if entry.tag() != gimli::DW_TAG_formal_parameter {
+                continue;
+            }
+
+            let value = entry.attr_value(gimli::DW_AT_location).unwrap().unwrap();
+            let mut list = dwarf.attr_locations(&unit, value).unwrap().unwrap();
+            let mut found = Vec::new();
+            while let Some(x) = list.next().unwrap() {
+                found.push((x.range.begin, x.range.end, x.data.0.to_vec()));
+            }
+            let die = entry.offset().to_debug_info_offset(&unit.header).unwrap();
+            return (die.0 as u64, found);
+        }
+        panic!("no parameter");
+    }
+
+    /// A report of the one function, saving its argument at -40 from
+    /// `%rbp` from +0x8 to +0x20
+    fn report(data: &[u8]) -> ScanReport {
+        let mut func = function("one", 0x1000, 0x1040, Status::Saved { offset: -40 });
+        func.parameters = vec![Parameter {
+            name: None,
+            die: locations(data).0,
+            integer: true,
+        }];
+
+        ScanReport {
+            functions: vec![func],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fills_gaps() {
+        let data = object();
+        let rewritten = rewrite(&data, &report(&data)).unwrap();
+
+        // DW_OP_reg5, and DW_OP_breg6 -40
+        assert_eq!(
+            locations(&rewritten).1,
+            vec![
+                (0x1000, 0x1010, vec![0x55]),
+                (0x1010, 0x1020, vec![0x76, 0x58]),
+            ]
+        );
+    }
+
+    #[test]
+    fn untrusted() {
+        let data = object();
+        let mut report = report(&data);
+        report.functions[0].clobbered = Some(0x18);
+        let rewritten = rewrite(&data, &report).unwrap();
+
+        assert_eq!(locations(&rewritten).1, vec![(0x1000, 0x1010, vec![0x55])]);
+    }
+
+    #[test]
+    fn refuses_relocatable() {
+        let data = elf::tests::object(&[0xc3]);
+        assert!(rewrite(&data, &ScanReport::default()).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Whether the saved arguments may be found at a given address, and where
+//!
+//! An [`Index`] divides the code of each function we examined into the
+//! parts in which its saved arguments are, and are not, valid, by absolute
+//! address, so that a debugger's question of a single address can be
+//! answered without reconsidering every function.  They are valid only
+//! where every slot has been stored, and nowhere in a function which may
+//! write to its slots once they are.
+//!
+//! In a relocatable object each section of code begins at 0, so if there are
+//! several their addresses are ambiguous, and an answer is given only for
+//! the function which starts last.
+
+use anyhow::{anyhow, Result};
+use object::{Object, ObjectSymbol};
+
+use crate::range::register_name;
+use crate::select::parse_address;
+use crate::{CodeRange, FunctionRecord, ScanReport, Status};
+
+/// A part of a function, by absolute address
+#[derive(Debug, Clone)]
+struct Interval {
+    range: CodeRange,
+    /// Index of the function in [`Index::functions`]
+    function: usize,
+    valid: bool,
+}
+
+/// Each function of an object, by address
+#[derive(Debug, Clone)]
+pub struct Index {
+    arch: object::Architecture,
+    functions: Vec<FunctionRecord>,
+    /// Sorted by start address
+    intervals: Vec<Interval>,
+}
+
+/// A slot of the saved argument array
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Slot {
+    /// The offset of the slot from the frame base register
+    pub offset: i64,
+    /// The parameter saved there
+    pub name: Option<String>,
+}
+
+/// What we know of the saved arguments at an address
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Lookup {
+    pub pc: u64,
+    /// The function containing `pc`
+    pub function: String,
+    /// Offset of the function's entry in `.debug_info`
+    pub die: u64,
+    /// The offset of `pc` from the start of the function
+    pub offset: u64,
+    /// True if the function saved its arguments
+    pub saved: bool,
+    /// True if the saved arguments may be found at `pc`
+    pub valid: bool,
+    /// The register relative to which the saved arguments are found, if
+    /// they were saved
+    pub register: Option<String>,
+    /// The slots of the saved argument array, in order of address
+    pub slots: Vec<Slot>,
+}
+
+impl Index {
+    /// Index each function in `report` of `object`
+    pub fn new(object: &object::File, report: ScanReport) -> Self {
+        let mut intervals = Vec::new();
+
+        for (i, func) in report.functions.iter().enumerate() {
+            let absolute = |r: &CodeRange, valid| Interval {
+                range: CodeRange {
+                    start: func.range.start + r.start,
+                    end: func.range.start + r.end,
+                },
+                function: i,
+                valid,
+            };
+
+            let valid = func.trusted();
+
+            // Everything not valid is invalid, which is not quite what
+            // FunctionRecord::invalid() says.
+            let mut next = 0;
+            for r in &valid {
+                intervals.push(absolute(
+                    &CodeRange {
+                        start: next,
+                        end: r.start,
+                    },
+                    false,
+                ));
+                intervals.push(absolute(r, true));
+                next = r.end;
+            }
+            let len = func.range.end - func.range.start;
+            intervals.push(absolute(
+                &CodeRange {
+                    start: next,
+                    end: len,
+                },
+                false,
+            ));
+        }
+
+        intervals.retain(|x| x.range.start < x.range.end);
+        intervals.sort_by_key(|x| x.range.start);
+
+        Index {
+            arch: object.architecture(),
+            functions: report.functions,
+            intervals,
+        }
+    }
+
+    /// What we know of the saved arguments at `pc`, if it is in a function
+    /// we examined
+    pub fn lookup(&self, pc: u64) -> Option<Lookup> {
+        let n = self.intervals.partition_point(|x| x.range.start <= pc);
+        let interval = self.intervals[..n].last().filter(|x| pc < x.range.end)?;
+        let func = &self.functions[interval.function];
+        let saved = matches!(func.status, Status::Saved { .. });
+
+        Some(Lookup {
+            pc,
+            function: func.name.clone(),
+            die: func.die,
+            offset: pc - func.range.start,
+            saved,
+            valid: interval.valid,
+            register: saved.then(|| register_name(self.arch, func.base.register)),
+            slots: func
+                .slots()
+                .into_iter()
+                .map(|(offset, param)| Slot {
+                    offset,
+                    name: param.name.clone(),
+                })
+                .collect(),
+        })
+    }
+}
+
+/// Parse `s` as a hex number, with or without `0x`, as a debugger would, or
+/// as `SYMBOL` or `SYMBOL+OFFSET` with `symbol` giving the address of each
+/// symbol
+///
+/// Only what is not a hex number is taken to be a symbol, so a symbol which
+/// is, such as `add`, must be given as `add+0`.
+pub(crate) fn resolve_with(s: &str, symbol: impl Fn(&str) -> Option<u64>) -> Result<u64> {
+    let (name, offset) = match s.rsplit_once('+') {
+        Some((name, offset)) => (name, parse_address(offset)?),
+        None => match parse_address(s) {
+            Ok(x) => return Ok(x),
+            Err(_) => (s, 0),
+        },
+    };
+
+    symbol(name)
+        .map(|x| x + offset)
+        .ok_or_else(|| anyhow!("no such symbol: {name}"))
+}
+
+/// Parse `s` as an address in `object`, either a hex number, or `SYMBOL` or
+/// `SYMBOL+OFFSET`, a symbol being taken only for what is not hex
+pub fn resolve(object: &object::File, s: &str) -> Result<u64> {
+    resolve_with(s, |name| {
+        object
+            .symbols()
+            .chain(object.dynamic_symbols())
+            .find(|x| x.is_definition() && x.name() == Ok(name))
+            .map(|x| x.address())
+    })
+}
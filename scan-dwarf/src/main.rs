@@ -2,637 +2,921 @@
 // That file doesn't have a copyright notice, but I think the whole of gimli is
 //     (C) The Rust Project Developers
 
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::{env, fs};
 
 use anyhow::{anyhow, Context, Result};
-use fallible_iterator::FallibleIterator;
-use itertools::Itertools;
+use globset::GlobBuilder;
 use object::{Object, ObjectKind};
-use typed_arena::Arena;
+use regex::Regex;
+
+use scan_dwarf::baseline::Baseline;
+use scan_dwarf::check::{self, Rules};
+use scan_dwarf::ctf::{self, Ctf};
+use scan_dwarf::index::{self, IndexFile};
+use scan_dwarf::lookup::{self, Index};
+use scan_dwarf::policy::{Level, Policy};
+use scan_dwarf::prologue;
+use scan_dwarf::saveargs;
+use scan_dwarf::schema::{self, Record};
+use scan_dwarf::select::Selector;
+use scan_dwarf::stats::Stats;
+use scan_dwarf::{
+    cache, diff, explain, format, locations, scan_object, scan_object_with, Outcome, ScanReport,
+    Severity,
+};
 
-mod reloc;
+#[derive(Debug, Clone, Copy)]
+enum Output {
+    Text,
+    Json,
+    Sarif,
+}
+
+/// Scan the functions `selector` chooses in `object`, or fetch the results of
+/// having done so from the cache in `cache_dir`
+///
+/// Only the results of scanning every function are cached.
+fn scan_cached(
+    path: &str,
+    object: &object::File,
+    cache_dir: Option<&Path>,
+    selector: &Selector,
+) -> Result<ScanReport> {
+    if !selector.is_empty() {
+        return Ok(scan_object_with(object, selector));
+    }
+
+    let Some(dir) = cache_dir else {
+        return Ok(scan_object(object));
+    };
+
+    let key = cache::key(object).with_context(|| format!("computing cache key for {path}"))?;
+    if let Some(report) = cache::load(dir, &key) {
+        return Ok(report);
+    }
+
+    let report = scan_object(object);
+    if report.is_complete() {
+        if let Err(x) = cache::store(dir, &key, &report) {
+            eprintln!("{path}: WARNING: failed to cache results: {x:?}");
+        }
+    }
 
-#[derive(Debug, Clone, serde::Serialize)]
-struct CodeRange {
-    start: u64,
-    end: u64,
+    Ok(report)
 }
 
-impl CodeRange {
-    /// The extent of `entry` as a CodeRange
-    fn from_function_die<T: gimli::Reader>(
-        entry: &gimli::DebuggingInformationEntry<T>,
-    ) -> Result<Option<Self>> {
-        assert!(entry.tag() == gimli::DW_TAG_subprogram);
-
-        let low = match entry.attr_value(gimli::DW_AT_low_pc)? {
-            Some(gimli::AttributeValue::Addr(x)) => x,
-            Some(y) => return Err(anyhow!("unknown kind of low_pc: {y:?}")),
-            None => return Ok(None),
-        };
-
-        let high = match entry.attr_value(gimli::DW_AT_high_pc)? {
-            Some(gimli::AttributeValue::Addr(x)) => x,
-            Some(y) => return Err(anyhow!("unknown kind of high_pc: {y:?}")),
-            None => return Ok(None),
-        };
-
-        Ok(Some(CodeRange {
-            start: low,
-            end: high,
-        }))
+/// `path` with `root` removed from its start, or if it is not within `root`
+/// and `basename` is true, its file name alone
+fn relative_path(path: &str, root: Option<&str>, basename: bool) -> String {
+    let path = Path::new(path);
+
+    if let Some(x) = root.and_then(|x| path.strip_prefix(x).ok()) {
+        return x.to_string_lossy().into_owned();
+    }
+
+    match path.file_name() {
+        Some(x) if basename => x.to_string_lossy().into_owned(),
+        _ => path.to_string_lossy().into_owned(),
     }
 }
 
-/// A simple description of an offset from a base register, as used for local
-/// variables
-#[derive(Debug, Clone)]
-struct BaseOffset {
-    valid: Vec<CodeRange>,
-    offset: i64,
-    register: gimli::Register,
+/// The results for `path`, which is either an object to scan or the JSON
+/// output of a previous scan
+///
+/// Objects, whether scanned or named in previous results, are named by their
+/// path with `root` removed from its start, or if `basename` is true and they
+/// are not within `root`, by their file name alone, so that the same object
+/// in different places can be compared.
+fn load_results(
+    path: &str,
+    root: Option<&str>,
+    basename: bool,
+    cache_dir: Option<&Path>,
+) -> Result<Vec<Record>> {
+    let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+
+    if let Ok(object) = object::File::parse(&*mmap) {
+        return Ok(schema::records(
+            &relative_path(path, root, basename),
+            &scan_cached(path, &object, cache_dir, &Selector::default())?,
+        ));
+    }
+
+    let mut records = schema::read(&mmap[..])
+        .with_context(|| format!("{path} is neither an object nor scan results"))?;
+
+    for record in &mut records {
+        let path = record.path_mut();
+        *path = relative_path(path, root, basename);
+    }
+
+    Ok(records)
 }
 
-impl BaseOffset {
-    /// Given a frame_base attribute, return a suitable BaseOffset
-    fn from_frame_base<T: gimli::Reader>(
-        frame_base: gimli::AttributeValue<T>,
-        range: &CodeRange, // Extent of the function with this attribute
-        object: &object::File,
-        dwarf: &gimli::Dwarf<T>,
-        unit: &gimli::Unit<T>,
-    ) -> Result<Option<BaseOffset>> {
-        let function_start_pc = range.start;
-
-        let mut bases = match frame_base {
-            gimli::AttributeValue::LocationListsRef(ll) => {
-                let list = loclist_as_offsets(ll, dwarf, unit)?;
-
-                let pointer_reg = match object.architecture() {
-                    object::Architecture::X86_64 => gimli::Register(6), // %rbp
-                    object::Architecture::Aarch64 => gimli::Register(31), // %sp
-                    x => return Err(anyhow!("unknown architecture {x:?}")),
-                };
-
-                // This is the value of the assignment, remember that
-                BaseOffset::from_merged_base_offsets(pointer_reg, object, list)
-                    .context("merging frame-bases")?
-            }
-            gimli::AttributeValue::Exprloc(x) => {
-                // We see these, but is exceptionally unlikely to see one that
-                // also has saved arguments, because this implies the base is
-                // constant (and thus if it's the frame pointer, it's the
-                // _callers_).
-                Some(BaseOffset::from_exprloc(x, range, unit)?)
-            }
-            x => return Err(anyhow!("unexpected frame-bases: {x:?}")),
-        };
+/// Compare two sets of results, failing if the second is worse
+fn diff_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optopt(
+        "t",
+        "threshold",
+        "report coverage changes of more than PCT percentage points (default 1)",
+        "PCT",
+    );
+    opts.optopt(
+        "",
+        "old-root",
+        "remove DIR from paths in the old results, rather than naming objects by file name",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "new-root",
+        "remove DIR from paths in the new results, rather than naming objects by file name",
+        "DIR",
+    );
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let threshold = match matches.opt_str("t") {
+        Some(x) => x
+            .parse::<f64>()
+            .with_context(|| format!("invalid threshold: {x}"))?,
+        None => 1.0,
+    };
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    let [old, new] = &matches.free[..] else {
+        return Err(anyhow!("usage: scan-dwarf diff [options] OLD NEW"));
+    };
+
+    let old = load_results(
+        old,
+        matches.opt_str("old-root").as_deref(),
+        true,
+        cache_dir.as_deref(),
+    )?;
+    let new = load_results(
+        new,
+        matches.opt_str("new-root").as_deref(),
+        true,
+        cache_dir.as_deref(),
+    )?;
+    let changes = diff::diff(&old, &new, threshold);
+
+    for change in &changes {
+        println!("{change}");
+    }
+
+    if changes.iter().any(|x| x.is_regression()) {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
 
-        if let Some(x) = bases.as_mut() {
-            x.relativize(function_start_pc);
+/// Scan the functions `selector` chooses in each object at `paths` which
+/// might have saved arguments, passing what we find to `f` once `policy` has
+/// been applied, with those in which they are valid in less than
+/// `min_coverage` percent reported
+fn scan_paths(
+    paths: &[String],
+    cache_dir: Option<&Path>,
+    selector: &Selector,
+    policy: &Policy,
+    ctf_parent: Option<&Ctf>,
+    min_coverage: Option<f64>,
+    mut f: impl FnMut(&str, ScanReport) -> Result<()>,
+) -> Result<()> {
+    for path in paths {
+        let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+        let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+        let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+
+        if object.section_by_name(".SUNW_ctf").is_none() {
+            continue; // No CTF, no saved arguments
         }
 
-        Ok(bases)
-    }
-
-    /// given a DWARF location expression turn it into a BaseOffset
-    fn from_exprloc<T: gimli::Reader>(
-        expr: gimli::Expression<T>,
-        range: &CodeRange,
-        unit: &gimli::Unit<T>,
-    ) -> Result<Self> {
-        let mut ops = expr.operations(unit.encoding());
-        let mut bo: Option<BaseOffset> = None;
-
-        if let Some(op) = ops.next()? {
-            match op {
-                gimli::Operation::RegisterOffset {
-                    register,
-                    offset,
-                    base_type: _,
-                } => {
-                    bo = Some(BaseOffset {
-                        valid: vec![range.clone()],
-                        offset,
-                        register,
-                    })
-                }
-                x => return Err(anyhow!("unexpected frame_base calculation: {x:?}")),
+        match object.kind() {
+            ObjectKind::Executable | ObjectKind::Dynamic | ObjectKind::Relocatable => (),
+            _ => {
+                eprintln!(
+                    "{path}: WARNING: only executable, relocatable and dynamic objects and supported"
+                );
+                continue;
             }
         }
 
-        if ops.count()? != 0 {
-            return Err(anyhow!("base expression has multiple location operations"));
+        let mut report = scan_cached(path, &object, cache_dir, selector)?;
+        // Not cached, as the parent may differ from run to run
+        let diags = ctf::check(&object, ctf_parent, &report);
+        report.diagnostics.extend(diags);
+        if let Some(min) = min_coverage {
+            let diags = report.low_coverage(min);
+            report.diagnostics.extend(diags);
         }
+        policy.apply(&mut report);
+        f(path, report)?;
+    }
 
-        Ok(bo.unwrap())
-    }
-
-    /// If there exists a base offset that is relative to the frame register, or
-    /// there exist multiple each with the same offset, that is our offset.
-    /// Return a merged entry reflecting all valid pcs.
-    ///
-    /// XXX: As a sad quirk, on AArch64, if the register is the stack pointer,
-    /// ignore 0 offsets.  This is because GCC seems unwilling to _say_ the
-    /// frame base is at the frame pointer, on ARM.  So we get entries at SP+0
-    /// (which are valid frame bases, but not for us), and at SP+<something>,
-    /// which iff equal(something) is ours.
-    ///
-    /// XXX: We could perhaps figure this out better, but I'm not sure how we
-    /// could do it realistically.
-    ///
-    /// The possibility of multiples come from code generation such as:
-    ///   [ prologue ]
-    ///   [ work work work ]
-    ///   [ epilogue ] <------------.
-    ///   [ return ]                |
-    ///   [ prologue ]              |
-    ///   [ more work work work ]   |
-    ///   [ jump to epilogue ] -----'
-    ///
-    /// which we do see generated
-    fn from_merged_base_offsets(
-        reg: gimli::Register,
-        object: &object::File,
-        v: Vec<BaseOffset>,
-    ) -> Result<Option<BaseOffset>> {
-        let mut it: Box<dyn std::iter::Iterator<Item = BaseOffset>> =
-            if object.architecture() == object::Architecture::Aarch64 {
-                Box::new(v.into_iter().filter(|x| x.offset != 0))
-            } else {
-                Box::new(v.into_iter())
-            };
-        let mut first = it.find(|x| x.register == reg).unwrap();
-
-        // We side-effect `first` to be our return value, and gather each entry
-        // that is a register but not an offset match into `bogons`.
-        //
-        // I don't like this.
-        let mut bogons = it
-            .filter(|x| x.register == reg)
-            .filter_map(|y| {
-                if y.offset == first.offset {
-                    first.valid.extend(y.valid);
-                    None
-                } else {
-                    Some(y)
-                }
-            })
-            .collect::<Vec<_>>();
+    Ok(())
+}
 
-        if !bogons.is_empty() {
-            bogons.push(first);
-            Err(anyhow!("multiple frame-pointer offsets: {bogons:?}"))
+/// Hold results to the rules of a policy file
+fn check_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("j", "json", "json output");
+    opts.reqopt("p", "policy", "check against the rules in FILE", "FILE");
+    opts.optopt(
+        "",
+        "root",
+        "remove DIR from paths in previous results",
+        "DIR",
+    );
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let rules = Rules::load(Path::new(&matches.opt_str("p").unwrap()))?;
+    let root = matches.opt_str("root");
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    let mut errors = false;
+    for path in &matches.free {
+        let records = load_results(path, root.as_deref(), false, cache_dir.as_deref())?;
+        let findings = check::check(&rules, &records);
+        errors |= findings.iter().any(|x| x.severity == Severity::Error);
+
+        if matches.opt_present("j") {
+            for finding in findings {
+                schema::write(&mut io::stdout(), &Record::Diagnostic(finding))?;
+            }
         } else {
-            Ok(Some(first))
+            format::text::findings(&findings, &mut io::stdout())?;
         }
     }
 
-    /// Cause all the validity entries in a BaseOffset to be relative to to, in place
-    fn relativize(&mut self, to: u64) {
-        self.valid.iter_mut().for_each(|x| {
-            x.start -= to;
-            x.end -= to;
-        });
+    Ok(if errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Explain in detail what was found of each function chosen
+fn explain_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    selector_options(&mut opts);
+    let matches = opts.parse(args)?;
+    let selector = selector(&matches)?;
+
+    // Explaining every function of an object is never what was meant
+    if selector.is_empty() {
+        return Err(anyhow!(
+            "usage: scan-dwarf explain --name REGEX|--address PC|... OBJECT..."
+        ));
     }
 
-    /// Take a BaseOffset and a range, and return a vec of CodeRange which covers
-    /// the parts of the original the BaseOffset misses.
-    ///
-    /// range is the more complete range to which the baseoffset is relative
-    fn invert(&self, range: &CodeRange) -> Vec<CodeRange> {
-        let mut ret: Vec<CodeRange> = Vec::with_capacity(self.valid.len());
+    let mut found = 0;
+    for path in &matches.free {
+        let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+        let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+        let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
 
-        if let Some(x) = self.valid.first() {
-            if 0 < x.start {
-                ret.push(CodeRange {
-                    start: 0,
-                    end: x.start - 1,
-                });
-            }
-        }
+        println!("{path}:");
+        found += explain::explain(&object, &selector, &mut io::stdout())
+            .with_context(|| format!("Explaining {path}"))?;
+    }
 
-        for (r1, r2) in self.valid.iter().tuple_windows() {
-            if r1.end < r2.start {
-                ret.push(CodeRange {
-                    start: r1.end,
-                    end: r2.start,
-                });
-            }
-        }
+    if found == 0 {
+        return Err(anyhow!("no function matches"));
+    }
 
-        if let Some(x) = self.valid.last() {
-            if x.end + 1 < range.end - range.start {
-                ret.push(CodeRange {
-                    start: x.end + 1,
-                    end: range.end - range.start,
-                });
-            }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Say whether the saved arguments may be found at each address given, and
+/// where
+fn lookup_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("j", "json", "json output");
+    opts.optflag("i", "index", "look up in an index, rather than an object");
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    let [path, pcs @ ..] = &matches.free[..] else {
+        return Err(anyhow!(
+            "usage: scan-dwarf lookup [options] OBJECT|INDEX PC..."
+        ));
+    };
+
+    let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+
+    let lookups = if matches.opt_present("i") {
+        let index = IndexFile::parse(&mmap).with_context(|| format!("Parsing {path}"))?;
+        pcs.iter()
+            .map(|x| Ok(index.lookup(index.resolve(x)?)))
+            .collect::<Result<Vec<_>>>()?
+    } else {
+        let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+
+        // Stripped objects may still say where their arguments are, and if
+        // they have no symbols, the names of their functions will do
+        if let Some(index) = saveargs::read(&object)? {
+            pcs.iter()
+                .map(|x| {
+                    let pc = lookup::resolve(&object, x).or_else(|_| index.resolve(x))?;
+                    Ok(index.lookup(pc))
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+            let index = Index::new(&object, report);
+            pcs.iter()
+                .map(|x| Ok(index.lookup(lookup::resolve(&object, x)?)))
+                .collect::<Result<Vec<_>>>()?
         }
+    };
 
-        ret
+    for (query, lookup) in pcs.iter().zip(&lookups) {
+        if matches.opt_present("j") {
+            format::json::lookup(path, query, lookup.as_ref(), &mut io::stdout())?;
+        } else {
+            format::text::lookup(path, query, lookup.as_ref(), &mut io::stdout())?;
+        }
     }
-}
 
-/// Return the offset of a given DebuggingInformationEntry
-fn entry_to_die_offset<T: gimli::Reader>(
-    entry: &gimli::DebuggingInformationEntry<T>,
-    unit: &gimli::Unit<T>,
-) -> Option<<T as gimli::Reader>::Offset> {
-    entry
-        .offset()
-        .to_debug_info_offset(&unit.header)
-        .map(|x| x.0)
+    Ok(if lookups.iter().all(Option::is_some) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
 }
 
-/// Translate various kinds of DWARF attribute values to strings
-fn attr_to_string<T: gimli::Reader>(
-    attr: gimli::AttributeValue<T>,
-    dwarf: &gimli::Dwarf<T>,
-    unit: &gimli::Unit<T>,
-) -> Option<String> {
-    match attr {
-        gimli::AttributeValue::DebugStrRef(_) => dwarf
-            .attr_string(unit, attr)
-            .unwrap()
-            .to_string()
-            .ok()
-            .map(|x| x.into_owned()),
-        gimli::AttributeValue::String(_) => dwarf
-            .attr_string(unit, attr)
-            .unwrap()
-            .to_string()
-            .ok()
-            .map(|x| x.into_owned()),
-        gimli::AttributeValue::FileIndex(n) => {
-            let nameref = unit
-                .line_program
-                .as_ref()
-                .and_then(|x| x.header().file(n).map(|f| f.path_name()))
-                .unwrap();
-
-            attr_to_string(nameref, dwarf, unit)
+/// Write an index of where the saved arguments of each function in an object
+/// may be found, or verify that an existing index is correct
+fn index_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag(
+        "",
+        "verify",
+        "check INDEX against OBJECT, rather than writing it",
+    );
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    let [path, output] = &matches.free[..] else {
+        return Err(anyhow!("usage: scan-dwarf index [options] OBJECT INDEX"));
+    };
+
+    let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+    let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+
+    if matches.opt_present("verify") {
+        let data = fs::read(output).with_context(|| format!("Reading {output}"))?;
+        let index = IndexFile::parse(&data).with_context(|| format!("Parsing {output}"))?;
+        let problems = index::verify(&index, &object)?;
+
+        for problem in &problems {
+            println!("{output}: {problem}");
         }
-        _ => panic!("Unknown attribute for string conversion: {attr:?}"),
+
+        return Ok(if problems.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
     }
+
+    let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+    if !report.is_complete() {
+        return Err(anyhow!("{path}: could not examine the whole object"));
+    }
+
+    let mut data = Vec::new();
+    index::write(object.architecture(), &report.functions, &mut data)?;
+    fs::write(output, data).with_context(|| format!("Writing {output}"))?;
+
+    Ok(ExitCode::SUCCESS)
 }
 
-/// True if this function is concrete, meaning in our terms that is not a
-/// prototype, not an abstract parent of an inlined call, and not itself
-/// inlined
-fn is_concrete_function<T: gimli::Reader>(
-    entry: &gimli::DebuggingInformationEntry<T>,
-) -> Result<bool> {
-    assert!(entry.tag() == gimli::DW_TAG_subprogram);
-
-    if entry.attr_value(gimli::DW_AT_declaration)?.is_some()
-        || entry.attr_value(gimli::DW_AT_abstract_origin)?.is_some()
-    {
-        Ok(false)
-    } else {
-        match entry.attr_value(gimli::DW_AT_inline)? {
-            Some(gimli::AttributeValue::Inline(x)) => match x {
-                gimli::DW_INL_inlined => Ok(false),
-                gimli::DW_INL_declared_inlined => Ok(false),
-                gimli::DW_INL_not_inlined => Ok(true),
-                gimli::DW_INL_declared_not_inlined => Ok(true),
-                _ => Err(anyhow!("function has weird inline attribute: {x:?}")),
-            },
-            Some(x) => Err(anyhow!("function has weird inline attribute type: {x:?}")),
-            None => Ok(true),
+/// Write a copy of an object with a section saying where the arguments of
+/// each function are saved, or check that such a section survives stripping
+fn saveargs_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag(
+        "",
+        "check",
+        "check that the section describes OBJECT correctly once stripped",
+    );
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    let (path, output) = match (&matches.free[..], matches.opt_present("check")) {
+        ([path], true) => (path, None),
+        ([path, output], false) => (path, Some(output)),
+        _ => {
+            return Err(anyhow!(
+                "usage: scan-dwarf saveargs [options] OBJECT OUTPUT\n       \
+                 scan-dwarf saveargs --check OBJECT"
+            ))
+        }
+    };
+
+    let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+
+    let Some(output) = output else {
+        let problems = saveargs::check(&mmap).with_context(|| format!("Checking {path}"))?;
+        for problem in &problems {
+            println!("{path}: {problem}");
         }
+
+        return Ok(if problems.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        });
+    };
+
+    let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+    let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+    if !report.is_complete() {
+        return Err(anyhow!("{path}: could not examine the whole object"));
     }
-}
 
-/// Source file of the given DebuggingInformationEntry
-fn die_source_file<T: gimli::Reader>(
-    entry: &gimli::DebuggingInformationEntry<T>,
-    dwarf: &gimli::Dwarf<T>,
-    unit: &gimli::Unit<T>,
-) -> Result<Option<String>> {
-    Ok(entry
-        .attr_value(gimli::DW_AT_decl_file)?
-        .and_then(|x| attr_to_string(x, dwarf, unit)))
+    let data = saveargs::write(&mmap, &report).with_context(|| format!("Rewriting {path}"))?;
+    fs::write(output, data).with_context(|| format!("Writing {output}"))?;
+    fs::set_permissions(output, file.metadata()?.permissions())?;
+
+    Ok(ExitCode::SUCCESS)
 }
 
-/// True if a DebuggingInformationEntry is not assembler (we're lax about what "C source" means)
-fn die_has_c_source<T: gimli::Reader>(
-    entry: &gimli::DebuggingInformationEntry<T>,
-    dwarf: &gimli::Dwarf<T>,
-    unit: &gimli::Unit<T>,
-) -> Result<bool> {
-    match die_source_file(entry, dwarf, unit)? {
-        Some(file) if file.ends_with(".s") || file.ends_with(".S") => Ok(false),
-        Some(_) => Ok(true),
-        None => Ok(false), // GAS has no source file names
+/// Write a copy of an object in which debuggers find parameters in the slots
+/// where their arguments were saved
+fn locations_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    let [path, output] = &matches.free[..] else {
+        return Err(anyhow!(
+            "usage: scan-dwarf locations [options] OBJECT OUTPUT"
+        ));
+    };
+
+    let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+    let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+    let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+    if !report.is_complete() {
+        return Err(anyhow!("{path}: could not examine the whole object"));
     }
+
+    let data = locations::rewrite(&mmap, &report).with_context(|| format!("Rewriting {path}"))?;
+    fs::write(output, data).with_context(|| format!("Writing {output}"))?;
+    fs::set_permissions(output, file.metadata()?.permissions())?;
+
+    Ok(ExitCode::SUCCESS)
 }
 
-/// Given a DWARF location list, return each as BaseOffsets
-fn loclist_as_offsets<T: gimli::Reader>(
-    ll: gimli::LocationListsOffset<T::Offset>,
-    dwarf: &gimli::Dwarf<T>,
-    unit: &gimli::Unit<T>,
-) -> Result<Vec<BaseOffset>> {
-    let mut locs = dwarf.locations(unit, ll)?;
-    let mut vec: Vec<BaseOffset> = Vec::new();
-
-    while let Some(loc) = locs.next()? {
-        vec.push(BaseOffset::from_exprloc(
-            loc.data,
-            &CodeRange {
-                start: loc.range.begin,
-                end: loc.range.end,
-            },
-            unit,
-        )?);
-    }
-
-    Ok(vec)
+/// Describe where the code of each function of each object seems to save its
+/// arguments, and how often that agrees with its DWARF, if it has any
+fn prologue_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("j", "json", "json output");
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+
+    if matches.free.is_empty() {
+        return Err(anyhow!("usage: scan-dwarf prologue [options] OBJECT..."));
+    }
+
+    for path in &matches.free {
+        let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+        let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+        let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+
+        let mut functions =
+            prologue::infer(&object).with_context(|| format!("Examining code of {path}"))?;
+        let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+        prologue::compare(&mut functions, &report);
+
+        if matches.opt_present("j") {
+            format::json::prologue(path, &functions, &mut io::stdout())?;
+        } else {
+            format::text::prologue(path, &functions, &mut io::stdout())?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
 }
 
-// True if the type of this entry would be passed in an integer register
-fn is_register_type<T: gimli::Reader>(
-    entry: &gimli::DebuggingInformationEntry<T>,
-    unit: &gimli::Unit<T>,
-) -> Result<bool> {
-    let tipe = match entry.attr_value(gimli::DW_AT_type)? {
-        Some(gimli::AttributeValue::UnitRef(x)) => unit.entry(x)?,
-        Some(x) => return Err(anyhow!("type has weird value type: {x:?}")),
-        None => return Err(anyhow!("entry has no value?")),
-    };
+/// Say by which protocols a debugger would recover the arguments of each
+/// function
+fn protocols_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("j", "json", "json output");
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
 
-    match tipe.tag() {
-        gimli::DW_TAG_base_type => match tipe.attr_value(gimli::DW_AT_encoding)? {
-            Some(gimli::AttributeValue::Encoding(x)) => match x {
-                gimli::DW_ATE_complex_float | gimli::DW_ATE_float => Ok(false),
-                gimli::DW_ATE_signed | gimli::DW_ATE_unsigned => Ok(true),
-                gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char => Ok(true),
-                gimli::DW_ATE_boolean => Ok(true),
-                _ => Err(anyhow!("base type has unknown encoding: {x:?}")),
-            },
-            Some(x) => Err(anyhow!("base type has weird encoding: {x:?}")),
-            None => Err(anyhow!("base type has no encoding!")),
-        },
-        gimli::DW_TAG_pointer_type => Ok(true),
-        gimli::DW_TAG_array_type => Ok(true),
-        gimli::DW_TAG_enumeration_type => Ok(true),
-        gimli::DW_TAG_volatile_type => is_register_type(&tipe, unit),
-        gimli::DW_TAG_typedef => is_register_type(&tipe, unit),
-        gimli::DW_TAG_const_type => is_register_type(&tipe, unit),
-        gimli::DW_TAG_structure_type => Ok(false),
-        gimli::DW_TAG_union_type => {
-            // A union fits in a register if all of its variants do
-            let mut tree = unit.entries_tree(Some(tipe.offset()))?;
-            let root = tree.root()?;
-            let mut children = root.children();
-
-            while let Some(child) = children.next()? {
-                if !is_register_type(child.entry(), unit)? {
-                    return Ok(false);
-                }
-            }
+    if matches.free.is_empty() {
+        return Err(anyhow!("usage: scan-dwarf protocols [options] OBJECT..."));
+    }
+
+    for path in &matches.free {
+        let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+        let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+        let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
 
-            Ok(true)
+        let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+        if matches.opt_present("j") {
+            format::json::protocols(path, &report, &mut io::stdout())?;
+        } else {
+            format::text::protocols(path, &report, &mut io::stdout())?;
         }
-        x => Err(anyhow!("entry has unknown type type: {x:?}")),
     }
+
+    Ok(ExitCode::SUCCESS)
 }
 
-fn dump_file(
-    path: &str,
-    object: &object::File,
-    endian: gimli::RunTimeEndian,
-    output: Output,
-) -> Result<()> {
-    let arena_data = Arena::new();
-    let arena_relocations = Arena::new();
+/// Describe the CTF of an object, or write a copy of the object with its CTF
+/// extended to say where the arguments of each function are saved
+fn ctf_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    ctf_parent_option(&mut opts);
+    let matches = opts.parse(args)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+    let parent = ctf_parent(&matches)?;
+
+    let (path, output) = match &matches.free[..] {
+        [path] => (path, None),
+        [path, output] => (path, Some(output)),
+        _ => return Err(anyhow!("usage: scan-dwarf ctf [options] OBJECT [OUTPUT]")),
+    };
 
-    // Load a section and return as `Cow<[u8]>`.
-    let load_section = |id: gimli::SectionId| -> Result<_> {
-        reloc::load_file_section(id, object, endian, false, &arena_data, &arena_relocations)
+    let file = fs::File::open(path).with_context(|| format!("Opening {path}"))?;
+    let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
+    let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
+
+    let Some(output) = output else {
+        let ctf = ctf::read(&object)
+            .with_context(|| format!("Reading CTF from {path}"))?
+            .ok_or_else(|| anyhow!("{path}: no CTF"))?;
+        format::text::ctf(
+            path,
+            &ctf,
+            parent.as_ref(),
+            object.architecture(),
+            &ctf::function_symbols(&object),
+            &mut io::stdout(),
+        )?;
+        return Ok(ExitCode::SUCCESS);
     };
 
-    // Load all of the sections.
-    let dwarf = gimli::Dwarf::load(&load_section)?;
-
-    // Iterate over the compilation units.
-    let mut iter = dwarf.units();
-    while let Some(header) = iter.next()? {
-        let unit = dwarf.unit(header)?;
-        let mut tree = unit.entries_tree(None)?;
-        let root = tree.root()?;
-        let mut children = root.children();
-
-        while let Some(child) = children.next()? {
-            let funcentry = child.entry();
-            let funcoffset = entry_to_die_offset(funcentry, &unit).unwrap();
-
-            if funcentry.tag() != gimli::DW_TAG_subprogram
-                || !is_concrete_function(funcentry)
-                    .with_context(|| format!("{path}+{funcoffset:#x} is concrete?"))?
-                || !die_has_c_source(funcentry, &dwarf, &unit)
-                    .with_context(|| format!("{path}+{funcoffset:#x} has C source?"))?
-            {
-                continue;
-            }
+    let report = scan_cached(path, &object, cache_dir.as_deref(), &Selector::default())?;
+    if !report.is_complete() {
+        return Err(anyhow!("{path}: could not examine the whole object"));
+    }
 
-            let name = match funcentry
-                .attr_value(gimli::DW_AT_name)?
-                .and_then(|x| attr_to_string(x, &dwarf, &unit))
-            {
-                Some(x) => x,
-                None => continue, // Apparently this may happen in C++, on ARM, sometimes?
-            };
+    let data = ctf::annotate(&mmap, &report).with_context(|| format!("Rewriting {path}"))?;
+    fs::write(output, data).with_context(|| format!("Writing {output}"))?;
+    fs::set_permissions(output, file.metadata()?.permissions())?;
 
-            let range = match CodeRange::from_function_die(funcentry)
-                .with_context(|| format!("looking up {path}+{funcoffset:#x} extent"))?
-            {
-                Some(x) => x,
-                // Apparently this may happen on ARM, where on amd64 we'd
-                // generate an empty function, ARM elides the call (but
-                // not the DWARF?)
-                None => continue,
-            };
+    Ok(ExitCode::SUCCESS)
+}
 
-            let frame_base = match funcentry.attr_value(gimli::DW_AT_frame_base)? {
-                Some(x) => x,
-                None => {
-                    eprintln!("{path}+{funcoffset:#x}: WARNING: {name}() has no frame base");
-                    continue;
-                }
-            };
+/// Add the option giving the parent of children's CTF to `opts`
+fn ctf_parent_option(opts: &mut getopts::Options) {
+    opts.optopt(
+        "",
+        "ctf-parent",
+        "find types CTF shares with its parent in the CTF of OBJECT",
+        "OBJECT",
+    );
+}
 
-            let base_offset = match BaseOffset::from_frame_base(
-                frame_base, &range, object, &dwarf, &unit,
-            ) {
-                Err(x) => {
-                    eprintln!("{path}+{funcoffset:#x} {name}(): reading frame base: {x:?}");
-                    continue;
-                }
-                Ok(Some(x)) => x,
-                Ok(None) => {
-                    eprintln!(
-                        "{path}+{funcoffset:#x}: WARNING: {name}() has no recognized base-pointer"
-                    );
-                    continue;
-                }
-            };
+/// The CTF given by the option added by `ctf_parent_option`
+fn ctf_parent(matches: &getopts::Matches) -> Result<Option<Ctf>> {
+    let Some(path) = matches.opt_str("ctf-parent") else {
+        return Ok(None);
+    };
 
-            let mut nparams = 0;
-            let mut found = false;
-            let mut children = child.children();
-            while let Some(child) = children.next()? {
-                let childentry = child.entry();
-                let childoffset = entry_to_die_offset(childentry, &unit).unwrap();
-
-                match childentry.tag() {
-                    gimli::DW_TAG_formal_parameter => {
-                        if is_register_type(childentry, &unit).with_context(|| {
-                            format!("{path}+{childoffset:#x}: checking parameter type")
-                        })? {
-                            nparams += 1;
-                        } else {
-                            found = true; // Really, we've found it to be invalid
-                            continue;
-                        }
-                    }
-                    gimli::DW_TAG_variable => (),
-                    _ => continue,
-                }
+    let data = fs::read(&path).with_context(|| format!("Reading {path}"))?;
+    let object = object::File::parse(&*data).with_context(|| format!("Parsing {path}"))?;
+    let ctf = ctf::read(&object)
+        .with_context(|| format!("Reading CTF from {path}"))?
+        .ok_or_else(|| anyhow!("{path}: no CTF"))?;
 
-                match childentry
-                    .attr_value(gimli::DW_AT_name)?
-                    .and_then(|x| attr_to_string(x, &dwarf, &unit))
-                {
-                    Some(x) if x == "__illumos_saved_args_v1__" => x,
-                    _ => continue, // Nameless variables exist, but we needn't worry
-                };
-
-                // Our symbol is decidedly unreal
-                if childentry.attr_value(gimli::DW_AT_artificial)?.is_none() {
-                    eprintln!("{path}+{childoffset:#x}: WARNING: {name}() __illumos_saved_args_v1__ is not artificial");
-                }
+    Ok(Some(ctf))
+}
 
-                if let Some(e) = childentry
-                    .attr_value(gimli::DW_AT_location)?
-                    .unwrap()
-                    .exprloc_value()
-                {
-                    let mut ops = e.operations(unit.encoding());
-
-                    if let Some(op) = ops.next()? {
-                        match op {
-                            gimli::read::Operation::FrameOffset { offset: off } => {
-                                let locstr = base_offset
-                                    .valid
-                                    .iter()
-                                    .map(|x| format!("[+{:#x},+{:#x})", x.start, x.end))
-                                    .join(", ");
-
-                                let inv_offset = base_offset.invert(&range);
-
-                                let opstr = inv_offset
-                                    .iter()
-                                    .map(|x| format!("[+{:#x},+{:#x})", x.start, x.end))
-                                    .join(", ");
-
-                                let goodperc = base_offset
-                                    .valid
-                                    .iter()
-                                    .fold(0, |acc, x| acc + (x.end - x.start).max(1))
-                                    as f64
-                                    / (range.end - range.start) as f64
-                                    * 100.0;
-
-                                let badperc = 100.0 - goodperc;
-
-                                match output {
-                                    Output::Json => println!(
-                                        "{}",
-                                        serde_json::to_string(&serde_json::json!({"name": name,
-                                                                                  "nparams": nparams,
-                                                                                  "offset": base_offset.offset + off,
-                                                                                  "valid": base_offset.valid,
-                                                                                  "invalid": inv_offset,
-                                        }))?
-                                    ),
-                                    Output::Text => println!(
-                                        "{path}+{funcoffset:#x} {name}() has {nparams} \
-                                         saved arguments at frame offset {} \
-                                         valid in {locstr} ({goodperc:2.2}%) \
-                                         invalid in {opstr} ({badperc:2.2}%)",
-                                        base_offset.offset + off
-                                    ),
-                                }
-                            }
-                            x => {
-                                eprintln!("{path}+{childoffset:#x}: WARNING: {name}() __illumos_saved_args_v1__ has unexpected location expression: {x:?}");
-                                continue;
-                            }
-                        }
-                    }
+/// Add the options choosing which functions to examine to `opts`
+fn selector_options(opts: &mut getopts::Options) {
+    opts.optopt(
+        "n",
+        "name",
+        "select functions with names matching REGEX",
+        "REGEX",
+    );
+    opts.optopt(
+        "",
+        "linkage-name",
+        "select the function with linkage NAME",
+        "NAME",
+    );
+    opts.optopt(
+        "s",
+        "source",
+        "select functions from source matching GLOB",
+        "GLOB",
+    );
+    opts.optopt(
+        "u",
+        "unit",
+        "select functions in units named by GLOB",
+        "GLOB",
+    );
+    opts.optmulti(
+        "",
+        "address",
+        "select functions overlapping ADDR, START-END, or START+LENGTH",
+        "ADDR",
+    );
+    opts.optmulti("o", "outcome", "select functions with OUTCOME", "OUTCOME");
+    opts.optflag(
+        "",
+        "invalid",
+        "select functions whose saved arguments are somewhere untrustworthy",
+    );
+}
 
-                    if ops.count()? != 0 {
-                        eprintln!("{path}+{childoffset:#x}: WARNING: {name}() __illumos_saved_args_v1__ has extra location operations");
-                        continue;
-                    }
+/// The selector described by the options added by `selector_options`
+fn selector(matches: &getopts::Matches) -> Result<Selector> {
+    let glob = |x: String| -> Result<_> {
+        Ok(GlobBuilder::new(&x)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("invalid glob: {x}"))?
+            .compile_matcher())
+    };
 
-                    found = true;
-                }
-            }
+    let mut selector = Selector {
+        name: matches
+            .opt_str("n")
+            .map(|x| Regex::new(&x).with_context(|| format!("invalid regex: {x}")))
+            .transpose()?,
+        linkage_name: matches.opt_str("linkage-name"),
+        source: matches.opt_str("s").map(glob).transpose()?,
+        unit: matches.opt_str("u").map(glob).transpose()?,
+        invalid: matches.opt_present("invalid"),
+        ..Selector::default()
+    };
 
-            if nparams != 0 && !found {
-                eprintln!(
-                    "{path}+{funcoffset:#x}: WARNING: {name}(): {nparams} parameters but no saved args"
-                );
-            }
-        }
+    for x in matches.opt_strs("address") {
+        selector.add_address(&x)?;
     }
-    Ok(())
+
+    for x in matches.opt_strs("o") {
+        let outcome = Outcome::ALL
+            .into_iter()
+            .find(|o| o.to_string() == x)
+            .ok_or_else(|| anyhow!("unknown outcome: {x}"))?;
+        selector.outcomes.push(outcome);
+    }
+
+    Ok(selector)
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Output {
-    Text,
-    Json,
+/// Add the options controlling which diagnostics are errors to `opts`
+fn policy_options(opts: &mut getopts::Options) {
+    opts.optmulti("A", "allow", "suppress diagnostics with CODE", "CODE");
+    opts.optmulti(
+        "W",
+        "warn",
+        "report diagnostics with CODE as warnings",
+        "CODE",
+    );
+    opts.optmulti(
+        "D",
+        "deny",
+        "report diagnostics with CODE as errors",
+        "CODE",
+    );
 }
 
-fn main() -> Result<()> {
+/// The policy described by the options added by `policy_options`, later
+/// options overriding earlier ones
+fn policy(matches: &getopts::Matches) -> Result<Policy> {
+    let mut policy = Policy::default();
+    let mut settings = [("A", Level::Allow), ("W", Level::Warn), ("D", Level::Deny)]
+        .into_iter()
+        .flat_map(|(opt, level)| {
+            matches
+                .opt_strs_pos(opt)
+                .into_iter()
+                .map(move |(pos, code)| (pos, code, level))
+        })
+        .collect::<Vec<_>>();
+    settings.sort_by_key(|(pos, _, _)| *pos);
+
+    for (_, code, level) in settings {
+        policy.set(&code, level)?;
+    }
+
+    Ok(policy)
+}
+
+/// Total what we find in each object
+fn summary_main(args: &[String]) -> Result<ExitCode> {
     let mut opts = getopts::Options::new();
     opts.optflag("j", "json", "json output");
-    let matches = opts.parse(env::args().skip(1))?;
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    policy_options(&mut opts);
+    selector_options(&mut opts);
+    ctf_parent_option(&mut opts);
+    let matches = opts.parse(args)?;
+    let parent = ctf_parent(&matches)?;
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+    let policy = policy(&matches)?;
+    let selector = selector(&matches)?;
+
+    let mut stats = Stats::default();
+    let mut errors = false;
+    scan_paths(
+        &matches.free,
+        cache_dir.as_deref(),
+        &selector,
+        &policy,
+        parent.as_ref(),
+        None,
+        |path, report| {
+            errors |= report.has_errors();
+            stats.add(path, &report);
+            Ok(())
+        },
+    )?;
+
+    if matches.opt_present("j") {
+        format::json::stats(&stats, &mut io::stdout())?;
+    } else {
+        format::text::stats(&stats, &mut io::stdout())?;
+    }
+
+    Ok(if errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Scan each object, reporting what we find
+fn scan_main(args: &[String]) -> Result<ExitCode> {
+    let mut opts = getopts::Options::new();
+    opts.optflag("j", "json", "json output");
+    opts.optflag("", "sarif", "SARIF output of diagnostics");
+    opts.optflag(
+        "a",
+        "all",
+        "also describe what became of functions which did not save arguments",
+    );
+    opts.optopt("c", "cache", "cache results in DIR", "DIR");
+    opts.optopt(
+        "b",
+        "baseline",
+        "do not report findings known in FILE",
+        "FILE",
+    );
+    opts.optopt("", "write-baseline", "record all findings in FILE", "FILE");
+    opts.optopt(
+        "",
+        "baseline-root",
+        "remove DIR from the paths of objects in baselines",
+        "DIR",
+    );
+    opts.optopt(
+        "",
+        "min-coverage",
+        "report functions whose saved arguments are valid in less than PCT% of them",
+        "PCT",
+    );
+    policy_options(&mut opts);
+    selector_options(&mut opts);
+    ctf_parent_option(&mut opts);
+    let matches = opts.parse(args)?;
+    let parent = ctf_parent(&matches)?;
+    let all = matches.opt_present("a");
+    let policy = policy(&matches)?;
+    let selector = selector(&matches)?;
+    let mut baseline = match matches.opt_str("b") {
+        Some(x) => Some(Baseline::load(Path::new(&x))?),
+        None => None,
+    };
+    let mut known = Baseline::default();
+    let root = matches.opt_str("baseline-root");
     let output = if matches.opt_present("j") {
         Output::Json
+    } else if matches.opt_present("sarif") {
+        Output::Sarif
     } else {
         Output::Text
     };
+    let cache_dir = matches.opt_str("c").map(PathBuf::from);
+    let min_coverage = match matches.opt_str("min-coverage") {
+        Some(x) => Some(
+            x.parse::<f64>()
+                .with_context(|| format!("invalid coverage: {x}"))?,
+        ),
+        None => None,
+    };
 
-    for path in matches.free {
-        let file = fs::File::open(&path).with_context(|| format!("Opening {path}"))?;
-        let mmap = unsafe { memmap2::Mmap::map(&file).with_context(|| format!("Mapping {path}"))? };
-        let object = object::File::parse(&*mmap).with_context(|| format!("Parsing {path}"))?;
-        let endian = if object.is_little_endian() {
-            gimli::RunTimeEndian::Little
-        } else {
-            gimli::RunTimeEndian::Big
-        };
-
-        if object.section_by_name(".SUNW_ctf").is_none() {
-            continue; // No CTF, no saved arguments
-        }
-
-        match object.kind() {
-            ObjectKind::Executable | ObjectKind::Dynamic | ObjectKind::Relocatable => {
-                if let Err(x) = dump_file(&path, &object, endian, output) {
-                    eprintln!("{path}: WARNING: failed to examine: {x:?}");
+    let mut errors = false;
+    let mut reports = Vec::new();
+    scan_paths(
+        &matches.free,
+        cache_dir.as_deref(),
+        &selector,
+        &policy,
+        parent.as_ref(),
+        min_coverage,
+        |path, mut report| {
+            let object = relative_path(path, root.as_deref(), false);
+            known.add(&object, &report);
+            if let Some(baseline) = &mut baseline {
+                baseline.suppress(&object, &mut report);
+            }
+            errors |= report.has_errors();
+
+            match output {
+                // SARIF describes every object at once
+                Output::Sarif => reports.push((path.to_string(), report)),
+                Output::Json => format::json::report(path, &report, &mut io::stdout())?,
+                Output::Text => {
+                    format::text::functions(path, &report, &mut io::stdout())?;
+                    if all {
+                        format::text::outcomes(path, &report, &mut io::stdout())?;
+                    }
+                    format::text::diagnostics(path, &report, &mut io::stderr())?;
                 }
             }
-            _ => eprintln!(
-                "{path}: WARNING: only executable, relocatable and dynamic objects and supported"
-            ),
+            Ok(())
+        },
+    )?;
+
+    if let Output::Sarif = output {
+        format::sarif::log(&reports, &mut io::stdout())?;
+    }
+
+    if let Some(baseline) = &baseline {
+        for (entry, n) in baseline.fixed() {
+            let function = entry.function.as_deref().unwrap_or("<unknown>");
+            let times = if n > 1 {
+                format!(" {n} times")
+            } else {
+                String::new()
+            };
+            eprintln!(
+                "{}: NOTE: {function}() [{}] is in the baseline{times} but no longer found",
+                entry.object, entry.code
+            );
         }
     }
 
-    Ok(())
+    if let Some(path) = matches.opt_str("write-baseline") {
+        known.store(Path::new(&path))?;
+    }
+
+    Ok(if errors {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn main() -> Result<ExitCode> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    match args.first().map(String::as_str) {
+        Some("ctf") => ctf_main(&args[1..]),
+        Some("diff") => diff_main(&args[1..]),
+        Some("summary") => summary_main(&args[1..]),
+        Some("check") => check_main(&args[1..]),
+        Some("explain") => explain_main(&args[1..]),
+        Some("index") => index_main(&args[1..]),
+        Some("locations") => locations_main(&args[1..]),
+        Some("lookup") => lookup_main(&args[1..]),
+        Some("prologue") => prologue_main(&args[1..]),
+        Some("protocols") => protocols_main(&args[1..]),
+        Some("saveargs") => saveargs_main(&args[1..]),
+        _ => scan_main(&args),
+    }
 }
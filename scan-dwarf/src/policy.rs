@@ -0,0 +1,81 @@
+//! Which diagnostics matter, and how much
+//!
+//! By default each diagnostic has the severity of its kind.  A [`Policy`]
+//! may instead promote diagnostics to errors, or suppress them altogether,
+//! by code.  The special code `warnings` refers to every diagnostic which
+//! would otherwise be a warning.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::{DiagnosticKind, ScanReport, Severity};
+
+/// What to do with a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Level {
+    /// Suppress it
+    Allow,
+    /// Report it as a warning
+    Warn,
+    /// Report it as an error
+    Deny,
+}
+
+/// The level of each diagnostic code, where it differs from the default
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    levels: BTreeMap<String, Level>,
+}
+
+impl Policy {
+    /// Treat diagnostics with `code` as `level`
+    ///
+    /// Later settings for the same code replace earlier ones.  Failure to
+    /// examine an object may be denied, but not allowed, lest incomplete
+    /// results pass unnoticed.
+    pub fn set(&mut self, code: &str, level: Level) -> Result<()> {
+        if code != "warnings" && !DiagnosticKind::CODES.contains(&code) {
+            return Err(anyhow!("unknown diagnostic code: {code}"));
+        }
+
+        if code == "failed" && level != Level::Deny {
+            return Err(anyhow!("failures to examine an object cannot be allowed"));
+        }
+
+        self.levels.insert(code.to_string(), level);
+        Ok(())
+    }
+
+    /// The level at which to report diagnostics of `kind`
+    pub fn level(&self, kind: &DiagnosticKind) -> Level {
+        self.level_of(kind.code(), kind.default_severity())
+    }
+
+    /// The level at which to report diagnostics with `code`, which would
+    /// otherwise have severity `default`
+    pub fn level_of(&self, code: &str, default: Severity) -> Level {
+        if let Some(level) = self.levels.get(code) {
+            return *level;
+        }
+
+        match default {
+            Severity::Error => Level::Deny,
+            Severity::Warning => self.levels.get("warnings").copied().unwrap_or(Level::Warn),
+        }
+    }
+
+    /// Remove the diagnostics in `report` which are allowed, and give the
+    /// rest their severity
+    pub fn apply(&self, report: &mut ScanReport) {
+        report.diagnostics.retain_mut(|diag| {
+            match self.level(&diag.kind) {
+                Level::Allow => return false,
+                Level::Warn => diag.severity = Severity::Warning,
+                Level::Deny => diag.severity = Severity::Error,
+            }
+            true
+        });
+    }
+}
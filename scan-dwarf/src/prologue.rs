@@ -0,0 +1,363 @@
+//! Where a function saves its arguments, as far as its code alone says
+//!
+//! Most objects have no DWARF, but their symbol tables still say where each
+//! function is.  [`infer`] follows the code of each from its entry to its
+//! first call, return, or unconditional jump, looking for its integer
+//! arguments being stored each below the last, from the first down, as the
+//! plugin saves them.  On amd64 a run may instead begin with the second
+//! argument register, as the first holds the address of any structure
+//! returned in memory, which is not saved.  From the run reaching the last
+//! argument we infer the offset of the saved arguments from the frame
+//! register, and how many there are.  Of runs reaching the same argument we
+//! take the one completed last, as the compiler may copy the arguments to a
+//! temporary first and initialise the array from that, and then the
+//! longest.
+//!
+//! Where there is DWARF, [`compare`] measures what we inferred against what
+//! it says.
+
+use anyhow::Result;
+use object::{Object, ObjectSection, ObjectSymbol, SymbolKind};
+
+use crate::code::{Arch, Decoder, Insn, Op, State, Value};
+use crate::range::frame_register;
+use crate::scan::SLOT_SIZE;
+use crate::{CodeRange, ScanReport, Status};
+
+/// Where the saved arguments are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Inference {
+    /// The offset of the lowest slot from the frame register
+    pub offset: i64,
+    /// The number of slots
+    pub slots: usize,
+}
+
+/// How what we inferred of a function compares with what its DWARF says
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Agreement {
+    /// The DWARF says the same
+    Agrees,
+    /// The DWARF says the arguments are saved, but not as we inferred
+    Disagrees(Inference),
+    /// The DWARF says the arguments are saved, and we found nothing
+    Missed(Inference),
+    /// The DWARF says the arguments are not saved, but we found something
+    Spurious,
+    /// Neither found saved arguments
+    Neither,
+}
+
+/// A function of the symbol table
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Function {
+    pub name: String,
+    /// Extent of the function
+    pub range: CodeRange,
+    /// Where its code seems to save its arguments
+    pub inferred: Option<Inference>,
+    /// How that compares with the DWARF, if it describes the function
+    pub agreement: Option<Agreement>,
+}
+
+/// Where the code of `insns` saves its arguments relative to `frame`, if it
+/// seems to
+fn infer_code(arch: Arch, frame: gimli::Register, insns: &[Insn]) -> Option<Inference> {
+    let mut state = State::entry(arch);
+
+    // Each store of an argument into our own frame: where from the frame
+    // register, which, and by which instruction
+    let mut stores = Vec::new();
+    for (i, insn) in insns.iter().enumerate() {
+        for store in state.step(insn) {
+            if let (Some(address), Some(8), Value::Arg { index, .. }, Some(frame)) = (
+                store.address,
+                store.size,
+                store.value,
+                state.register(frame).stack(),
+            ) {
+                if address < 0 {
+                    stores.push((address - frame, index, i));
+                }
+            }
+        }
+
+        if insn.ops.iter().any(|x| {
+            matches!(
+                x,
+                Op::Call
+                    | Op::Return
+                    | Op::Jump {
+                        conditional: false,
+                        ..
+                    }
+            )
+        }) {
+            break;
+        }
+    }
+
+    // Each store of the first argument, or on amd64 the second, may begin a
+    // run: (the argument after the last in it, the instruction completing
+    // it, slots, the offset of the lowest slot)
+    let firsts = if arch == Arch::X86_64 { 0..2 } else { 0..1 };
+    let mut best: Option<(usize, usize, usize, i64)> = None;
+    for &(address, first, at) in stores.iter().filter(|x| firsts.contains(&x.1)) {
+        let (mut slots, mut completed) = (1, at);
+        while let Some(&(_, _, at)) = stores
+            .iter()
+            .rev()
+            .find(|x| x.0 == address - slots as i64 * SLOT_SIZE && x.1 == first + slots)
+        {
+            completed = completed.max(at);
+            slots += 1;
+        }
+
+        let offset = address - (slots as i64 - 1) * SLOT_SIZE;
+        let run = (first + slots, completed, slots, offset);
+        if best.is_none_or(|x| (run.0, run.1, run.2) > (x.0, x.1, x.2)) {
+            best = Some(run);
+        }
+    }
+
+    best.map(|(_, _, slots, offset)| Inference { offset, slots })
+}
+
+/// Each function in the symbol table of `object`, and where its code seems
+/// to save its arguments
+pub fn infer(object: &object::File) -> Result<Vec<Function>> {
+    let arch = Arch::of(object)?;
+    let decoder = Decoder::new(arch)?;
+    let frame = frame_register(object)?;
+
+    // A stripped object still has the dynamic symbols, and of several names
+    // for a function, we prefer one which is global
+    let symbols = match object.symbol_table() {
+        Some(_) => object.symbols(),
+        None => object.dynamic_symbols(),
+    };
+    let mut symbols = symbols
+        .filter(|x| x.kind() == SymbolKind::Text && x.is_definition() && x.size() > 0)
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|x| (x.section_index().map(|x| x.0), x.address(), !x.is_global()));
+    symbols.dedup_by_key(|x| (x.section_index(), x.address()));
+
+    let mut ret = Vec::new();
+    for symbol in symbols {
+        let Some(section) = symbol
+            .section_index()
+            .and_then(|x| object.section_by_index(x).ok())
+        else {
+            continue;
+        };
+
+        let range = CodeRange {
+            start: symbol.address(),
+            end: symbol.address() + symbol.size(),
+        };
+        let data = section.data()?;
+        let start = (range.start - section.address()) as usize;
+        let Some(code) = data.get(start..start + symbol.size() as usize) else {
+            continue;
+        };
+
+        let insns = decoder.decode(code, range.start)?;
+        ret.push(Function {
+            name: symbol.name()?.to_string(),
+            range,
+            inferred: infer_code(arch, frame, &insns),
+            agreement: None,
+        });
+    }
+
+    Ok(ret)
+}
+
+/// Compare what we inferred of each function with what `report` says of the
+/// function starting at the same address, preferring one of the same name
+pub fn compare(functions: &mut [Function], report: &ScanReport) {
+    for func in functions {
+        let mut records = report
+            .functions
+            .iter()
+            .filter(|x| x.range.start == func.range.start);
+        let Some(record) = records
+            .clone()
+            .find(|x| x.name == func.name)
+            .or_else(|| records.next())
+        else {
+            continue;
+        };
+
+        let described = match record.status {
+            Status::Saved { offset } => Some(Inference {
+                offset,
+                slots: record.nparams(),
+            }),
+            Status::Missing => None,
+        };
+
+        func.agreement = Some(match (func.inferred, described) {
+            (Some(x), Some(y)) if x == y => Agreement::Agrees,
+            (Some(_), Some(y)) => Agreement::Disagrees(y),
+            (None, Some(y)) => Agreement::Missed(y),
+            (Some(_), None) => Agreement::Spurious,
+            (None, None) => Agreement::Neither,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::function;
+
+    const RBP: gimli::Register = gimli::Register(6);
+
+    /// `pushq %rbp; movq %rsp,%rbp; subq $0x30,%rsp`
+    const PROLOGUE: [u8; 8] = [0x55, 0x48, 0x89, 0xe5, 0x48, 0x83, 0xec, 0x30];
+
+    fn infer_x86(code: &[&[u8]]) -> Option<Inference> {
+        let code = [&PROLOGUE[..]]
+            .iter()
+            .chain(code)
+            .copied()
+            .collect::<Vec<_>>();
+        let insns = Decoder::new(Arch::X86_64)
+            .unwrap()
+            .decode(&code.concat(), 0x1000)
+            .unwrap();
+        infer_code(Arch::X86_64, RBP, &insns)
+    }
+
+    #[test]
+    fn stored_in_order() {
+        assert_eq!(
+            infer_x86(&[
+                &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+                &[0x48, 0x89, 0x75, 0xf0], // movq %rsi,-0x10(%rbp)
+                &[0x48, 0x89, 0x55, 0xe8], // movq %rdx,-0x18(%rbp)
+                &[0xe8, 0, 0, 0, 0],       // callq
+            ]),
+            Some(Inference {
+                offset: -0x18,
+                slots: 3,
+            })
+        );
+    }
+
+    #[test]
+    fn struct_return() {
+        // %rdi holds the address of the structure, and is kept apart
+        assert_eq!(
+            infer_x86(&[
+                &[0x48, 0x89, 0x7d, 0xd8], // movq %rdi,-0x28(%rbp)
+                &[0x48, 0x89, 0x75, 0xf8], // movq %rsi,-0x8(%rbp)
+                &[0x48, 0x89, 0x55, 0xf0], // movq %rdx,-0x10(%rbp)
+                &[0xc3],                   // retq
+            ]),
+            Some(Inference {
+                offset: -0x10,
+                slots: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn copied_first() {
+        // Spilled to temporaries, and only then into the array
+        assert_eq!(
+            infer_x86(&[
+                &[0x48, 0x89, 0x7d, 0xd8], // movq %rdi,-0x28(%rbp)
+                &[0x48, 0x89, 0x75, 0xd0], // movq %rsi,-0x30(%rbp)
+                &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+                &[0x48, 0x89, 0x75, 0xf0], // movq %rsi,-0x10(%rbp)
+                &[0xc3],                   // retq
+            ]),
+            Some(Inference {
+                offset: -0x10,
+                slots: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn nothing_before_call() {
+        assert_eq!(
+            infer_x86(&[
+                &[0xe8, 0, 0, 0, 0],       // callq
+                &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn aarch64() {
+        let code = [
+            0xfd, 0x7b, 0xbe, 0xa9, // stp x29, x30, [sp, #-32]!
+            0xfd, 0x03, 0x00, 0x91, // mov x29, sp
+            0xe0, 0x0f, 0x00, 0xf9, // str x0, [sp, #24]
+            0xe1, 0x0b, 0x00, 0xf9, // str x1, [sp, #16]
+            0x00, 0x00, 0x00, 0x94, // bl
+        ];
+        let insns = Decoder::new(Arch::Aarch64)
+            .unwrap()
+            .decode(&code, 0x1000)
+            .unwrap();
+
+        assert_eq!(
+            infer_code(Arch::Aarch64, gimli::Register(31), &insns),
+            Some(Inference {
+                offset: 16,
+                slots: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn agreement() {
+        let inferred = |name: &str, start, inferred| Function {
+            name: name.to_string(),
+            range: CodeRange {
+                start,
+                end: start + 0x40,
+            },
+            inferred,
+            agreement: None,
+        };
+        let at = |offset| Some(Inference { offset, slots: 3 });
+        let mut functions = [
+            inferred("one", 0x1000, at(-40)),
+            inferred("two", 0x1100, at(-48)),
+            inferred("three", 0x1200, None),
+            inferred("four", 0x1300, at(-40)),
+            inferred("five", 0x1400, None),
+            inferred("six", 0x1500, None),
+        ];
+        let report = ScanReport {
+            functions: vec![
+                function("one", 0x1000, 0x1040, Status::Saved { offset: -40 }),
+                function("two", 0x1100, 0x1140, Status::Saved { offset: -40 }),
+                function("three", 0x1200, 0x1240, Status::Saved { offset: -40 }),
+                function("four", 0x1300, 0x1340, Status::Missing),
+                function("five", 0x1400, 0x1440, Status::Missing),
+            ],
+            ..Default::default()
+        };
+        compare(&mut functions, &report);
+
+        assert_eq!(
+            functions.map(|x| x.agreement),
+            [
+                Some(Agreement::Agrees),
+                Some(Agreement::Disagrees(at(-40).unwrap())),
+                Some(Agreement::Missed(at(-40).unwrap())),
+                Some(Agreement::Spurious),
+                Some(Agreement::Neither),
+                None,
+            ]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! The ways in which a debugger may recover the arguments of a function
+//!
+//! Before this plugin there was GCC's `-msave-args`, by which each function
+//! pushed or stored its integer argument registers just below its frame
+//! pointer in its prologue, and which debuggers found with libsaveargs by
+//! looking for exactly those instructions.  That is version 0 of saving
+//! arguments, and the array variable of this plugin, which the DWARF
+//! describes, version 1, and any later version will be named for its own.
+//! A function saves its arguments by the version in the name of its
+//! variable or, having none, by version 0 if it was compiled with
+//! `-msave-args` or its prologue shows it.  [`libsaveargs`] looks at the
+//! code of a function as libsaveargs would on amd64, so that what each
+//! protocol would recover may be compared.
+
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+
+use crate::code::{disassembler, Arch};
+
+/// A way in which arguments are saved, by its version
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct Protocol(pub u32);
+
+impl Protocol {
+    /// GCC's `-msave-args`, which libsaveargs recognises
+    pub const V0: Protocol = Protocol(0);
+    /// The array variable, described by the DWARF
+    pub const V1: Protocol = Protocol(1);
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// How much of the start of a function is looked at
+const PROLOGUE_LENGTH: usize = 256;
+
+/// `pushq %rbp`, or a breakpoint in its place
+const SAVE_FP_PUSHES: [&[u8]; 2] = [&[0x55], &[0xcc]];
+
+/// `movq %rsp,%rbp`, as either way it may be encoded
+const SAVE_FP_MOVS: [&[u8]; 2] = [&[0x48, 0x89, 0xe5], &[0x48, 0x8b, 0xec]];
+
+/// `movq` of each argument register to 8 bytes below the last, from
+/// `-0x8(%rbp)` down, as Studio saved every argument register
+const SAVE_MOVS: [&[u8]; 6] = [
+    &[0x48, 0x89, 0x7d, 0xf8],
+    &[0x48, 0x89, 0x75, 0xf0],
+    &[0x48, 0x89, 0x55, 0xe8],
+    &[0x48, 0x89, 0x4d, 0xe0],
+    &[0x4c, 0x89, 0x45, 0xd8],
+    &[0x4c, 0x89, 0x4d, 0xd0],
+];
+
+/// `pushq` of each argument register, as GCC saved them
+const SAVE_PUSHES: [&[u8]; 6] = [
+    &[0x57],
+    &[0x56],
+    &[0x52],
+    &[0x51],
+    &[0x41, 0x50],
+    &[0x41, 0x51],
+];
+
+/// `movq` of each argument register after `%rdi` from `-0x8(%rbp)` down,
+/// as GCC saved them where `%rdi` holds the address of the structure
+/// returned
+const SAVE_MOVS_STRUCT: [&[u8]; 5] = [
+    &[0x48, 0x89, 0x75, 0xf8],
+    &[0x48, 0x89, 0x55, 0xf0],
+    &[0x48, 0x89, 0x4d, 0xe8],
+    &[0x4c, 0x89, 0x45, 0xe0],
+    &[0x4c, 0x89, 0x4d, 0xd8],
+];
+
+/// True if `insns` push the frame pointer, and then set it
+fn saves_fp(insns: &[&[u8]]) -> bool {
+    let Some(push) = insns.iter().position(|x| SAVE_FP_PUSHES.contains(x)) else {
+        return false;
+    };
+    insns[push + 1..].iter().any(|x| SAVE_FP_MOVS.contains(x))
+}
+
+/// True if `insns` include every one of `patterns`, in whatever order
+fn saves(insns: &[&[u8]], patterns: &[&[u8]]) -> bool {
+    !patterns.is_empty() && patterns.iter().all(|x| insns.contains(x))
+}
+
+/// True if libsaveargs would find the `nargs` integer arguments of a
+/// function saved by the code at its start, `code`, where if
+/// `struct_return` the address of the structure it returns is passed before
+/// them
+///
+/// As libsaveargs, we look for at most the six passed in registers, in
+/// instructions encoded exactly as the compilers which saved them encoded
+/// them, and in nothing else.
+pub(crate) fn libsaveargs(code: &[u8], nargs: usize, struct_return: bool) -> Result<bool> {
+    let cs = disassembler(Arch::X86_64)?;
+    let code = &code[..code.len().min(PROLOGUE_LENGTH)];
+    let decoded = cs.disasm_all(code, 0).map_err(|x| anyhow!("{x}"))?;
+    let insns = decoded.iter().map(|x| x.bytes()).collect::<Vec<_>>();
+
+    if !saves_fp(&insns) {
+        return Ok(false);
+    }
+
+    let start = usize::from(struct_return);
+    let argc = (start + nargs).min(SAVE_MOVS.len());
+
+    Ok(saves(&insns, &SAVE_MOVS[..argc])
+        || saves(&insns, &SAVE_PUSHES[start..argc])
+        || (struct_return && saves(&insns, &SAVE_MOVS_STRUCT[..argc - start])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `pushq %rbp; movq %rsp,%rbp`
+    const SAVE_FP: [u8; 4] = [0x55, 0x48, 0x89, 0xe5];
+
+    fn code(parts: &[&[u8]]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    #[test]
+    fn pushes() {
+        // As GCC's -msave-args, with a frame
+        let gcc = code(&[&SAVE_FP, &[0x57, 0x56, 0x52], &[0x48, 0x83, 0xec, 0x10]]);
+
+        assert!(libsaveargs(&gcc, 3, false).unwrap());
+        assert!(libsaveargs(&gcc, 2, false).unwrap());
+        assert!(!libsaveargs(&gcc, 4, false).unwrap());
+        assert!(!libsaveargs(&gcc, 0, false).unwrap());
+    }
+
+    #[test]
+    fn movs() {
+        // As Studio saved them, in whatever order
+        let studio = code(&[
+            &SAVE_FP,
+            &[0x48, 0x89, 0x75, 0xf0], // movq %rsi,-0x10(%rbp)
+            &[0x48, 0x89, 0x7d, 0xf8], // movq %rdi,-0x8(%rbp)
+        ]);
+        assert!(libsaveargs(&studio, 2, false).unwrap());
+        assert!(!libsaveargs(&studio, 3, false).unwrap());
+
+        // Every register is saved however many arguments there are
+        let all = code(&[&SAVE_FP, &SAVE_MOVS.concat()]);
+        assert!(libsaveargs(&all, 8, false).unwrap());
+    }
+
+    #[test]
+    fn frame() {
+        let pushes = [0x57, 0x56];
+
+        // movq %rsp,%rbp encoded the other way, and a breakpoint on the push
+        assert!(libsaveargs(&code(&[&[0x55, 0x48, 0x8b, 0xec], &pushes]), 2, false).unwrap());
+        assert!(libsaveargs(&code(&[&[0xcc, 0x48, 0x89, 0xe5], &pushes]), 2, false).unwrap());
+
+        // No frame, or the frame pointer set before it is pushed
+        assert!(!libsaveargs(&pushes, 2, false).unwrap());
+        assert!(!libsaveargs(&code(&[&[0x48, 0x89, 0xe5, 0x55], &pushes]), 2, false).unwrap());
+    }
+
+    #[test]
+    fn struct_return() {
+        let movs = code(&[&SAVE_FP, &SAVE_MOVS_STRUCT[..2].concat()]);
+        assert!(libsaveargs(&movs, 2, true).unwrap());
+        assert!(!libsaveargs(&movs, 2, false).unwrap());
+
+        // %rdi is not pushed, only what follows
+        let pushes = code(&[&SAVE_FP, &[0x56, 0x52]]);
+        assert!(libsaveargs(&pushes, 2, true).unwrap());
+        assert!(!libsaveargs(&pushes, 2, false).unwrap());
+
+        // Studio saves %rdi with the rest
+        let studio = code(&[&SAVE_FP, &SAVE_MOVS[..3].concat()]);
+        assert!(libsaveargs(&studio, 2, true).unwrap());
+    }
+
+    #[test]
+    fn prologue_only() {
+        let late = code(&[&SAVE_FP, &[0x90; PROLOGUE_LENGTH], &[0x57]]);
+        assert!(!libsaveargs(&late, 1, false).unwrap());
+    }
+}
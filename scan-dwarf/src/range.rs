@@ -0,0 +1,390 @@
+use std::fmt;
+
+use anyhow::{anyhow, Context, Result};
+use fallible_iterator::FallibleIterator;
+use itertools::Itertools;
+use object::Object;
+
+/// A range of addresses, either absolute or relative to the start of the
+/// function containing them
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CodeRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl CodeRange {
+    /// The extent of `entry` as a CodeRange
+    pub(crate) fn from_function_die<T: gimli::Reader>(
+        entry: &gimli::DebuggingInformationEntry<T>,
+    ) -> Result<Option<Self>> {
+        assert!(entry.tag() == gimli::DW_TAG_subprogram);
+
+        let low = match entry.attr_value(gimli::DW_AT_low_pc)? {
+            Some(gimli::AttributeValue::Addr(x)) => x,
+            Some(y) => return Err(anyhow!("unknown kind of low_pc: {y:?}")),
+            None => return Ok(None),
+        };
+
+        let high = match entry.attr_value(gimli::DW_AT_high_pc)? {
+            Some(gimli::AttributeValue::Addr(x)) => x,
+            Some(y) => return Err(anyhow!("unknown kind of high_pc: {y:?}")),
+            None => return Ok(None),
+        };
+
+        Ok(Some(CodeRange {
+            start: low,
+            end: high,
+        }))
+    }
+}
+
+/// The parts of `ranges` outside every one of `holes`, both being ordered and
+/// disjoint
+pub(crate) fn without(ranges: &[CodeRange], holes: &[CodeRange]) -> Vec<CodeRange> {
+    let mut ret = Vec::with_capacity(ranges.len());
+
+    for x in ranges {
+        let mut start = x.start;
+        for hole in holes.iter().filter(|h| h.start < x.end && x.start < h.end) {
+            if start < hole.start {
+                ret.push(CodeRange {
+                    start,
+                    end: hole.start,
+                });
+            }
+            start = start.max(hole.end);
+        }
+        if start < x.end {
+            ret.push(CodeRange { start, end: x.end });
+        }
+    }
+
+    ret
+}
+
+/// A simple description of an offset from a base register, as used for local
+/// variables
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BaseOffset {
+    /// Where this is valid, relative to the start of the function
+    pub valid: Vec<CodeRange>,
+    pub offset: i64,
+    #[serde(with = "RegisterDef")]
+    pub register: gimli::Register,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(remote = "gimli::Register")]
+struct RegisterDef(u16);
+
+impl BaseOffset {
+    /// Given a frame_base attribute, return a suitable BaseOffset
+    pub(crate) fn from_frame_base<T: gimli::Reader>(
+        frame_base: gimli::AttributeValue<T>,
+        range: &CodeRange, // Extent of the function with this attribute
+        object: &object::File,
+        dwarf: &gimli::Dwarf<T>,
+        unit: &gimli::Unit<T>,
+    ) -> Result<Option<BaseOffset>> {
+        let function_start_pc = range.start;
+
+        let mut bases = match frame_base {
+            gimli::AttributeValue::LocationListsRef(ll) => {
+                let list = loclist_as_offsets(ll, dwarf, unit)?;
+                let pointer_reg = frame_register(object)?;
+
+                // This is the value of the assignment, remember that
+                BaseOffset::from_merged_base_offsets(pointer_reg, object, list)
+                    .context("merging frame-bases")?
+            }
+            gimli::AttributeValue::Exprloc(x) => {
+                // We see these, but is exceptionally unlikely to see one that
+                // also has saved arguments, because this implies the base is
+                // constant (and thus if it's the frame pointer, it's the
+                // _callers_).
+                Some(BaseOffset::from_exprloc(x, range, unit)?)
+            }
+            x => return Err(anyhow!("unexpected frame-bases: {x:?}")),
+        };
+
+        if let Some(x) = bases.as_mut() {
+            x.relativize(function_start_pc);
+        }
+
+        Ok(bases)
+    }
+
+    /// given a DWARF location expression turn it into a BaseOffset
+    pub(crate) fn from_exprloc<T: gimli::Reader>(
+        expr: gimli::Expression<T>,
+        range: &CodeRange,
+        unit: &gimli::Unit<T>,
+    ) -> Result<Self> {
+        let mut ops = expr.operations(unit.encoding());
+        let mut bo: Option<BaseOffset> = None;
+
+        if let Some(op) = ops.next()? {
+            match op {
+                gimli::Operation::RegisterOffset {
+                    register,
+                    offset,
+                    base_type: _,
+                } => {
+                    bo = Some(BaseOffset {
+                        valid: vec![range.clone()],
+                        offset,
+                        register,
+                    })
+                }
+                x => return Err(anyhow!("unexpected frame_base calculation: {x:?}")),
+            }
+        }
+
+        if ops.count()? != 0 {
+            return Err(anyhow!("base expression has multiple location operations"));
+        }
+
+        Ok(bo.unwrap())
+    }
+
+    /// If there exists a base offset that is relative to the frame register, or
+    /// there exist multiple each with the same offset, that is our offset.
+    /// Return a merged entry reflecting all valid pcs.
+    ///
+    /// XXX: As a sad quirk, on AArch64, if the register is the stack pointer,
+    /// ignore 0 offsets.  This is because GCC seems unwilling to _say_ the
+    /// frame base is at the frame pointer, on ARM.  So we get entries at SP+0
+    /// (which are valid frame bases, but not for us), and at SP+<something>,
+    /// which iff equal(something) is ours.
+    ///
+    /// XXX: We could perhaps figure this out better, but I'm not sure how we
+    /// could do it realistically.
+    ///
+    /// The possibility of multiples come from code generation such as:
+    ///   [ prologue ]
+    ///   [ work work work ]
+    ///   [ epilogue ] <------------.
+    ///   [ return ]                |
+    ///   [ prologue ]              |
+    ///   [ more work work work ]   |
+    ///   [ jump to epilogue ] -----'
+    ///
+    /// which we do see generated
+    fn from_merged_base_offsets(
+        reg: gimli::Register,
+        object: &object::File,
+        v: Vec<BaseOffset>,
+    ) -> Result<Option<BaseOffset>> {
+        let treatments = treat_base_offsets(reg, object, &v);
+        let mut merged: Option<BaseOffset> = None;
+        let mut bogons = Vec::new();
+
+        for (x, treatment) in v.into_iter().zip(treatments) {
+            match treatment {
+                Treatment::First => merged = Some(x),
+                Treatment::Merged => merged.as_mut().unwrap().valid.extend(x.valid),
+                Treatment::Conflicting => bogons.push(x),
+                Treatment::ZeroStackOffset | Treatment::OtherRegister => (),
+            }
+        }
+
+        if !bogons.is_empty() {
+            bogons.extend(merged);
+            Err(anyhow!("multiple frame-pointer offsets: {bogons:?}"))
+        } else {
+            Ok(merged)
+        }
+    }
+
+    /// Cause all the validity entries in a BaseOffset to be relative to to, in place
+    fn relativize(&mut self, to: u64) {
+        self.valid.iter_mut().for_each(|x| {
+            x.start -= to;
+            x.end -= to;
+        });
+    }
+
+    /// Take a BaseOffset and a range, and return a vec of CodeRange which covers
+    /// the parts of the original the BaseOffset misses.
+    ///
+    /// range is the more complete range to which the baseoffset is relative
+    pub fn invert(&self, range: &CodeRange) -> Vec<CodeRange> {
+        let mut ret: Vec<CodeRange> = Vec::with_capacity(self.valid.len());
+
+        if let Some(x) = self.valid.first() {
+            if 0 < x.start {
+                ret.push(CodeRange {
+                    start: 0,
+                    end: x.start - 1,
+                });
+            }
+        }
+
+        for (r1, r2) in self.valid.iter().tuple_windows() {
+            if r1.end < r2.start {
+                ret.push(CodeRange {
+                    start: r1.end,
+                    end: r2.start,
+                });
+            }
+        }
+
+        if let Some(x) = self.valid.last() {
+            if x.end + 1 < range.end - range.start {
+                ret.push(CodeRange {
+                    start: x.end + 1,
+                    end: range.end - range.start,
+                });
+            }
+        }
+
+        ret
+    }
+
+    /// The percentage of `range` in which this BaseOffset is valid
+    pub fn coverage(&self, range: &CodeRange) -> f64 {
+        self.coverage_from(range, 0)
+    }
+
+    /// The percentage of `range` in which this BaseOffset is valid, from
+    /// `from` on
+    pub fn coverage_from(&self, range: &CodeRange, from: u64) -> f64 {
+        self.valid
+            .iter()
+            .filter(|x| x.start >= from || x.end > from)
+            .fold(0, |acc, x| acc + (x.end - x.start.max(from)).max(1)) as f64
+            / (range.end - range.start) as f64
+            * 100.0
+    }
+}
+
+/// The register relative to which the saved arguments of functions in
+/// `object` are described
+pub(crate) fn frame_register(object: &object::File) -> Result<gimli::Register> {
+    match object.architecture() {
+        object::Architecture::X86_64 => Ok(gimli::Register(6)), // %rbp
+        object::Architecture::Aarch64 => Ok(gimli::Register(31)), // %sp
+        x => Err(anyhow!("unknown architecture {x:?}")),
+    }
+}
+
+/// The name of `register` on `arch`, or its number if we do not know it
+pub(crate) fn register_name(arch: object::Architecture, register: gimli::Register) -> String {
+    let name = match arch {
+        object::Architecture::X86_64 => gimli::X86_64::register_name(register),
+        object::Architecture::Aarch64 => gimli::AArch64::register_name(register),
+        _ => None,
+    };
+
+    match name {
+        Some(x) => format!("%{x}"),
+        None => format!("reg{}", register.0),
+    }
+}
+
+/// What [`BaseOffset::from_merged_base_offsets`] makes of each of a
+/// function's frame bases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Treatment {
+    /// Ignored, being at offset 0 from the stack pointer on AArch64
+    ZeroStackOffset,
+    /// Ignored, being relative to some other register
+    OtherRegister,
+    /// The first relative to the frame register, whose offset is ours
+    First,
+    /// Relative to the frame register at our offset, and so merged with the
+    /// first
+    Merged,
+    /// Relative to the frame register at some other offset, and so an error
+    Conflicting,
+}
+
+impl fmt::Display for Treatment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Treatment::ZeroStackOffset => "ignored, zero offset from the stack pointer",
+            Treatment::OtherRegister => "ignored, not relative to the frame register",
+            Treatment::First => "chosen, its offset is the frame base",
+            Treatment::Merged => "merged, at the same offset as that chosen",
+            Treatment::Conflicting => "conflicts, at a different offset to that chosen",
+        })
+    }
+}
+
+/// How each of `v`, relative to `reg`, is treated when merging
+pub(crate) fn treat_base_offsets(
+    reg: gimli::Register,
+    object: &object::File,
+    v: &[BaseOffset],
+) -> Vec<Treatment> {
+    let aarch64 = object.architecture() == object::Architecture::Aarch64;
+    let mut first = None;
+
+    v.iter()
+        .map(|x| {
+            if aarch64 && x.offset == 0 {
+                Treatment::ZeroStackOffset
+            } else if x.register != reg {
+                Treatment::OtherRegister
+            } else {
+                match first {
+                    None => {
+                        first = Some(x.offset);
+                        Treatment::First
+                    }
+                    Some(y) if y == x.offset => Treatment::Merged,
+                    Some(_) => Treatment::Conflicting,
+                }
+            }
+        })
+        .collect()
+}
+
+/// Given a DWARF location list, return each as BaseOffsets
+fn loclist_as_offsets<T: gimli::Reader>(
+    ll: gimli::LocationListsOffset<T::Offset>,
+    dwarf: &gimli::Dwarf<T>,
+    unit: &gimli::Unit<T>,
+) -> Result<Vec<BaseOffset>> {
+    let mut locs = dwarf.locations(unit, ll)?;
+    let mut vec: Vec<BaseOffset> = Vec::new();
+
+    while let Some(loc) = locs.next()? {
+        vec.push(BaseOffset::from_exprloc(
+            loc.data,
+            &CodeRange {
+                start: loc.range.begin,
+                end: loc.range.end,
+            },
+            unit,
+        )?);
+    }
+
+    Ok(vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(ranges: &[(u64, u64)]) -> Vec<CodeRange> {
+        ranges
+            .iter()
+            .map(|&(start, end)| CodeRange { start, end })
+            .collect()
+    }
+
+    #[test]
+    fn without_holes() {
+        let valid = ranges(&[(4, 0x20), (0x30, 0x40)]);
+
+        assert_eq!(without(&valid, &[]), valid);
+        assert_eq!(without(&valid, &valid), Vec::new());
+        assert_eq!(
+            without(&valid, &ranges(&[(0, 8), (0x10, 0x18), (0x1c, 0x34)])),
+            ranges(&[(8, 0x10), (0x18, 0x1c), (0x34, 0x40)])
+        );
+        assert_eq!(without(&valid, &ranges(&[(0x20, 0x30)])), valid);
+    }
+}
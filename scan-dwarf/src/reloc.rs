@@ -59,6 +59,7 @@ pub fn load_file_section<'input, 'arena, Endian: gimli::Endianity>(
 /// - allows readonly buffers, we don't need to implement writing of values back to buffers
 /// - potentially allows us to handle addresses and offsets differently
 /// - potentially allows us to add metadata from the relocation (eg symbol names)
+///
 /// Cons
 /// - maybe incomplete
 #[derive(Debug, Clone)]
@@ -171,17 +172,17 @@ impl<'a, R: gimli::Reader<Offset = usize>> gimli::Reader for Relocate<'a, R> {
     }
 
     #[inline]
-    fn to_slice(&self) -> gimli::Result<Cow<[u8]>> {
+    fn to_slice(&self) -> gimli::Result<Cow<'_, [u8]>> {
         self.reader.to_slice()
     }
 
     #[inline]
-    fn to_string(&self) -> gimli::Result<Cow<str>> {
+    fn to_string(&self) -> gimli::Result<Cow<'_, str>> {
         self.reader.to_string()
     }
 
     #[inline]
-    fn to_string_lossy(&self) -> gimli::Result<Cow<str>> {
+    fn to_string_lossy(&self) -> gimli::Result<Cow<'_, str>> {
         self.reader.to_string_lossy()
     }
 
@@ -0,0 +1,131 @@
+//! The `.SUNW_saveargs` section, which carries what we found into objects
+//! from which the DWARF will be stripped
+//!
+//! The section holds an [`index`](crate::index) of each function: its start
+//! address, where its saved arguments are valid, their offset from the frame
+//! register, and how many there are.  It is not allocated, and so survives
+//! stripping as `.SUNW_ctf` does.  In a relocatable object, addresses are
+//! relative to the section containing each function.
+
+use anyhow::{anyhow, Result};
+use object::{Object, ObjectSection};
+
+use crate::index::{self, IndexFile};
+use crate::lookup::Index;
+use crate::{elf, scan_object, ScanReport};
+
+/// The name of the section
+pub const SECTION_NAME: &str = ".SUNW_saveargs";
+
+/// A copy of the object in `data`, with a section describing each function
+/// in `report`
+pub fn write(data: &[u8], report: &ScanReport) -> Result<Vec<u8>> {
+    let object = object::File::parse(data)?;
+    let mut contents = Vec::new();
+
+    index::write(object.architecture(), &report.functions, &mut contents)?;
+    elf::add_section(data, SECTION_NAME, &contents)
+}
+
+/// The section of `object`, if it has one
+pub fn read<'data>(object: &object::File<'data>) -> Result<Option<IndexFile<'data>>> {
+    object
+        .section_by_name(SECTION_NAME)
+        .map(|x| IndexFile::parse(x.data()?))
+        .transpose()
+}
+
+/// Write the section into the object in `data`, strip the result of its
+/// debug information, and describe each way in which the section then
+/// disagrees with the DWARF
+///
+/// The section is checked both entry by entry, and by looking up the start
+/// and end of each function and of each range in which its arguments are
+/// valid.
+pub fn check(data: &[u8]) -> Result<Vec<String>> {
+    let object = object::File::parse(data)?;
+    let report = scan_object(&object);
+    if !report.is_complete() {
+        return Err(anyhow!("could not examine the whole object"));
+    }
+
+    let written = write(data, &report)?;
+    let stripped = elf::hide_sections(&written, |name| {
+        [
+            &b".debug_"[..],
+            b".zdebug_",
+            b".rela.debug_",
+            b".rel.debug_",
+        ]
+        .iter()
+        .any(|x| name.starts_with(x))
+    })?;
+    let stripped = object::File::parse(&*stripped)?;
+
+    let mut problems = Vec::new();
+    if !scan_object(&stripped).functions.is_empty() {
+        problems.push(String::from("debug information survived stripping"));
+    }
+
+    let Some(section) = read(&stripped)? else {
+        problems.push(format!("{SECTION_NAME} did not survive stripping"));
+        return Ok(problems);
+    };
+
+    problems.extend(index::verify(&section, &object)?);
+
+    let mut pcs = Vec::new();
+    for func in &report.functions {
+        pcs.extend([
+            func.range.start,
+            func.range.end.saturating_sub(1),
+            func.range.end,
+        ]);
+        for r in func.base.valid.iter().chain(&func.trusted()) {
+            pcs.extend([r.start, r.end.saturating_sub(1), r.end].map(|x| func.range.start + x));
+        }
+    }
+
+    let dwarf = Index::new(&object, report);
+    for pc in pcs {
+        let (expected, found) = (dwarf.lookup(pc), section.lookup(pc));
+        if expected != found {
+            problems.push(format!(
+                "{pc:#x}: section says {found:?}, DWARF says {expected:?}"
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::function;
+    use crate::Status;
+
+    #[test]
+    fn round_trip() {
+        let data = elf::tests::object(&[0xc3; 0x40]);
+        let report = ScanReport {
+            functions: vec![function("one", 0, 0x40, Status::Saved { offset: -40 })],
+            ..Default::default()
+        };
+        let written = write(&data, &report).unwrap();
+        let object = object::File::parse(&*written).unwrap();
+        let section = read(&object).unwrap().unwrap();
+
+        assert_eq!(section.len(), 1);
+        assert_eq!(section.function(0).valid, vec![(8, 0x20)]);
+        assert_eq!(
+            section.lookup(0x10),
+            Index::new(&object, report).lookup(0x10)
+        );
+
+        assert!(read(&object::File::parse(&*data).unwrap())
+            .unwrap()
+            .is_none());
+        assert!(write(&written, &ScanReport::default()).is_err());
+    }
+}
@@ -0,0 +1,563 @@
+//! How a function saves its arguments, as its code shows
+//!
+//! The DWARF says where the saved arguments are, and where the register they
+//! are relative to is known, but not when they are put there, nor what with.
+//! [`examine`] follows the code of a function along every path from its
+//! entry, to find at which of its instructions every slot has certainly been
+//! written, and whether what is in each slot where that begins is its
+//! argument as it was at entry.  Once stored, nothing should write to the
+//! slots again, and any store to the stack which might is reported, as what
+//! a debugger finds in them is then not to be trusted.  Stores through
+//! pointers we cannot follow are assumed not to be to the stack, and code we
+//! cannot follow to is taken not to have stored the slots.
+
+use anyhow::Result;
+
+use crate::code::{flow, function_code, Arch, Decoder, Extend, State, Value};
+use crate::scan::{DiagnosticKind, FunctionRecord, SLOT_SIZE};
+use crate::CodeRange;
+
+/// What the code of a function does with its saved arguments
+#[derive(Debug, Default)]
+pub(crate) struct Saving {
+    /// The parts of the function in which the frame base is valid, but not
+    /// every slot has been written on every path there
+    pub unstored: Vec<CodeRange>,
+    /// Each slot which does not then hold its argument as it was at entry,
+    /// by the DIE of its parameter, and the first instruction which may
+    /// write to the slots afterward, by the DIE of the function
+    pub problems: Vec<(u64, DiagnosticKind)>,
+}
+
+/// What is in the slot at `address` in `state`, with whatever was stored
+/// in only part of it made the low bits of an unknown value
+fn slot_value(state: &State, address: i64) -> Value {
+    for size in [8, 4, 2, 1] {
+        match state.load(address, size) {
+            Value::Unknown => continue,
+            Value::Arg { index, bits, .. } if bits > size * 8 => {
+                return Value::Arg {
+                    index,
+                    bits: size * 8,
+                    extend: None,
+                }
+            }
+            x => return x,
+        }
+    }
+    Value::Unknown
+}
+
+/// Follow the code of `func` from its entry, to see when and how it saves
+/// its arguments, which if `struct_return` follow the address of the
+/// structure it returns
+pub(crate) fn examine(
+    object: &object::File,
+    func: &FunctionRecord,
+    struct_return: bool,
+) -> Result<Saving> {
+    let slots = func.slots();
+    let Some(&(offset, _)) = slots.first() else {
+        return Ok(Saving::default());
+    };
+    let base = &func.base;
+    let Some(code) = function_code(object, &func.name, &func.range) else {
+        return Ok(Saving {
+            unstored: base.valid.clone(),
+            problems: Vec::new(),
+        });
+    };
+
+    let arch = Arch::of(object)?;
+    let insns = Decoder::new(arch)?.decode(code, func.range.start)?;
+    let states = flow(arch, &insns);
+    let size = slots.len() as i64 * SLOT_SIZE;
+
+    // Where the slots are at the start of each instruction at which the
+    // frame base is valid and every slot has been written
+    let stored = insns
+        .iter()
+        .zip(&states)
+        .map(|(insn, state)| {
+            let pc = insn.address - func.range.start;
+            let state = state.as_ref()?;
+            if !base.valid.iter().any(|x| x.start <= pc && pc < x.end) {
+                return None;
+            }
+
+            let start = state.register(base.register).stack()? + offset;
+            state.written(start, start + size).then_some(start)
+        })
+        .collect::<Vec<_>>();
+
+    let mut unstored: Vec<CodeRange> = Vec::new();
+    for (i, insn) in insns.iter().enumerate() {
+        if stored[i].is_some() {
+            continue;
+        }
+
+        let pc = insn.address - func.range.start;
+        let end = insns.get(i + 1).map_or(func.range.end, |x| x.address) - func.range.start;
+        for x in &base.valid {
+            let (start, end) = (pc.max(x.start), end.min(x.end));
+            if start >= end {
+                continue;
+            }
+            match unstored.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => unstored.push(CodeRange { start, end }),
+            }
+        }
+    }
+
+    // The lowest slot holds the last argument
+    // The address of a returned structure comes before the arguments on
+    // amd64, but is passed in x8 on AArch64
+    let first = usize::from(struct_return && arch == Arch::X86_64);
+    let mut problems = Vec::new();
+
+    // What is in each slot wherever they have just been stored, by any path
+    let starts = (0..insns.len())
+        .filter_map(|i| {
+            let start = stored[i]?;
+            let state = states[i].as_ref()?;
+            (i == 0 || stored[i - 1].is_none()).then_some((state, start))
+        })
+        .collect::<Vec<_>>();
+    for (i, (_, param)) in slots.iter().enumerate() {
+        let expected = first + slots.len() - 1 - i;
+        // Where the slot is found holding something we can say is wrong on
+        // one path, that outweighs what we cannot say on another
+        let kind = starts
+            .iter()
+            .filter_map(|(state, start)| {
+                slot_problem(
+                    state,
+                    start + i as i64 * SLOT_SIZE,
+                    expected,
+                    param.name.clone(),
+                )
+            })
+            .min_by_key(|x| match x {
+                DiagnosticKind::SavedUntraced { value: None, .. } => 2,
+                DiagnosticKind::SavedUntraced { .. } => 1,
+                _ => 0,
+            });
+        if let Some(kind) = kind {
+            problems.push((param.die, kind));
+        }
+    }
+
+    // Whatever is written once they are stored, which may be to the slots
+    let clobber = insns
+        .iter()
+        .zip(&states)
+        .zip(&stored)
+        .find(|((insn, state), start)| {
+            let (Some(state), Some(start)) = (state, start) else {
+                return false;
+            };
+
+            state
+                .clone()
+                .step(insn)
+                .iter()
+                .any(|x| match (x.address, x.size) {
+                    (Some(a), Some(n)) => a < start + size && *start < a + n as i64,
+                    (Some(a), None) => a < start + size,
+                    (None, _) => false,
+                })
+        });
+    if let Some(((insn, _), _)) = clobber {
+        problems.push((
+            func.die,
+            DiagnosticKind::SavedClobbered {
+                pc: insn.address - func.range.start,
+            },
+        ));
+    }
+
+    Ok(Saving { unstored, problems })
+}
+
+/// What is wrong with the slot at `address` in `state`, which should hold
+/// integer argument `index`, named `name`, as it was at entry
+///
+/// A constant may be what the argument was known to be on the path by which
+/// it is stored, as where `if (a == 0)` leads to storing `$0`, and what we
+/// cannot follow may be the argument all the same, so neither is said to
+/// have overwritten it.
+fn slot_problem(
+    state: &State,
+    address: i64,
+    index: usize,
+    name: Option<String>,
+) -> Option<DiagnosticKind> {
+    Some(match slot_value(state, address) {
+        Value::Arg { index: from, .. } if from != index => {
+            DiagnosticKind::SavedWrongArgument { index, name, from }
+        }
+        Value::Arg { bits: 64, .. } => return None,
+        Value::Arg { bits, extend, .. } => DiagnosticKind::SavedModified {
+            index,
+            name,
+            bits,
+            extend: extend.map(|x| {
+                String::from(match x {
+                    Extend::Sign => "sign",
+                    Extend::Zero => "zero",
+                })
+            }),
+        },
+        Value::Stack(_) => DiagnosticKind::SavedOverwritten { index, name },
+        Value::Const(x) => DiagnosticKind::SavedUntraced {
+            index,
+            name,
+            value: Some(x),
+        },
+        Value::Unknown => DiagnosticKind::SavedUntraced {
+            index,
+            name,
+            value: None,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elf;
+    use crate::index::tests::function;
+    use crate::{Parameter, Status};
+
+    /// `pushq %rbp; movq %rsp,%rbp`
+    const PROLOGUE: [u8; 4] = [0x55, 0x48, 0x89, 0xe5];
+    /// `movq %rdi,-0x8(%rbp); movq %rsi,-0x10(%rbp)`
+    const SAVE: [u8; 8] = [0x48, 0x89, 0x7d, 0xf8, 0x48, 0x89, 0x75, 0xf0];
+
+    /// What `examine` makes of `code`, a function saving two arguments at
+    /// -0x10 from `%rbp`, whose frame base is valid in `valid`
+    fn examine_code(code: &[u8], valid: &[(u64, u64)], struct_return: bool) -> Saving {
+        let data = elf::tests::object(code);
+        let object = object::File::parse(&*data).unwrap();
+        let mut func = function("f", 0, code.len() as u64, Status::Saved { offset: -16 });
+        func.parameters = ["a", "b"]
+            .iter()
+            .zip(1..)
+            .map(|(name, die)| Parameter {
+                name: Some(name.to_string()),
+                die,
+                integer: true,
+            })
+            .collect();
+        func.base.valid = valid
+            .iter()
+            .map(|&(start, end)| CodeRange { start, end })
+            .collect();
+
+        examine(&object, &func, struct_return).unwrap()
+    }
+
+    fn ranges(ranges: &[(u64, u64)]) -> Vec<CodeRange> {
+        ranges
+            .iter()
+            .map(|&(start, end)| CodeRange { start, end })
+            .collect()
+    }
+
+    #[test]
+    fn stored() {
+        let code = [&PROLOGUE[..], &SAVE, &[0x90, 0x5d, 0xc3]].concat();
+        let saving = examine_code(&code, &[(4, 0xd)], false);
+
+        assert_eq!(saving.unstored, ranges(&[(4, 0xc)]));
+        assert!(saving.problems.is_empty());
+    }
+
+    #[test]
+    fn stored_on_every_path() {
+        let code = [
+            &PROLOGUE[..],
+            &[0x48, 0x85, 0xff], // testq %rdi,%rdi
+            &[0x74, 0x0b],       // je 1f
+            &SAVE,
+            &[0x90, 0x5d, 0xc3], // 2: nop; popq %rbp; retq
+            &SAVE,               // 1:
+            &[0xeb, 0xf3],       // jmp 2b
+        ]
+        .concat();
+        let saving = examine_code(&code, &[(4, 0x13), (0x14, 0x1e)], false);
+
+        assert_eq!(saving.unstored, ranges(&[(4, 0x11), (0x14, 0x1c)]));
+        assert!(saving.problems.is_empty());
+    }
+
+    #[test]
+    fn stored_on_one_path() {
+        // As above, but the second path stores only the first argument
+        let code = [
+            &PROLOGUE[..],
+            &[0x48, 0x85, 0xff], // testq %rdi,%rdi
+            &[0x74, 0x0b],       // je 1f
+            &SAVE,
+            &[0x90, 0x5d, 0xc3],       // 2: nop; popq %rbp; retq
+            &[0x48, 0x89, 0x7d, 0xf8], // 1: movq %rdi,-0x8(%rbp)
+            &[0x90; 4],
+            &[0xeb, 0xf3], // jmp 2b
+        ]
+        .concat();
+        let saving = examine_code(&code, &[(4, 0x13), (0x14, 0x1e)], false);
+
+        assert_eq!(saving.unstored, ranges(&[(4, 0x13), (0x14, 0x1e)]));
+    }
+
+    #[test]
+    fn unreachable() {
+        // Code after the return is reached by no path we can follow
+        let code = [&PROLOGUE[..], &SAVE, &[0xc3, 0x90, 0x90]].concat();
+        let saving = examine_code(&code, &[(4, 0xf)], false);
+
+        assert_eq!(saving.unstored, ranges(&[(4, 0xc), (0xd, 0xf)]));
+    }
+
+    #[test]
+    fn no_code() {
+        let data = elf::tests::object(&[0xc3]);
+        let object = object::File::parse(&*data).unwrap();
+        let func = function("f", 0x1000, 0x1040, Status::Saved { offset: -40 });
+
+        assert_eq!(
+            examine(&object, &func, false).unwrap().unstored,
+            ranges(&[(4, 0x20)])
+        );
+    }
+
+    /// `code` saving the arguments one way, and `other` another, joining
+    /// before a return
+    fn branches(code: &[u8], other: &[u8]) -> Vec<u8> {
+        let ret = [0x90, 0x5d, 0xc3]; // nop; popq %rbp; retq
+        let join = PROLOGUE.len() + 5 + code.len();
+        let end = join + ret.len() + other.len() + 2;
+        [
+            &PROLOGUE[..],
+            &[0x48, 0x85, 0xff],                     // testq %rdi,%rdi
+            &[0x74, (code.len() + ret.len()) as u8], // je
+            code,
+            &ret,
+            other,
+            &[0xeb, (join as isize - end as isize) as u8], // jmp
+        ]
+        .concat()
+    }
+
+    /// The problem with the slot of the parameter `die`
+    fn problem(saving: &Saving, die: u64) -> Option<&DiagnosticKind> {
+        saving.problems.iter().find(|x| x.0 == die).map(|x| &x.1)
+    }
+
+    #[test]
+    fn swapped() {
+        let code = [
+            &PROLOGUE[..],
+            &[0x48, 0x89, 0x75, 0xf8], // movq %rsi,-0x8(%rbp)
+            &[0x48, 0x89, 0x7d, 0xf0], // movq %rdi,-0x10(%rbp)
+            &[0xc3],
+        ]
+        .concat();
+        let saving = examine_code(&code, &[(4, 0xd)], false);
+
+        assert!(matches!(
+            problem(&saving, 1),
+            Some(DiagnosticKind::SavedWrongArgument {
+                index: 0,
+                from: 1,
+                ..
+            })
+        ));
+        assert!(matches!(
+            problem(&saving, 2),
+            Some(DiagnosticKind::SavedWrongArgument {
+                index: 1,
+                from: 0,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn struct_return() {
+        // The first argument is in %rsi, after the returned structure's
+        // address
+        let code = [
+            &PROLOGUE[..],
+            &[0x48, 0x89, 0x75, 0xf8], // movq %rsi,-0x8(%rbp)
+            &[0x48, 0x89, 0x55, 0xf0], // movq %rdx,-0x10(%rbp)
+            &[0xc3],
+        ]
+        .concat();
+
+        assert!(examine_code(&code, &[(4, 0xd)], true).problems.is_empty());
+        assert_eq!(examine_code(&code, &[(4, 0xd)], false).problems.len(), 2);
+    }
+
+    #[test]
+    fn modified() {
+        let code = [
+            &PROLOGUE[..],
+            &[0x89, 0xff],       // movl %edi,%edi
+            &[0x48, 0x63, 0xf6], // movslq %esi,%rsi
+            &SAVE,
+            &[0xc3],
+        ]
+        .concat();
+        let saving = examine_code(&code, &[(4, 0x12)], false);
+
+        assert!(matches!(
+            problem(&saving, 1),
+            Some(DiagnosticKind::SavedModified { index: 0, bits: 32, extend: Some(x), .. })
+                if x == "zero"
+        ));
+        assert!(matches!(
+            problem(&saving, 2),
+            Some(DiagnosticKind::SavedModified { index: 1, bits: 32, extend: Some(x), .. })
+                if x == "sign"
+        ));
+    }
+
+    #[test]
+    fn overwritten() {
+        let code = [
+            &PROLOGUE[..],
+            &[0x48, 0x8d, 0x7d, 0xe0], // leaq -0x20(%rbp),%rdi
+            &[0x48, 0x83, 0xc6, 0x01], // addq $1,%rsi
+            &SAVE,
+            &[0xc3],
+        ]
+        .concat();
+        let saving = examine_code(&code, &[(4, 0x15)], false);
+
+        assert!(matches!(
+            problem(&saving, 1),
+            Some(DiagnosticKind::SavedOverwritten { index: 0, .. })
+        ));
+        assert!(matches!(
+            problem(&saving, 2),
+            Some(DiagnosticKind::SavedUntraced {
+                index: 1,
+                value: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn constant_on_one_path() {
+        // As GCC stores $0 where the argument is known to be 0
+        let code = branches(
+            &SAVE,
+            &[
+                &[0x48, 0xc7, 0x45, 0xf8, 0, 0, 0, 0][..], // movq $0,-0x8(%rbp)
+                &[0x48, 0x89, 0x75, 0xf0],                 // movq %rsi,-0x10(%rbp)
+            ]
+            .concat(),
+        );
+        let saving = examine_code(&code, &[(4, code.len() as u64)], false);
+
+        assert!(matches!(
+            problem(&saving, 1),
+            Some(DiagnosticKind::SavedUntraced {
+                index: 0,
+                value: Some(0),
+                ..
+            })
+        ));
+        assert!(problem(&saving, 2).is_none());
+    }
+
+    #[test]
+    fn wrong_on_one_path() {
+        // What is wrong on one path outweighs what is unknown where the
+        // paths join
+        let code = branches(
+            &SAVE,
+            &[
+                &[0x48, 0x89, 0x75, 0xf8][..], // movq %rsi,-0x8(%rbp)
+                &[0x48, 0x89, 0x75, 0xf0],     // movq %rsi,-0x10(%rbp)
+            ]
+            .concat(),
+        );
+        let saving = examine_code(&code, &[(4, code.len() as u64)], false);
+
+        assert!(matches!(
+            problem(&saving, 1),
+            Some(DiagnosticKind::SavedWrongArgument {
+                index: 0,
+                from: 1,
+                ..
+            })
+        ));
+    }
+
+    /// The instruction found to write to the slots once they are stored,
+    /// if any
+    fn clobbered(code: &[&[u8]]) -> Option<u64> {
+        let code = [&PROLOGUE[..], &SAVE]
+            .iter()
+            .chain(code)
+            .copied()
+            .collect::<Vec<_>>();
+        let code = [code.concat(), vec![0xc3]].concat();
+        let saving = examine_code(&code, &[(4, code.len() as u64)], false);
+
+        saving.problems.iter().find_map(|x| match x.1 {
+            DiagnosticKind::SavedClobbered { pc } => Some(pc),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn clobbers() {
+        assert_eq!(clobbered(&[]), None);
+        assert_eq!(
+            clobbered(&[&[0x48, 0xc7, 0x45, 0xf8, 1, 0, 0, 0]]), // movq $1,-0x8(%rbp)
+            Some(0xc)
+        );
+        assert_eq!(
+            clobbered(&[
+                &[0x48, 0x8d, 0x45, 0xf0],                   // leaq -0x10(%rbp),%rax
+                &[0x48, 0xc7, 0x00, 0x01, 0x00, 0x00, 0x00], // movq $1,(%rax)
+            ]),
+            Some(0x10)
+        );
+        assert_eq!(
+            clobbered(&[
+                &[0x48, 0x8d, 0x7d, 0xe0],       // leaq -0x20(%rbp),%rdi
+                &[0xb9, 0x04, 0x00, 0x00, 0x00], // movl $4,%ecx
+                &[0x31, 0xc0],                   // xorl %eax,%eax
+                &[0xf3, 0x48, 0xab],             // rep stosq
+            ]),
+            Some(0x17)
+        );
+        // Below the slots, and through a pointer we cannot follow
+        assert_eq!(
+            clobbered(&[
+                &[0x48, 0xc7, 0x45, 0xe8, 1, 0, 0, 0], // movq $1,-0x18(%rbp)
+                &[0x48, 0xc7, 0x07, 0x01, 0x00, 0x00, 0x00], // movq $1,(%rdi)
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn written_before_stored() {
+        let code = [
+            &PROLOGUE[..],
+            &[0x48, 0xc7, 0x45, 0xf8, 1, 0, 0, 0], // movq $1,-0x8(%rbp)
+            &SAVE,
+            &[0xc3],
+        ]
+        .concat();
+
+        assert!(examine_code(&code, &[(4, 0x15)], false).problems.is_empty());
+    }
+}
@@ -0,0 +1,1163 @@
+// This is a hacked up copy/paste of the Gimli example, modified to do stuff.
+// That file doesn't have a copyright notice, but I think the whole of gimli is
+//     (C) The Rust Project Developers
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use fallible_iterator::FallibleIterator;
+use gimli::ReaderOffset;
+use object::Object;
+use rayon::prelude::*;
+use typed_arena::Arena;
+
+use crate::code::{function_code, Arch};
+use crate::dwarf::{
+    attr_to_string, die_has_c_source, die_source_file, die_source_line, entry_to_die_offset,
+    is_memory_type, is_register_type, non_concrete_outcome,
+};
+use crate::protocol::{self, Protocol};
+use crate::range::{without, BaseOffset, CodeRange};
+use crate::reloc;
+use crate::saving;
+use crate::select::Selector;
+
+/// The name of the variable in which the plugin saves arguments
+pub const SAVED_ARGS_NAME: &str = "__illumos_saved_args_v1__";
+
+/// The version of the protocol by which the variable `name` holds saved
+/// arguments, if it is one, as `__illumos_saved_args_v<N>__` is of version N
+pub fn saved_args_version(name: &str) -> Option<u32> {
+    let version = name
+        .strip_prefix("__illumos_saved_args_v")?
+        .strip_suffix("__")?;
+    if version.is_empty() || !version.bytes().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    version.parse().ok()
+}
+
+/// True if `producer`, the `DW_AT_producer` of a unit, says it was compiled
+/// with `-msave-args`, as GCC records the options with which it compiled
+fn compiled_with_msave_args(producer: &str) -> bool {
+    producer.split_whitespace().any(|x| x == "-msave-args")
+}
+
+/// The size of each slot in the saved argument array
+pub(crate) const SLOT_SIZE: i64 = 8;
+
+/// A formal parameter of a function
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Parameter {
+    pub name: Option<String>,
+    pub die: u64,
+    /// True if this parameter would be passed in an integer register, and so
+    /// should have been saved
+    pub integer: bool,
+}
+
+/// Where a function came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Source {
+    /// Name of the compilation unit
+    pub unit: Option<String>,
+    /// Directory in which the compilation unit was compiled
+    pub comp_dir: Option<String>,
+    /// File in which the function is declared
+    pub file: Option<String>,
+    /// Line on which the function is declared
+    pub line: Option<u64>,
+}
+
+impl Source {
+    /// The file in which the function is declared, relative to the
+    /// compilation directory if it is not already absolute
+    pub fn path(&self) -> Option<PathBuf> {
+        let file = Path::new(self.file.as_ref()?);
+
+        match &self.comp_dir {
+            Some(dir) if file.is_relative() => Some(Path::new(dir).join(file)),
+            _ => Some(file.to_path_buf()),
+        }
+    }
+}
+
+/// Whether a function saved its arguments
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Status {
+    /// The arguments are saved at `offset` from the frame base register
+    Saved { offset: i64 },
+    /// The function has integer parameters, but does not save them
+    Missing,
+}
+
+/// Everything we know about a function
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct FunctionRecord {
+    pub name: String,
+    /// Offset of the function's entry in `.debug_info`
+    pub die: u64,
+    pub source: Source,
+    /// Extent of the function
+    pub range: CodeRange,
+    /// Where the frame base is, and in which parts of the function
+    pub base: BaseOffset,
+    pub parameters: Vec<Parameter>,
+    pub status: Status,
+    /// The parts of the function in which the frame base is known, but not
+    /// every slot has certainly been stored
+    pub unstored: Vec<CodeRange>,
+    /// The first instruction which may write to the slots once they are
+    /// stored, after which what is in them is never to be trusted
+    pub clobbered: Option<u64>,
+    /// The protocol by which the function saves its arguments, as its DWARF
+    /// or its code says, if it does
+    pub protocol: Option<Protocol>,
+    /// The protocols by which a debugger would recover its arguments
+    pub protocols: Vec<Protocol>,
+}
+
+impl FunctionRecord {
+    /// The number of parameters that should have been saved
+    pub fn nparams(&self) -> usize {
+        self.parameters.iter().filter(|x| x.integer).count()
+    }
+
+    /// The offset from the frame base register of each slot of the saved
+    /// argument array, and the parameter saved there, which are in reverse
+    /// order
+    pub fn slots(&self) -> Vec<(i64, &Parameter)> {
+        let Status::Saved { offset } = self.status else {
+            return Vec::new();
+        };
+
+        self.parameters
+            .iter()
+            .filter(|x| x.integer)
+            .rev()
+            .enumerate()
+            .map(|(i, x)| (offset + i as i64 * SLOT_SIZE, x))
+            .collect()
+    }
+
+    /// The parts of the function in which the frame base is not known
+    pub fn invalid(&self) -> Vec<CodeRange> {
+        self.base.invert(&self.range)
+    }
+
+    /// The parts of the function in which the saved arguments may be
+    /// recovered, the frame base being known and every slot stored
+    pub fn recoverable(&self) -> Vec<CodeRange> {
+        without(&self.base.valid, &self.unstored)
+    }
+
+    /// The parts of the function in which a debugger may trust what it
+    /// finds in the slots: wherever they are recoverable, unless the slots
+    /// may be written once stored
+    pub fn trusted(&self) -> Vec<CodeRange> {
+        match (&self.status, self.clobbered) {
+            (Status::Saved { .. }, None) => self.recoverable(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The percentage of the function in which the frame base is known, but
+    /// not every slot has been stored
+    pub fn unstored_coverage(&self) -> f64 {
+        self.unstored
+            .iter()
+            .fold(0, |acc, x| acc + (x.end - x.start)) as f64
+            / (self.range.end - self.range.start) as f64
+            * 100.0
+    }
+
+    /// The percentage of the function in which the frame base is known
+    /// and, if the arguments are saved, every slot has been stored
+    pub fn coverage(&self) -> f64 {
+        match self.status {
+            Status::Saved { .. } => {
+                (self.base.coverage(&self.range) - self.unstored_coverage()).max(0.0)
+            }
+            Status::Missing => self.base.coverage(&self.range),
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        match self.status {
+            Status::Saved { .. } => Outcome::Analysed,
+            Status::Missing => Outcome::MissingArray,
+        }
+    }
+}
+
+/// Something wrong with a function, or with the object as a whole
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Diagnostic {
+    /// Offset of the entry this concerns in `.debug_info`, if any
+    pub die: Option<u64>,
+    /// Name of the function this concerns, if any
+    pub function: Option<String>,
+    pub source: Source,
+    pub kind: DiagnosticKind,
+    pub severity: Severity,
+}
+
+/// How much a diagnostic matters
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    #[default]
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum DiagnosticKind {
+    /// The function has no `DW_AT_frame_base`
+    NoFrameBase,
+    /// The function's frame base could not be understood
+    BadFrameBase(String),
+    /// The function's frame base is never relative to the base pointer
+    NoBasePointer,
+    /// The saved argument variable is not marked artificial
+    NotArtificial,
+    /// The saved argument variable's location is not a frame offset
+    UnexpectedLocation(String),
+    /// The saved argument variable's location is more than a frame offset
+    ExtraLocationOperations,
+    /// The function has integer parameters but did not save them
+    NoSavedArgs { nparams: usize },
+    /// We gave up examining the object
+    Failed(String),
+    /// The object's CTF could not be understood
+    BadCtf(String),
+    /// CTF says the function has `ctf` arguments, DWARF `dwarf`
+    CtfArgCount { ctf: usize, dwarf: usize },
+    /// CTF and DWARF disagree whether the argument at `index` is passed in an
+    /// integer register, DWARF saying that it is if `integer`
+    CtfArgType {
+        index: usize,
+        name: Option<String>,
+        integer: bool,
+    },
+    /// The slot of integer argument `index` holds integer argument `from`
+    SavedWrongArgument {
+        index: usize,
+        name: Option<String>,
+        from: usize,
+    },
+    /// The slot of integer argument `index` holds only its low `bits`,
+    /// extended as `extend` says if it is
+    SavedModified {
+        index: usize,
+        name: Option<String>,
+        bits: u8,
+        extend: Option<String>,
+    },
+    /// The slot of integer argument `index` holds an address on the stack,
+    /// the argument having been overwritten before it was saved
+    SavedOverwritten { index: usize, name: Option<String> },
+    /// The slot of integer argument `index` holds the constant `value`,
+    /// which the argument may have been known to be, or if none something
+    /// we cannot follow back to the function's entry
+    SavedUntraced {
+        index: usize,
+        name: Option<String>,
+        value: Option<i64>,
+    },
+    /// The saved arguments may be written again by the instruction at `pc`
+    /// once they are saved
+    SavedClobbered { pc: u64 },
+    /// The saved arguments are valid in only `coverage` percent of the
+    /// function, less than the `min` required
+    LowCoverage { coverage: f64, min: f64 },
+}
+
+impl DiagnosticKind {
+    /// The code of every kind of diagnostic, and of each way in which
+    /// [`check`](crate::check) finds a function short of its rules
+    pub const CODES: [&'static str; 19] = [
+        "no-frame-base",
+        "bad-frame-base",
+        "no-base-pointer",
+        "not-artificial",
+        "unexpected-location",
+        "extra-location-operations",
+        "no-saved-args",
+        "failed",
+        "bad-ctf",
+        "ctf-arg-count",
+        "ctf-arg-type",
+        "saved-wrong-arg",
+        "saved-modified",
+        "saved-overwritten",
+        "saved-untraced",
+        "saved-clobbered",
+        "missing-array",
+        "forbidden-array",
+        "low-coverage",
+    ];
+
+    /// A sentence describing the diagnostics with `code`
+    pub fn describe(code: &str) -> Option<&'static str> {
+        Some(match code {
+            "no-frame-base" => "The function has no frame base",
+            "bad-frame-base" => "The function's frame base could not be understood",
+            "no-base-pointer" => "The function's frame base is never relative to the base pointer",
+            "not-artificial" => "The saved argument variable is not marked artificial",
+            "unexpected-location" => "The saved argument variable is not at a frame offset",
+            "extra-location-operations" => {
+                "The saved argument variable's location is more than a frame offset"
+            }
+            "no-saved-args" => "The function has integer parameters but did not save them",
+            "failed" => "The object could not be examined",
+            "bad-ctf" => "The object's CTF could not be understood",
+            "ctf-arg-count" => "CTF and DWARF disagree on how many arguments the function has",
+            "ctf-arg-type" => {
+                "CTF and DWARF disagree on whether an argument is passed in an integer register"
+            }
+            "saved-wrong-arg" => "An argument's slot is stored from another argument",
+            "saved-modified" => "An argument is saved truncated or extended",
+            "saved-overwritten" => "An argument is saved after it was overwritten",
+            "saved-untraced" => {
+                "An argument is saved from a constant or from what cannot be traced"
+            }
+            "saved-clobbered" => "The saved arguments may be overwritten once saved",
+            "missing-array" => "The function does not save its arguments, which is required",
+            "forbidden-array" => "The function saves its arguments, which is forbidden",
+            "low-coverage" => "The saved arguments are valid in less of the function than required",
+            _ => return None,
+        })
+    }
+
+    /// The stable name by which this kind of diagnostic is known
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticKind::NoFrameBase => "no-frame-base",
+            DiagnosticKind::BadFrameBase(_) => "bad-frame-base",
+            DiagnosticKind::NoBasePointer => "no-base-pointer",
+            DiagnosticKind::NotArtificial => "not-artificial",
+            DiagnosticKind::UnexpectedLocation(_) => "unexpected-location",
+            DiagnosticKind::ExtraLocationOperations => "extra-location-operations",
+            DiagnosticKind::NoSavedArgs { .. } => "no-saved-args",
+            DiagnosticKind::Failed(_) => "failed",
+            DiagnosticKind::BadCtf(_) => "bad-ctf",
+            DiagnosticKind::CtfArgCount { .. } => "ctf-arg-count",
+            DiagnosticKind::CtfArgType { .. } => "ctf-arg-type",
+            DiagnosticKind::SavedWrongArgument { .. } => "saved-wrong-arg",
+            DiagnosticKind::SavedModified { .. } => "saved-modified",
+            DiagnosticKind::SavedOverwritten { .. } => "saved-overwritten",
+            DiagnosticKind::SavedUntraced { .. } => "saved-untraced",
+            DiagnosticKind::SavedClobbered { .. } => "saved-clobbered",
+            DiagnosticKind::LowCoverage { .. } => "low-coverage",
+        }
+    }
+
+    /// The severity of this kind of diagnostic, unless told otherwise
+    pub fn default_severity(&self) -> Severity {
+        match self {
+            DiagnosticKind::Failed(_)
+            | DiagnosticKind::SavedClobbered { .. }
+            | DiagnosticKind::LowCoverage { .. } => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// The stable name by which this kind of diagnostic is known
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.function.as_deref().unwrap_or("<unknown>");
+
+        match &self.kind {
+            DiagnosticKind::NoFrameBase => write!(f, "{name}() has no frame base"),
+            DiagnosticKind::BadFrameBase(x) => write!(f, "{name}(): reading frame base: {x}"),
+            DiagnosticKind::NoBasePointer => {
+                write!(f, "{name}() has no recognized base-pointer")
+            }
+            DiagnosticKind::NotArtificial => {
+                write!(f, "{name}() {SAVED_ARGS_NAME} is not artificial")
+            }
+            DiagnosticKind::UnexpectedLocation(x) => write!(
+                f,
+                "{name}() {SAVED_ARGS_NAME} has unexpected location expression: {x}"
+            ),
+            DiagnosticKind::ExtraLocationOperations => {
+                write!(
+                    f,
+                    "{name}() {SAVED_ARGS_NAME} has extra location operations"
+                )
+            }
+            DiagnosticKind::NoSavedArgs { nparams } => {
+                write!(f, "{name}(): {nparams} parameters but no saved args")
+            }
+            DiagnosticKind::Failed(x) => write!(f, "failed to examine: {x}"),
+            DiagnosticKind::BadCtf(x) => write!(f, "reading CTF: {x}"),
+            DiagnosticKind::CtfArgCount { ctf, dwarf } => write!(
+                f,
+                "{name}(): CTF has {ctf} arguments, DWARF {dwarf} parameters"
+            ),
+            DiagnosticKind::CtfArgType {
+                index,
+                name: param,
+                integer,
+            } => write!(
+                f,
+                "{name}(): argument {index} ({}) is {}an integer by DWARF, but {}by CTF",
+                param.as_deref().unwrap_or("<anonymous>"),
+                if *integer { "" } else { "not " },
+                if *integer { "not " } else { "" },
+            ),
+            DiagnosticKind::SavedWrongArgument {
+                index,
+                name: param,
+                from,
+            } => write!(
+                f,
+                "{name}(): argument {index} ({}) is saved from argument {from}",
+                param.as_deref().unwrap_or("<anonymous>"),
+            ),
+            DiagnosticKind::SavedModified {
+                index,
+                name: param,
+                bits,
+                extend,
+            } => write!(
+                f,
+                "{name}(): argument {index} ({}) is saved {}",
+                param.as_deref().unwrap_or("<anonymous>"),
+                match extend {
+                    Some(x) => format!("{x}-extended from {bits} bits"),
+                    None => format!("truncated to {bits} bits"),
+                },
+            ),
+            DiagnosticKind::SavedOverwritten { index, name: param } => write!(
+                f,
+                "{name}(): argument {index} ({}) is saved after it was overwritten",
+                param.as_deref().unwrap_or("<anonymous>"),
+            ),
+            DiagnosticKind::SavedUntraced {
+                index,
+                name: param,
+                value: Some(value),
+            } => write!(
+                f,
+                "{name}(): argument {index} ({}) is saved as the constant {value:#x}, \
+                 which it may not be",
+                param.as_deref().unwrap_or("<anonymous>"),
+            ),
+            DiagnosticKind::SavedUntraced {
+                index,
+                name: param,
+                value: None,
+            } => write!(
+                f,
+                "{name}(): argument {index} ({}) is saved from what cannot be traced to it",
+                param.as_deref().unwrap_or("<anonymous>"),
+            ),
+            DiagnosticKind::SavedClobbered { pc } => write!(
+                f,
+                "{name}(): saved arguments may be overwritten at +{pc:#x}, and are unsafe"
+            ),
+            DiagnosticKind::LowCoverage { coverage, min } => write!(
+                f,
+                "{name}() saved arguments valid in {coverage:2.2}%, less than the required {min}%"
+            ),
+        }
+    }
+}
+
+/// What became of a function
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// The function saved its arguments
+    Analysed,
+    /// The function is only a prototype
+    Declaration,
+    /// The function is the abstract parent of inlined copies
+    Abstract,
+    /// The function is an inlined or out-of-line copy of an abstract parent
+    Inlined,
+    /// The function was written in assembler
+    Asm,
+    /// The function has no name
+    Anonymous,
+    /// The function has no code
+    NoExtent,
+    /// The function has no `DW_AT_frame_base`
+    NoFrameBase,
+    /// The function's frame base could not be understood, or is never
+    /// relative to the base pointer
+    UnsupportedFrameBase,
+    /// The function has parameters not passed in integer registers, and so
+    /// is not expected to save them
+    NonIntegerParams,
+    /// The function has no parameters, and so nothing to save
+    NoParams,
+    /// The function has integer parameters, but did not save them
+    MissingArray,
+}
+
+impl Outcome {
+    /// Every outcome, in order
+    pub const ALL: [Outcome; 12] = [
+        Outcome::Analysed,
+        Outcome::Declaration,
+        Outcome::Abstract,
+        Outcome::Inlined,
+        Outcome::Asm,
+        Outcome::Anonymous,
+        Outcome::NoExtent,
+        Outcome::NoFrameBase,
+        Outcome::UnsupportedFrameBase,
+        Outcome::NonIntegerParams,
+        Outcome::NoParams,
+        Outcome::MissingArray,
+    ];
+
+    /// True if the function this describes has code of its own, rather than
+    /// merely describing code elsewhere
+    pub fn is_definition(&self) -> bool {
+        !matches!(
+            self,
+            Outcome::Declaration | Outcome::Abstract | Outcome::Inlined
+        )
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Analysed => "analysed",
+            Outcome::Declaration => "declaration",
+            Outcome::Abstract => "abstract",
+            Outcome::Inlined => "inlined",
+            Outcome::Asm => "asm",
+            Outcome::Anonymous => "anonymous",
+            Outcome::NoExtent => "no-extent",
+            Outcome::NoFrameBase => "no-frame-base",
+            Outcome::UnsupportedFrameBase => "unsupported-frame-base",
+            Outcome::NonIntegerParams => "non-integer-params",
+            Outcome::NoParams => "no-params",
+            Outcome::MissingArray => "missing-array",
+        })
+    }
+}
+
+/// A function we did not examine, or examined and found nothing to save
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Skipped {
+    pub name: Option<String>,
+    /// Offset of the function's entry in `.debug_info`
+    pub die: u64,
+    pub source: Source,
+    pub outcome: Outcome,
+}
+
+/// What we found in an object
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct ScanReport {
+    /// Each function we could examine, in the order they appear
+    pub functions: Vec<FunctionRecord>,
+    /// Every other function, and why it is not among `functions`
+    pub skipped: Vec<Skipped>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ScanReport {
+    /// The outcome of every function, in no particular order
+    pub fn outcomes(&self) -> impl Iterator<Item = Outcome> + '_ {
+        self.functions
+            .iter()
+            .map(|x| x.outcome())
+            .chain(self.skipped.iter().map(|x| x.outcome))
+    }
+
+    /// True if we examined the whole object
+    pub fn is_complete(&self) -> bool {
+        !self
+            .diagnostics
+            .iter()
+            .any(|x| matches!(x.kind, DiagnosticKind::Failed(_)))
+    }
+
+    /// A diagnostic for each function which saves its arguments, but in
+    /// which they are valid in less than `min` percent
+    pub fn low_coverage(&self, min: f64) -> Vec<Diagnostic> {
+        self.functions
+            .iter()
+            .filter(|x| matches!(x.status, Status::Saved { .. }) && x.coverage() < min)
+            .map(|x| {
+                let kind = DiagnosticKind::LowCoverage {
+                    coverage: x.coverage(),
+                    min,
+                };
+
+                Diagnostic {
+                    die: Some(x.die),
+                    function: Some(x.name.clone()),
+                    source: x.source.clone(),
+                    severity: kind.default_severity(),
+                    kind,
+                }
+            })
+            .collect()
+    }
+
+    /// True if any diagnostic is an error
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|x| x.severity == Severity::Error)
+    }
+
+    fn extend(&mut self, other: ScanReport) {
+        self.functions.extend(other.functions);
+        self.skipped.extend(other.skipped);
+        self.diagnostics.extend(other.diagnostics);
+    }
+
+    fn failed(&mut self, error: anyhow::Error) {
+        let kind = DiagnosticKind::Failed(format!("{error:?}"));
+
+        self.diagnostics.push(Diagnostic {
+            die: None,
+            function: None,
+            source: Source::default(),
+            severity: kind.default_severity(),
+            kind,
+        });
+    }
+}
+
+/// Examine each compilation unit in `object`
+///
+/// If we cannot examine a unit, we stop there, and the report contains what
+/// was found up to that point.
+pub fn scan_object(object: &object::File) -> ScanReport {
+    scan_object_with(object, &Selector::default())
+}
+
+/// Examine the functions in `object` chosen by `selector`
+///
+/// As with [`scan_object`], if we cannot examine a unit we stop there.
+pub fn scan_object_with(object: &object::File, selector: &Selector) -> ScanReport {
+    let mut report = ScanReport::default();
+    let endian = if object.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+
+    let arena_data = Arena::new();
+    let arena_relocations = Arena::new();
+
+    // Load a section and return as `Cow<[u8]>`.
+    let load_section = |id: gimli::SectionId| -> Result<_> {
+        reloc::load_file_section(id, object, endian, false, &arena_data, &arena_relocations)
+    };
+
+    // Load all of the sections.
+    let dwarf = match gimli::Dwarf::load(&load_section) {
+        Ok(x) => x,
+        Err(x) => {
+            report.failed(x);
+            return report;
+        }
+    };
+
+    let headers = match dwarf.units().collect::<Vec<_>>() {
+        Ok(x) => x,
+        Err(x) => {
+            report.failed(x.into());
+            return report;
+        }
+    };
+
+    // The compilation units are independent of each other, so examine them
+    // concurrently, but report what we found strictly in CU order so the
+    // output does not depend on scheduling.
+    let results = headers
+        .into_par_iter()
+        .map(|header| scan_unit(object, &dwarf, header, selector))
+        .collect::<Vec<_>>();
+
+    for result in results {
+        match result {
+            Ok(x) => report.extend(x),
+            Err(x) => {
+                report.failed(x);
+                break;
+            }
+        }
+    }
+
+    report
+}
+
+/// Examine each function in the compilation unit `header`
+fn scan_unit<R: gimli::Reader>(
+    object: &object::File,
+    dwarf: &gimli::Dwarf<R>,
+    header: gimli::UnitHeader<R>,
+    selector: &Selector,
+) -> Result<ScanReport> {
+    let mut report = ScanReport::default();
+    let unit = dwarf.unit(header)?;
+    let unit_name = unit
+        .name
+        .as_ref()
+        .and_then(|x| x.to_string_lossy().ok())
+        .map(|x| x.into_owned());
+    let comp_dir = unit
+        .comp_dir
+        .as_ref()
+        .and_then(|x| x.to_string_lossy().ok())
+        .map(|x| x.into_owned());
+
+    let producer = unit
+        .entries_tree(None)?
+        .root()?
+        .entry()
+        .attr_value(gimli::DW_AT_producer)?
+        .and_then(|x| attr_to_string(x, dwarf, &unit));
+    let msave_args = producer.is_some_and(|x| compiled_with_msave_args(&x));
+
+    if !selector.accepts_unit(unit_name.as_deref()) {
+        return Ok(report);
+    }
+
+    // Functions may be nested within other functions or lexical blocks, so
+    // visit every entry rather than only the unit's children.
+    let mut entries = unit.entries();
+    while let Some((_, entry)) = entries.next_dfs()? {
+        if entry.tag() != gimli::DW_TAG_subprogram {
+            continue;
+        }
+
+        let funcoffset = entry_to_die_offset(entry, &unit).unwrap().into_u64();
+        let source = Source {
+            unit: unit_name.clone(),
+            comp_dir: comp_dir.clone(),
+            file: die_source_file(entry, dwarf, &unit)?,
+            line: die_source_line(entry)?,
+        };
+
+        if !selector
+            .accepts_function(entry, dwarf, &unit, &source)
+            .with_context(|| format!("DIE {funcoffset:#x} selected?"))?
+        {
+            continue;
+        }
+
+        let mut found = ScanReport::default();
+        let offset = entry.offset();
+        scan_function(object, dwarf, &unit, offset, source, msave_args, &mut found)
+            .with_context(|| format!("DIE {funcoffset:#x}"))?;
+
+        if selector.accepts_result(&found) {
+            report.extend(found);
+        }
+    }
+
+    Ok(report)
+}
+
+/// Examine the function at `offset` in `unit`, which was compiled with
+/// `-msave-args` if `msave_args`, adding what we find to `report`
+fn scan_function<R: gimli::Reader>(
+    object: &object::File,
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+    offset: gimli::UnitOffset<R::Offset>,
+    source: Source,
+    msave_args: bool,
+    report: &mut ScanReport,
+) -> Result<()> {
+    let mut tree = unit.entries_tree(Some(offset))?;
+    let node = tree.root()?;
+    let funcentry = node.entry();
+    let funcoffset = entry_to_die_offset(funcentry, unit).unwrap().into_u64();
+    let name = funcentry
+        .attr_value(gimli::DW_AT_name)?
+        .and_then(|x| attr_to_string(x, dwarf, unit));
+
+    let skipped = |outcome: Outcome| Skipped {
+        name: name.clone(),
+        die: funcoffset,
+        source: source.clone(),
+        outcome,
+    };
+
+    if let Some(outcome) = non_concrete_outcome(funcentry).context("is concrete?")? {
+        report.skipped.push(skipped(outcome));
+        return Ok(());
+    }
+
+    if !die_has_c_source(funcentry, dwarf, unit).context("has C source?")? {
+        report.skipped.push(skipped(Outcome::Asm));
+        return Ok(());
+    }
+
+    let Some(name) = name.clone() else {
+        // Apparently this may happen in C++, on ARM, sometimes?
+        report.skipped.push(skipped(Outcome::Anonymous));
+        return Ok(());
+    };
+
+    let diagnostic = |die: u64, kind: DiagnosticKind| Diagnostic {
+        die: Some(die),
+        function: Some(name.clone()),
+        source: source.clone(),
+        severity: kind.default_severity(),
+        kind,
+    };
+
+    let range = match CodeRange::from_function_die(funcentry).context("looking up extent")? {
+        Some(x) => x,
+        // Apparently this may happen on ARM, where on amd64 we'd
+        // generate an empty function, ARM elides the call (but
+        // not the DWARF?)
+        None => {
+            report.skipped.push(skipped(Outcome::NoExtent));
+            return Ok(());
+        }
+    };
+
+    let frame_base = match funcentry.attr_value(gimli::DW_AT_frame_base)? {
+        Some(x) => x,
+        None => {
+            report
+                .diagnostics
+                .push(diagnostic(funcoffset, DiagnosticKind::NoFrameBase));
+            report.skipped.push(skipped(Outcome::NoFrameBase));
+            return Ok(());
+        }
+    };
+
+    let base = match BaseOffset::from_frame_base(frame_base, &range, object, dwarf, unit) {
+        Err(x) => {
+            report.diagnostics.push(diagnostic(
+                funcoffset,
+                DiagnosticKind::BadFrameBase(format!("{x:?}")),
+            ));
+            report.skipped.push(skipped(Outcome::UnsupportedFrameBase));
+            return Ok(());
+        }
+        Ok(Some(x)) => x,
+        Ok(None) => {
+            report
+                .diagnostics
+                .push(diagnostic(funcoffset, DiagnosticKind::NoBasePointer));
+            report.skipped.push(skipped(Outcome::UnsupportedFrameBase));
+            return Ok(());
+        }
+    };
+
+    let struct_return = is_memory_type(funcentry, unit).context("checking return type")?;
+
+    let mut parameters = Vec::new();
+    let mut status = None;
+    let mut version = None;
+    let mut found = false;
+    let mut children = node.children();
+    while let Some(child) = children.next()? {
+        let childentry = child.entry();
+        let childoffset = entry_to_die_offset(childentry, unit).unwrap().into_u64();
+        let childname = childentry
+            .attr_value(gimli::DW_AT_name)?
+            .and_then(|x| attr_to_string(x, dwarf, unit));
+
+        match childentry.tag() {
+            gimli::DW_TAG_formal_parameter => {
+                let integer = is_register_type(childentry, unit)
+                    .with_context(|| format!("DIE {childoffset:#x}: checking parameter type"))?;
+
+                parameters.push(Parameter {
+                    name: childname,
+                    die: childoffset,
+                    integer,
+                });
+
+                if !integer {
+                    found = true; // Really, we've found it to be invalid
+                }
+                continue;
+            }
+            gimli::DW_TAG_variable => (),
+            _ => continue,
+        }
+
+        match childname.as_deref().and_then(saved_args_version) {
+            Some(x) => version = Some(x),
+            None => continue, // Nameless variables exist, but we needn't worry
+        };
+
+        // Our symbol is decidedly unreal
+        if childentry.attr_value(gimli::DW_AT_artificial)?.is_none() {
+            report
+                .diagnostics
+                .push(diagnostic(childoffset, DiagnosticKind::NotArtificial));
+        }
+
+        // A variable optimised away has no location at all
+        let Some(location) = childentry.attr_value(gimli::DW_AT_location)? else {
+            report.diagnostics.push(diagnostic(
+                childoffset,
+                DiagnosticKind::UnexpectedLocation("none".to_string()),
+            ));
+            continue;
+        };
+
+        if let Some(e) = location.exprloc_value() {
+            let mut ops = e.operations(unit.encoding());
+
+            if let Some(op) = ops.next()? {
+                match op {
+                    gimli::read::Operation::FrameOffset { offset: off } => {
+                        status = Some(Status::Saved {
+                            offset: base.offset + off,
+                        });
+                    }
+                    x => {
+                        report.diagnostics.push(diagnostic(
+                            childoffset,
+                            DiagnosticKind::UnexpectedLocation(format!("{x:?}")),
+                        ));
+                        continue;
+                    }
+                }
+            }
+
+            if ops.count()? != 0 {
+                report.diagnostics.push(diagnostic(
+                    childoffset,
+                    DiagnosticKind::ExtraLocationOperations,
+                ));
+                continue;
+            }
+
+            found = true;
+        }
+    }
+
+    let nparams = parameters.iter().filter(|x| x.integer).count();
+
+    // Before the plugin, `-msave-args` saved arguments in the prologue
+    let code = function_code(object, &name, &range);
+    let saves_v0 = match (Arch::of(object), code) {
+        (Ok(Arch::X86_64), Some(code)) if nparams != 0 => {
+            protocol::libsaveargs(code, nparams, struct_return)?
+        }
+        _ => false,
+    };
+    let protocol = match (&status, version) {
+        (Some(Status::Saved { .. }), Some(x)) => Some(Protocol(x)),
+        _ if nparams != 0 && (msave_args || saves_v0) => Some(Protocol::V0),
+        _ => None,
+    };
+
+    if nparams != 0 && !found {
+        if protocol.is_none() {
+            report.diagnostics.push(diagnostic(
+                funcoffset,
+                DiagnosticKind::NoSavedArgs { nparams },
+            ));
+        }
+        status.get_or_insert(Status::Missing);
+    }
+
+    match status {
+        Some(status) => {
+            let mut func = FunctionRecord {
+                name: name.clone(),
+                die: funcoffset,
+                source: source.clone(),
+                range,
+                base,
+                parameters,
+                status,
+                unstored: Vec::new(),
+                clobbered: None,
+                protocol,
+                protocols: Vec::new(),
+            };
+
+            let saving = saving::examine(object, &func, struct_return).context("following code")?;
+            func.unstored = saving.unstored;
+            func.clobbered = saving.problems.iter().find_map(|x| match x.1 {
+                DiagnosticKind::SavedClobbered { pc } => Some(pc),
+                _ => None,
+            });
+
+            if saves_v0 {
+                func.protocols.push(Protocol::V0);
+            }
+            // Only saved arguments are ever stored
+            if let (Some(x), false) = (version, func.trusted().is_empty()) {
+                func.protocols.push(Protocol(x));
+            }
+
+            report.functions.push(func);
+            report.diagnostics.extend(
+                saving
+                    .problems
+                    .into_iter()
+                    .map(|(die, kind)| diagnostic(die, kind)),
+            );
+        }
+        None if parameters.is_empty() => report.skipped.push(skipped(Outcome::NoParams)),
+        None => report.skipped.push(skipped(Outcome::NonIntegerParams)),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use gimli::write::{self, Address, AttributeValue, EndianVec, Expression, Sections};
+    use gimli::LittleEndian;
+    use object::elf::ET_EXEC;
+
+    use super::*;
+    use crate::elf;
+
+    /// An executable whose DWARF has one function, `f(int)`, whose saved
+    /// argument variable is named `variable`, and has no location
+    fn object(variable: &str) -> Vec<u8> {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 8,
+        };
+        let mut dwarf = write::Dwarf::new();
+        let id = dwarf
+            .units
+            .add(write::Unit::new(encoding, write::LineProgram::none()));
+        let unit = dwarf.units.get_mut(id);
+
+        let int = unit.add(unit.root(), gimli::DW_TAG_base_type);
+        let entry = unit.get_mut(int);
+        entry.set(
+            gimli::DW_AT_encoding,
+            AttributeValue::Encoding(gimli::DW_ATE_signed),
+        );
+        entry.set(gimli::DW_AT_byte_size, AttributeValue::Data1(4));
+
+        let func = unit.add(unit.root(), gimli::DW_TAG_subprogram);
+        let mut rbp = Expression::new();
+        rbp.op_breg(gimli::X86_64::RBP, 16);
+        let entry = unit.get_mut(func);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(b"f".to_vec()));
+        entry.set(
+            gimli::DW_AT_decl_file,
+            AttributeValue::String(b"f.c".to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(0)),
+        );
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Address(Address::Constant(0x10)),
+        );
+        entry.set(gimli::DW_AT_frame_base, AttributeValue::Exprloc(rbp));
+
+        let param = unit.add(func, gimli::DW_TAG_formal_parameter);
+        let entry = unit.get_mut(param);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(b"x".to_vec()));
+        entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(int));
+
+        let var = unit.add(func, gimli::DW_TAG_variable);
+        let entry = unit.get_mut(var);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(variable.into()));
+        entry.set(gimli::DW_AT_artificial, AttributeValue::Flag(true));
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut contents = Vec::new();
+        sections
+            .for_each(|id, x| {
+                if !x.slice().is_empty() {
+                    contents.push((id.name(), x.slice().to_vec()));
+                }
+                Ok::<_, gimli::Error>(())
+            })
+            .unwrap();
+
+        let contents = contents
+            .iter()
+            .map(|(name, x)| (*name, x.as_slice()))
+            .collect::<Vec<_>>();
+        elf::tests::object_with(ET_EXEC, &[0xc3; 0x10], &contents)
+    }
+
+    #[test]
+    fn no_location() {
+        for name in [SAVED_ARGS_NAME, "__illumos_saved_args_v2__"] {
+            let data = object(name);
+            let report = scan_object(&object::File::parse(&*data).unwrap());
+
+            assert!(report.is_complete());
+            assert_eq!(report.functions.len(), 1);
+            assert_eq!(report.functions[0].status, Status::Missing);
+            assert!(report
+                .diagnostics
+                .iter()
+                .any(|x| matches!(&x.kind, DiagnosticKind::UnexpectedLocation(x) if x == "none")));
+        }
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(saved_args_version(SAVED_ARGS_NAME), Some(1));
+        assert_eq!(saved_args_version("__illumos_saved_args_v0__"), Some(0));
+        assert_eq!(saved_args_version("__illumos_saved_args_v12__"), Some(12));
+        assert_eq!(saved_args_version("__illumos_saved_args_v__"), None);
+        assert_eq!(saved_args_version("__illumos_saved_args_v+1__"), None);
+        assert_eq!(saved_args_version("__illumos_saved_args_v1"), None);
+        assert_eq!(saved_args_version("saved_args"), None);
+    }
+
+    #[test]
+    fn producers() {
+        assert!(compiled_with_msave_args(
+            "GNU C17 10.4.0 -m64 -msave-args -gdwarf-2 -O2"
+        ));
+        assert!(!compiled_with_msave_args("GNU C17 10.4.0 -m64 -O2"));
+        assert!(!compiled_with_msave_args(
+            "GNU C17 10.4.0 -mno-save-args -msave-args-ish"
+        ));
+    }
+}
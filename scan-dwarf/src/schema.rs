@@ -0,0 +1,468 @@
+//! The stable form of our results, as written by `--json`
+//!
+//! Each line of output is one [`Record`], tagged with the version of this
+//! schema that wrote it.  Fields may be added without changing the version,
+//! so readers should ignore fields they do not know, but any other change
+//! requires [`SCHEMA_VERSION`] be incremented.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{CodeRange, Outcome, ScanReport, Severity, Source, Status};
+
+/// The version of the schema written by this scanner
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The version from which coverage counts only where every slot is stored
+const COVERAGE_VERSION: u32 = 3;
+
+/// A formal parameter of a function
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Parameter {
+    pub name: Option<String>,
+    /// True if this parameter is passed in an integer register
+    pub integer: bool,
+}
+
+/// Whether a function saved its arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FunctionStatus {
+    Saved,
+    Missing,
+}
+
+/// A function we examined
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Function {
+    /// The object containing the function
+    pub path: String,
+    pub name: String,
+    /// Offset of the function's entry in `.debug_info`
+    pub die: u64,
+    /// Name of the compilation unit containing the function
+    pub unit: Option<String>,
+    pub comp_dir: Option<String>,
+    /// File and line on which the function is declared
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    /// Address of the first instruction of the function
+    pub address: u64,
+    pub size: u64,
+    pub parameters: Vec<Parameter>,
+    /// The number of parameters which should be saved
+    pub nparams: usize,
+    pub status: FunctionStatus,
+    /// Either `analysed` or `missing-array`, absent before version 2
+    #[serde(default)]
+    pub outcome: Option<Outcome>,
+    /// The DWARF number of the register relative to which the frame base is
+    /// described
+    pub register: u16,
+    /// Offset of the saved arguments from `register`
+    pub offset: Option<i64>,
+    /// Where the frame base is known, and is not, relative to `address`
+    pub valid: Vec<CodeRange>,
+    pub invalid: Vec<CodeRange>,
+    /// Where, of `valid`, not every slot has been stored, absent before
+    /// version 3
+    #[serde(default)]
+    pub unstored: Vec<CodeRange>,
+    /// Percentage of the function in which the frame base is known, and
+    /// from version 3, every slot has been stored
+    pub coverage: f64,
+    /// The version of the protocol by which the function saves its
+    /// arguments, 0 being `-msave-args` and 1 the saved argument variable,
+    /// absent if it does not
+    #[serde(default)]
+    pub protocol: Option<u32>,
+    /// The version of each protocol by which a debugger would recover the
+    /// arguments
+    #[serde(default)]
+    pub protocols: Vec<u32>,
+    /// The version of the schema in which this was written, which is that
+    /// of its line
+    #[serde(skip)]
+    pub version: u32,
+}
+
+impl Function {
+    /// True if the coverage of this and `other` mean the same, so may be
+    /// compared
+    pub fn same_coverage(&self, other: &Function) -> bool {
+        (self.version >= COVERAGE_VERSION) == (other.version >= COVERAGE_VERSION)
+    }
+
+    /// Where the function came from
+    pub fn source(&self) -> Source {
+        Source {
+            unit: self.unit.clone(),
+            comp_dir: self.comp_dir.clone(),
+            file: self.file.clone(),
+            line: self.line,
+        }
+    }
+}
+
+/// A function which is not described by a [`Function`] record, and why
+///
+/// Introduced in version 2.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Skipped {
+    pub path: String,
+    pub name: Option<String>,
+    pub die: u64,
+    pub unit: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    pub outcome: Outcome,
+}
+
+/// A problem we found
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Diagnostic {
+    /// The object in which it was found
+    pub path: String,
+    /// Offset in `.debug_info` of the entry it concerns
+    pub die: Option<u64>,
+    /// The function it concerns
+    pub function: Option<String>,
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub comp_dir: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    /// The stable name of this kind of diagnostic, empty if not known
+    #[serde(default)]
+    pub code: String,
+    #[serde(default)]
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Where the function it concerns came from
+    pub fn source(&self) -> Source {
+        Source {
+            unit: self.unit.clone(),
+            comp_dir: self.comp_dir.clone(),
+            file: self.file.clone(),
+            line: self.line,
+        }
+    }
+}
+
+/// Totals for one object
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Summary {
+    pub path: String,
+    /// Functions which saved their arguments
+    pub saved: usize,
+    /// Functions with integer parameters which did not
+    pub missing: usize,
+    pub diagnostics: usize,
+    /// How many of `diagnostics` are errors
+    #[serde(default)]
+    pub errors: usize,
+    /// False if we gave up part way through the object
+    pub complete: bool,
+    /// The number of functions with each outcome, absent before version 2
+    #[serde(default)]
+    pub outcomes: BTreeMap<Outcome, usize>,
+}
+
+/// One line of output
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Record {
+    Function(Function),
+    Skipped(Skipped),
+    Diagnostic(Diagnostic),
+    Summary(Summary),
+}
+
+impl Record {
+    /// The object this record describes
+    pub fn path(&self) -> &str {
+        match self {
+            Record::Function(x) => &x.path,
+            Record::Skipped(x) => &x.path,
+            Record::Diagnostic(x) => &x.path,
+            Record::Summary(x) => &x.path,
+        }
+    }
+
+    pub fn path_mut(&mut self) -> &mut String {
+        match self {
+            Record::Function(x) => &mut x.path,
+            Record::Skipped(x) => &mut x.path,
+            Record::Diagnostic(x) => &mut x.path,
+            Record::Summary(x) => &mut x.path,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Line {
+    version: u32,
+    #[serde(flatten)]
+    record: Record,
+}
+
+/// The records describing `report`, for the object at `path`
+pub fn records(path: &str, report: &ScanReport) -> Vec<Record> {
+    let mut ret = Vec::with_capacity(
+        report.functions.len() + report.skipped.len() + report.diagnostics.len() + 1,
+    );
+
+    for func in &report.functions {
+        let (status, offset) = match func.status {
+            Status::Saved { offset } => (FunctionStatus::Saved, Some(offset)),
+            Status::Missing => (FunctionStatus::Missing, None),
+        };
+
+        ret.push(Record::Function(Function {
+            path: path.to_string(),
+            name: func.name.clone(),
+            die: func.die,
+            unit: func.source.unit.clone(),
+            comp_dir: func.source.comp_dir.clone(),
+            file: func.source.file.clone(),
+            line: func.source.line,
+            address: func.range.start,
+            size: func.range.end - func.range.start,
+            parameters: func
+                .parameters
+                .iter()
+                .map(|x| Parameter {
+                    name: x.name.clone(),
+                    integer: x.integer,
+                })
+                .collect(),
+            nparams: func.nparams(),
+            status,
+            outcome: Some(func.outcome()),
+            register: func.base.register.0,
+            offset,
+            valid: func.base.valid.clone(),
+            invalid: func.invalid(),
+            unstored: func.unstored.clone(),
+            coverage: func.coverage(),
+            protocol: func.protocol.map(|x| x.0),
+            protocols: func.protocols.iter().map(|x| x.0).collect(),
+            version: SCHEMA_VERSION,
+        }));
+    }
+
+    for skip in &report.skipped {
+        ret.push(Record::Skipped(Skipped {
+            path: path.to_string(),
+            name: skip.name.clone(),
+            die: skip.die,
+            unit: skip.source.unit.clone(),
+            file: skip.source.file.clone(),
+            line: skip.source.line,
+            outcome: skip.outcome,
+        }));
+    }
+
+    for diag in &report.diagnostics {
+        ret.push(Record::Diagnostic(Diagnostic {
+            path: path.to_string(),
+            die: diag.die,
+            function: diag.function.clone(),
+            unit: diag.source.unit.clone(),
+            comp_dir: diag.source.comp_dir.clone(),
+            file: diag.source.file.clone(),
+            line: diag.source.line,
+            code: diag.code().to_string(),
+            severity: diag.severity,
+            message: diag.to_string(),
+        }));
+    }
+
+    ret.push(Record::Summary(Summary {
+        path: path.to_string(),
+        saved: report
+            .functions
+            .iter()
+            .filter(|x| matches!(x.status, Status::Saved { .. }))
+            .count(),
+        missing: report
+            .functions
+            .iter()
+            .filter(|x| x.status == Status::Missing)
+            .count(),
+        diagnostics: report.diagnostics.len(),
+        errors: report
+            .diagnostics
+            .iter()
+            .filter(|x| x.severity == Severity::Error)
+            .count(),
+        complete: report.is_complete(),
+        outcomes: report.outcomes().fold(BTreeMap::new(), |mut acc, x| {
+            *acc.entry(x).or_default() += 1;
+            acc
+        }),
+    }));
+
+    ret
+}
+
+/// Write `record` as a line
+pub fn write(w: &mut dyn Write, record: &Record) -> io::Result<()> {
+    let line = Line {
+        version: SCHEMA_VERSION,
+        record: record.clone(),
+    };
+
+    serde_json::to_writer(&mut *w, &line)?;
+    writeln!(w)
+}
+
+/// Read each record from `r`
+pub fn read(r: impl BufRead) -> Result<Vec<Record>> {
+    let mut ret = Vec::new();
+
+    for (n, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut line: Line =
+            serde_json::from_str(&line).with_context(|| format!("parsing line {}", n + 1))?;
+        if line.version > SCHEMA_VERSION {
+            return Err(anyhow!(
+                "line {} has schema version {}, but we only understand {SCHEMA_VERSION}",
+                n + 1,
+                line.version
+            ));
+        }
+
+        if let Record::Function(x) = &mut line.record {
+            x.version = line.version;
+        }
+        ret.push(line.record);
+    }
+
+    Ok(ret)
+}
+
+/// Read each record from the file at `path`
+pub fn read_file(path: &Path) -> Result<Vec<Record>> {
+    let file = fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    read(io::BufReader::new(file)).with_context(|| format!("reading {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::tests::function;
+    use crate::{Diagnostic as Found, DiagnosticKind, Source};
+
+    fn report() -> ScanReport {
+        let kind = DiagnosticKind::NoSavedArgs { nparams: 2 };
+
+        ScanReport {
+            functions: vec![
+                function("one", 0x1000, 0x1040, Status::Saved { offset: -40 }),
+                function("two", 0x1040, 0x1080, Status::Missing),
+            ],
+            diagnostics: vec![Found {
+                die: Some(0x2080),
+                function: Some("two".to_string()),
+                source: Source {
+                    unit: Some("t.c".to_string()),
+                    comp_dir: Some("/src".to_string()),
+                    file: Some("t.c".to_string()),
+                    line: Some(3),
+                },
+                severity: kind.default_severity(),
+                kind,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let records = records("t", &report());
+        let mut data = Vec::new();
+        for x in &records {
+            write(&mut data, x).unwrap();
+        }
+
+        assert_eq!(read(&data[..]).unwrap(), records);
+
+        let Record::Function(func) = &records[0] else {
+            panic!("not a function: {:?}", records[0]);
+        };
+        assert_eq!(func.version, SCHEMA_VERSION);
+        assert_eq!(func.offset, Some(-40));
+        assert_eq!(func.coverage, 37.5);
+
+        let Record::Diagnostic(diag) = &records[2] else {
+            panic!("not a diagnostic: {:?}", records[2]);
+        };
+        assert_eq!(diag.code, "no-saved-args");
+        assert_eq!(diag.source().path().as_deref(), Some(Path::new("/src/t.c")));
+    }
+
+    #[test]
+    fn versions() {
+        let records = records("t", &report());
+        let mut data = Vec::new();
+        write(&mut data, &records[0]).unwrap();
+        let line = String::from_utf8(data).unwrap();
+
+        // What an older scanner wrote is read as that version
+        let old = line.replace(&format!("\"version\":{SCHEMA_VERSION}"), "\"version\":2");
+        let Record::Function(func) = &read(old.as_bytes()).unwrap()[0] else {
+            panic!("not a function");
+        };
+        assert_eq!(func.version, 2);
+        let Record::Function(new) = &records[0] else {
+            panic!("not a function");
+        };
+        assert!(!func.same_coverage(new));
+
+        // But not that of a newer scanner
+        let new = line.replace(
+            &format!("\"version\":{SCHEMA_VERSION}"),
+            &format!("\"version\":{}", SCHEMA_VERSION + 1),
+        );
+        assert!(read(new.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn absent_fields() {
+        // As written by version 1, with neither outcomes, codes nor severity
+        let lines = concat!(
+            r#"{"version":1,"kind":"function","path":"t","name":"f","die":1,"#,
+            r#""unit":null,"comp_dir":null,"file":null,"line":null,"address":0,"#,
+            r#""size":16,"parameters":[],"nparams":0,"status":"missing","#,
+            r#""register":6,"offset":null,"valid":[],"invalid":[],"coverage":0.0}"#,
+            "\n\n",
+            r#"{"version":1,"kind":"diagnostic","path":"t","die":1,"function":"f","#,
+            r#""unit":null,"file":null,"line":null,"message":"f(): bad"}"#,
+            "\n",
+        );
+
+        let records = read(lines.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        let Record::Function(func) = &records[0] else {
+            panic!("not a function");
+        };
+        assert_eq!((func.version, func.outcome), (1, None));
+        assert!(func.unstored.is_empty());
+        let Record::Diagnostic(diag) = &records[1] else {
+            panic!("not a diagnostic");
+        };
+        assert_eq!((diag.code.as_str(), diag.severity), ("", Severity::Warning));
+    }
+}
@@ -0,0 +1,383 @@
+//! Choosing which functions to examine
+//!
+//! A [`Selector`] is applied as each function is found, before it is
+//! examined, where it can be, so that looking at a few functions in a large
+//! object is cheap.  Only selection by outcome must wait until a function
+//! has been examined.
+
+use anyhow::{anyhow, Context, Result};
+use globset::GlobMatcher;
+use regex::Regex;
+
+use crate::dwarf::attr_to_string;
+use crate::range::without;
+use crate::{CodeRange, Outcome, ScanReport, Source, Status};
+
+/// Which functions to examine, where every criterion given must be met
+#[derive(Debug, Clone, Default)]
+pub struct Selector {
+    /// A pattern the function's name must match
+    pub name: Option<Regex>,
+    /// The function's linkage (or symbol) name
+    pub linkage_name: Option<String>,
+    /// A glob the path of the function's source file must match
+    pub source: Option<GlobMatcher>,
+    /// A glob the name of the function's compilation unit must match
+    pub unit: Option<GlobMatcher>,
+    /// Ranges of addresses, at least one of which the function must overlap
+    pub addresses: Vec<CodeRange>,
+    /// The outcomes of which the function must have one
+    pub outcomes: Vec<Outcome>,
+    /// Select only functions which saved their arguments, but in which they
+    /// are somewhere not to be trusted, as the frame base is not known, a
+    /// slot is not stored, or the slots may be written once stored
+    pub invalid: bool,
+}
+
+/// Parse `s` as an address, a hex number with or without `0x`, as a
+/// debugger would
+pub(crate) fn parse_address(s: &str) -> Result<u64> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"));
+    u64::from_str_radix(digits.unwrap_or(s), 16).with_context(|| format!("invalid address: {s}"))
+}
+
+impl Selector {
+    /// True if this selects every function
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.linkage_name.is_none()
+            && self.source.is_none()
+            && self.unit.is_none()
+            && self.addresses.is_empty()
+            && self.outcomes.is_empty()
+            && !self.invalid
+    }
+
+    /// Parse `s` as an address, `START-END` or `START+LENGTH`, each in hex,
+    /// and select functions overlapping it
+    pub fn add_address(&mut self, s: &str) -> Result<()> {
+        let range = if let Some((start, end)) = s.split_once('-') {
+            CodeRange {
+                start: parse_address(start)?,
+                end: parse_address(end)?,
+            }
+        } else if let Some((start, len)) = s.split_once('+') {
+            let start = parse_address(start)?;
+            CodeRange {
+                start,
+                end: start.saturating_add(parse_address(len)?),
+            }
+        } else {
+            let start = parse_address(s)?;
+            CodeRange {
+                start,
+                end: start.saturating_add(1),
+            }
+        };
+
+        if range.start >= range.end {
+            return Err(anyhow!("empty address range: {s}"));
+        }
+
+        self.addresses.push(range);
+        Ok(())
+    }
+
+    /// True if functions in the compilation unit named `name` may be
+    /// selected
+    pub(crate) fn accepts_unit(&self, name: Option<&str>) -> bool {
+        match &self.unit {
+            None => true,
+            Some(glob) => name.is_some_and(|x| glob.is_match(x)),
+        }
+    }
+
+    /// True if the function `entry`, from `source`, may be selected
+    pub(crate) fn accepts_function<T: gimli::Reader>(
+        &self,
+        entry: &gimli::DebuggingInformationEntry<T>,
+        dwarf: &gimli::Dwarf<T>,
+        unit: &gimli::Unit<T>,
+        source: &Source,
+    ) -> Result<bool> {
+        let string = |attr| -> Result<Option<String>> {
+            Ok(entry
+                .attr_value(attr)?
+                .and_then(|x| attr_to_string(x, dwarf, unit)))
+        };
+
+        if let Some(re) = &self.name {
+            if !string(gimli::DW_AT_name)?.is_some_and(|x| re.is_match(&x)) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(wanted) = &self.linkage_name {
+            let name = match string(gimli::DW_AT_linkage_name)? {
+                Some(x) => Some(x),
+                None => string(gimli::DW_AT_MIPS_linkage_name)?,
+            };
+
+            // C functions have no linkage name, theirs is their name
+            let name = match name {
+                Some(x) => Some(x),
+                None => string(gimli::DW_AT_name)?,
+            };
+
+            if name.as_ref() != Some(wanted) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(glob) = &self.source {
+            if !source.path().is_some_and(|x| glob.is_match(x)) {
+                return Ok(false);
+            }
+        }
+
+        if !self.addresses.is_empty() {
+            // A function whose extent we cannot know is at no address
+            let Ok(Some(range)) = CodeRange::from_function_die(entry) else {
+                return Ok(false);
+            };
+
+            if !self
+                .addresses
+                .iter()
+                .any(|x| x.start < range.end && range.start < x.end)
+            {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// True if what was found of a single function, in `report`, is to be
+    /// kept
+    pub(crate) fn accepts_result(&self, report: &ScanReport) -> bool {
+        if !self.outcomes.is_empty() && !report.outcomes().any(|x| self.outcomes.contains(&x)) {
+            return false;
+        }
+
+        if self.invalid {
+            return report.functions.iter().any(|x| {
+                // What is trusted is relative to the start of the function
+                let whole = CodeRange {
+                    start: 0,
+                    end: x.range.end - x.range.start,
+                };
+                matches!(x.status, Status::Saved { .. })
+                    && !without(&[whole], &x.trusted()).is_empty()
+            });
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use gimli::write::{self, Address, AttributeValue, EndianVec, Sections};
+    use gimli::{EndianSlice, LittleEndian};
+    use globset::Glob;
+
+    use super::*;
+    use crate::index::tests::function;
+
+    /// The sections of DWARF with one function, `add` linked as `_add`, from
+    /// `lib/add.c`, at [0x1000, 0x1040)
+    fn sections() -> HashMap<gimli::SectionId, Vec<u8>> {
+        let encoding = gimli::Encoding {
+            format: gimli::Format::Dwarf32,
+            version: 4,
+            address_size: 8,
+        };
+        let mut dwarf = write::Dwarf::new();
+        let id = dwarf
+            .units
+            .add(write::Unit::new(encoding, write::LineProgram::none()));
+        let unit = dwarf.units.get_mut(id);
+        let func = unit.add(unit.root(), gimli::DW_TAG_subprogram);
+        let entry = unit.get_mut(func);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(b"add".to_vec()));
+        entry.set(
+            gimli::DW_AT_linkage_name,
+            AttributeValue::String(b"_add".to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_decl_file,
+            AttributeValue::String(b"lib/add.c".to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(0x1000)),
+        );
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Address(Address::Constant(0x1040)),
+        );
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut ret = HashMap::new();
+        sections
+            .for_each(|id, x| {
+                ret.insert(id, x.slice().to_vec());
+                Ok::<_, gimli::Error>(())
+            })
+            .unwrap();
+        ret
+    }
+
+    /// True if `selector` accepts the function in `sections`, compiled in
+    /// `/src`
+    fn accepts(selector: &Selector, sections: &HashMap<gimli::SectionId, Vec<u8>>) -> bool {
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_> {
+            let data = sections.get(&id).map(Vec::as_slice).unwrap_or(&[]);
+            Ok(EndianSlice::new(data, LittleEndian))
+        })
+        .unwrap();
+        let unit = dwarf.unit(dwarf.units().next().unwrap().unwrap()).unwrap();
+        let mut entries = unit.entries();
+        entries.next_dfs().unwrap();
+        let (_, entry) = entries.next_dfs().unwrap().unwrap();
+
+        let source = Source {
+            comp_dir: Some("/src".to_string()),
+            file: Some("lib/add.c".to_string()),
+            ..Default::default()
+        };
+        selector
+            .accepts_function(entry, &dwarf, &unit, &source)
+            .unwrap()
+    }
+
+    fn glob(pattern: &str) -> Option<GlobMatcher> {
+        Some(Glob::new(pattern).unwrap().compile_matcher())
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_address("1000").unwrap(), 0x1000);
+        assert_eq!(parse_address("0x1000").unwrap(), 0x1000);
+        assert_eq!(parse_address("0XfF").unwrap(), 0xff);
+        assert!(parse_address("").is_err());
+        assert!(parse_address("0x").is_err());
+        assert_eq!(parse_address("add").unwrap(), 0xadd);
+        assert!(parse_address("main").is_err());
+
+        let mut selector = Selector::default();
+        assert!(selector.is_empty());
+        for x in ["1000", "0x1000-0x1010", "1000+10"] {
+            selector.add_address(x).unwrap();
+        }
+        assert!(!selector.is_empty());
+        assert_eq!(
+            selector.addresses,
+            vec![
+                CodeRange {
+                    start: 0x1000,
+                    end: 0x1001
+                },
+                CodeRange {
+                    start: 0x1000,
+                    end: 0x1010
+                },
+                CodeRange {
+                    start: 0x1000,
+                    end: 0x1010
+                },
+            ]
+        );
+
+        assert!(selector.add_address("1010-1000").is_err());
+        assert!(selector.add_address("1000+0").is_err());
+        assert!(selector.add_address("1000-").is_err());
+        assert!(selector.add_address("fffffffffffffff0+100").is_ok());
+        assert!(selector.add_address("ffffffffffffffff").is_err());
+    }
+
+    #[test]
+    fn functions() {
+        let sections = sections();
+        let with = |f: fn(&mut Selector)| {
+            let mut selector = Selector::default();
+            f(&mut selector);
+            accepts(&selector, &sections)
+        };
+
+        assert!(with(|_| ()));
+        assert!(with(|x| x.name = Some(Regex::new("^ad").unwrap())));
+        assert!(!with(|x| x.name = Some(Regex::new("^sub").unwrap())));
+        assert!(with(|x| x.linkage_name = Some("_add".to_string())));
+        assert!(!with(|x| x.linkage_name = Some("add".to_string())));
+        // Matched by its path joined to where it was compiled
+        assert!(with(|x| x.source = glob("/src/lib/*.c")));
+        assert!(!with(|x| x.source = glob("lib/*.c")));
+        assert!(with(|x| x.add_address("103f").unwrap()));
+        assert!(with(|x| x.add_address("f00-1001").unwrap()));
+        assert!(!with(|x| x.add_address("1040+10").unwrap()));
+        assert!(!with(|x| {
+            x.name = Some(Regex::new("add").unwrap());
+            x.add_address("2000").unwrap();
+        }));
+    }
+
+    #[test]
+    fn units() {
+        let selector = Selector {
+            unit: glob("*.c"),
+            ..Default::default()
+        };
+        assert!(selector.accepts_unit(Some("add.c")));
+        assert!(!selector.accepts_unit(Some("add.s")));
+        assert!(!selector.accepts_unit(None));
+        assert!(Selector::default().accepts_unit(None));
+    }
+
+    #[test]
+    fn results() {
+        let saved = || function("f", 0x1000, 0x1040, Status::Saved { offset: -40 });
+        let report = |func| ScanReport {
+            functions: vec![func],
+            ..Default::default()
+        };
+
+        let outcomes = Selector {
+            outcomes: vec![Outcome::MissingArray],
+            ..Default::default()
+        };
+        assert!(!outcomes.accepts_result(&report(saved())));
+        assert!(outcomes.accepts_result(&report(function("f", 0x1000, 0x1040, Status::Missing))));
+
+        let invalid = Selector {
+            invalid: true,
+            ..Default::default()
+        };
+        assert!(!invalid.accepts_result(&report(function("f", 0x1000, 0x1040, Status::Missing))));
+
+        // Not valid before +0x4, nor stored before +0x8, nor after +0x20
+        assert!(invalid.accepts_result(&report(saved())));
+
+        // Valid and stored throughout
+        let mut func = saved();
+        func.base.valid = vec![CodeRange {
+            start: 0,
+            end: 0x40,
+        }];
+        func.unstored = vec![];
+        assert!(!invalid.accepts_result(&report(func.clone())));
+
+        // Unstored in part
+        func.unstored = vec![CodeRange { start: 0, end: 4 }];
+        assert!(invalid.accepts_result(&report(func.clone())));
+
+        // Or written once stored
+        func.unstored = vec![];
+        func.clobbered = Some(0x1010);
+        assert!(invalid.accepts_result(&report(func)));
+    }
+}
@@ -0,0 +1,133 @@
+//! Totals across the functions of many objects
+//!
+//! Functions are counted overall, per object, and per directory of source,
+//! so that the parts of a tree where arguments are least often found stand
+//! out.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::{Outcome, ScanReport, Source, Status};
+
+/// How many functions did, and did not, save their arguments
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Counts {
+    /// Functions which saved their arguments
+    pub saved: usize,
+    /// Functions with integer parameters which did not
+    pub missing: usize,
+    /// Functions with code of their own which we did not examine, or which
+    /// had nothing to save
+    pub skipped: usize,
+    /// The sum of the coverage of each function which saved its arguments
+    pub coverage: f64,
+}
+
+impl Counts {
+    /// The percentage of the functions which should have saved their
+    /// arguments which did
+    pub fn saved_percent(&self) -> f64 {
+        match self.saved + self.missing {
+            0 => 100.0,
+            n => self.saved as f64 / n as f64 * 100.0,
+        }
+    }
+
+    /// The average coverage of the functions which saved their arguments
+    pub fn mean_coverage(&self) -> f64 {
+        match self.saved {
+            0 => 0.0,
+            n => self.coverage / n as f64,
+        }
+    }
+}
+
+/// Totals across every object added
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Stats {
+    pub totals: Counts,
+    pub diagnostics: usize,
+    /// The number of functions with each outcome
+    pub outcomes: BTreeMap<Outcome, usize>,
+    /// The number of functions with coverage in each 10% bucket, the last
+    /// being for 90% up to and including 100%
+    pub coverage: [usize; 10],
+    /// The number of functions whose arguments are saved at an offset of
+    /// each magnitude, keyed by the smallest power of two not less than it
+    pub offsets: BTreeMap<u64, usize>,
+    pub objects: BTreeMap<String, Counts>,
+    /// Keyed by the directory containing the source of each function
+    pub directories: BTreeMap<String, Counts>,
+}
+
+/// The directory in which the function from `source` was written
+fn directory(source: &Source) -> String {
+    source
+        .path()
+        .as_deref()
+        .and_then(Path::parent)
+        .map(|x| x.display().to_string())
+        .or_else(|| source.comp_dir.clone())
+        .unwrap_or_else(|| String::from("<unknown>"))
+}
+
+impl Stats {
+    /// Count each function in `report`, for the object at `path`
+    pub fn add(&mut self, path: &str, report: &ScanReport) {
+        self.diagnostics += report.diagnostics.len();
+
+        for func in &report.functions {
+            let mut counts = Counts::default();
+
+            match func.status {
+                Status::Saved { offset } => {
+                    let coverage = func.coverage();
+                    let bucket = ((coverage / 10.0) as usize).min(9);
+
+                    counts.saved = 1;
+                    counts.coverage = coverage;
+                    self.coverage[bucket] += 1;
+                    *self
+                        .offsets
+                        .entry(offset.unsigned_abs().next_power_of_two())
+                        .or_default() += 1;
+                }
+                Status::Missing => counts.missing = 1,
+            }
+
+            self.count(path, &func.source, &counts);
+        }
+
+        for outcome in report.outcomes() {
+            *self.outcomes.entry(outcome).or_default() += 1;
+        }
+
+        // Declarations and the like describe code elsewhere, and would only
+        // clutter the roll-ups with the directories of headers.
+        for skip in report.skipped.iter().filter(|x| x.outcome.is_definition()) {
+            self.count(
+                path,
+                &skip.source,
+                &Counts {
+                    skipped: 1,
+                    ..Counts::default()
+                },
+            );
+        }
+    }
+
+    fn count(&mut self, path: &str, source: &Source, counts: &Counts) {
+        let totals = [
+            &mut self.totals,
+            self.objects.entry(path.to_string()).or_default(),
+            self.directories.entry(directory(source)).or_default(),
+        ];
+
+        for x in totals {
+            x.saved += counts.saved;
+            x.missing += counts.missing;
+            x.skipped += counts.skipped;
+            x.coverage += counts.coverage;
+        }
+    }
+}